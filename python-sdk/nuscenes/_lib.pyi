@@ -1,8 +1,490 @@
-from typing import Any, Dict, List, Optional
+from typing import Any, Dict, List, Optional, Sequence, Tuple, Union
+
+import numpy as np
+
+class CategoryKind:
+    ANIMAL: CategoryKind
+    HUMAN_PEDESTRIAN_ADULT: CategoryKind
+    HUMAN_PEDESTRIAN_CHILD: CategoryKind
+    HUMAN_PEDESTRIAN_CONSTRUCTION_WORKER: CategoryKind
+    HUMAN_PEDESTRIAN_PERSONAL_MOBILITY: CategoryKind
+    HUMAN_PEDESTRIAN_POLICE_OFFICER: CategoryKind
+    HUMAN_PEDESTRIAN_STROLLER: CategoryKind
+    HUMAN_PEDESTRIAN_WHEELCHAIR: CategoryKind
+    MOVABLE_OBJECT_BARRIER: CategoryKind
+    MOVABLE_OBJECT_DEBRIS: CategoryKind
+    MOVABLE_OBJECT_PUSHABLE_PULLABLE: CategoryKind
+    MOVABLE_OBJECT_TRAFFICCONE: CategoryKind
+    STATIC_OBJECT_BICYCLE_RACK: CategoryKind
+    VEHICLE_BICYCLE: CategoryKind
+    VEHICLE_BUS_BENDY: CategoryKind
+    VEHICLE_BUS_RIGID: CategoryKind
+    VEHICLE_CAR: CategoryKind
+    VEHICLE_CONSTRUCTION: CategoryKind
+    VEHICLE_EMERGENCY_AMBULANCE: CategoryKind
+    VEHICLE_EMERGENCY_POLICE: CategoryKind
+    VEHICLE_MOTORCYCLE: CategoryKind
+    VEHICLE_TRAILER: CategoryKind
+    VEHICLE_TRUCK: CategoryKind
+    NOISE: CategoryKind
+    FLAT_DRIVEABLE_SURFACE: CategoryKind
+    FLAT_OTHER: CategoryKind
+    FLAT_SIDEWALK: CategoryKind
+    FLAT_TERRAIN: CategoryKind
+    STATIC_MANMADE: CategoryKind
+    STATIC_OTHER: CategoryKind
+    STATIC_VEGETATION: CategoryKind
+    VEHICLE_EGO: CategoryKind
+    @staticmethod
+    def from_name(name: str) -> CategoryKind: ...
+    @staticmethod
+    def members() -> List[CategoryKind]: ...
+    @property
+    def name(self) -> str: ...
+    @property
+    def value(self) -> str: ...
+    @property
+    def root(self) -> str: ...
+    @property
+    def parent(self) -> Optional[str]: ...
+    def is_a(self, prefix: str) -> bool: ...
+
+class AttributeKind:
+    VEHICLE_MOVING: AttributeKind
+    VEHICLE_STOPPED: AttributeKind
+    VEHICLE_PARKED: AttributeKind
+    CYCLE_WITH_RIDER: AttributeKind
+    CYCLE_WITHOUT_RIDER: AttributeKind
+    PEDESTRIAN_SITTING_LYING_DOWN: AttributeKind
+    PEDESTRIAN_STANDING: AttributeKind
+    PEDESTRIAN_MOVING: AttributeKind
+    @staticmethod
+    def from_name(name: str) -> AttributeKind: ...
+    @staticmethod
+    def members() -> List[AttributeKind]: ...
+    @property
+    def name(self) -> str: ...
+    @property
+    def value(self) -> str: ...
+    @property
+    def root(self) -> str: ...
+    @property
+    def parent(self) -> Optional[str]: ...
+    def is_a(self, prefix: str) -> bool: ...
+
+class MapLocation:
+    SINGAPORE_ONENORTH: MapLocation
+    SINGAPORE_HOLLANDVILLAGE: MapLocation
+    SINGAPORE_QUEENSTOWN: MapLocation
+    BOSTON_SEAPORT: MapLocation
+    @staticmethod
+    def from_name(name: str) -> MapLocation: ...
+    @staticmethod
+    def members() -> List[MapLocation]: ...
+    @property
+    def name(self) -> str: ...
+    @property
+    def value(self) -> str: ...
+    @property
+    def root(self) -> str: ...
+    @property
+    def parent(self) -> Optional[str]: ...
+    def is_a(self, prefix: str) -> bool: ...
+
+class ClassMapping:
+    def __init__(self, name: str, classes: List[str], mapping: Dict[str, str]) -> None: ...
+    @classmethod
+    def detection(cls) -> ClassMapping: ...
+    @classmethod
+    def tracking(cls) -> ClassMapping: ...
+    @classmethod
+    def lidarseg(cls) -> ClassMapping: ...
+    @classmethod
+    def from_file(cls, path: str) -> ClassMapping: ...
+    @property
+    def name(self) -> str: ...
+    @property
+    def classes(self) -> List[str]: ...
+    @property
+    def mapping(self) -> Dict[str, str]: ...
+    def class_id(self, category: str) -> Optional[int]: ...
+    def class_name_of(self, category: str) -> Optional[str]: ...
+    def __len__(self) -> int: ...
+
+class BoxVisibility:
+    ALL: BoxVisibility
+    ANY: BoxVisibility
+    NONE: BoxVisibility
+
+class Boxes:
+    """A batch of 3D boxes, convertible to and from `nuscenes.utils.data_classes.Box`.
+
+    Like `Box`, `translate`, `rotate` and the transform methods modify the boxes in place.
+    """
+
+    def __init__(
+        self,
+        centers: np.ndarray,
+        sizes: np.ndarray,
+        rotations: np.ndarray,
+        velocities: Optional[np.ndarray] = None,
+        labels: Optional[List[Optional[int]]] = None,
+        scores: Optional[List[float]] = None,
+        names: Optional[List[Optional[str]]] = None,
+        tokens: Optional[List[Optional[str]]] = None,
+    ) -> None: ...
+    @classmethod
+    def from_boxes(cls, boxes: List[Any]) -> Boxes: ...
+    def to_boxes(self) -> List[Any]: ...
+    def __len__(self) -> int: ...
+    @property
+    def centers(self) -> np.ndarray: ...
+    @property
+    def wlh(self) -> np.ndarray: ...
+    @property
+    def rotations(self) -> np.ndarray: ...
+    @property
+    def velocities(self) -> np.ndarray: ...
+    @property
+    def yaws(self) -> np.ndarray: ...
+    @property
+    def labels(self) -> List[Optional[int]]: ...
+    @property
+    def scores(self) -> np.ndarray: ...
+    @property
+    def names(self) -> List[Optional[str]]: ...
+    @property
+    def tokens(self) -> List[Optional[str]]: ...
+    def corners(self, wlh_factor: float = 1.0) -> np.ndarray: ...
+    def bottom_corners(self) -> np.ndarray: ...
+    def in_image(
+        self, intrinsic: np.ndarray, imsize: Tuple[int, int], vis_level: Union[BoxVisibility, int, None] = None
+    ) -> np.ndarray: ...
+    def image_bboxes(self, intrinsic: np.ndarray, imsize: Tuple[int, int]) -> np.ndarray: ...
+    def translate(self, x: Sequence[float]) -> None: ...
+    def rotate(self, quaternion: Sequence[float]) -> None: ...
+    def transform(self, matrix: np.ndarray) -> None: ...
+    def transform_by_pose(self, translation: Sequence[float], rotation: Sequence[float], inverse: bool = False) -> None: ...
+
+class LidarPoints:
+    """Memory mapping of a .pcd.bin file, the base object of the arrays returned by `Tables.load_lidar`."""
+
+class MapMask:
+    """Semantic prior of a map, as `nuscenes.utils.map_mask.MapMask`. The image is read on first use.
+
+    As with Pillow, `mask()` keeps the channels of color images, as an (H, W, C) array. Dilated masks and the other
+    queries use their first channel.
+    """
+
+    def __init__(self, img_file: str, resolution: float = 0.1) -> None: ...
+    @property
+    def img_file(self) -> str: ...
+    @property
+    def resolution(self) -> float: ...
+    @property
+    def foreground(self) -> int: ...
+    @property
+    def background(self) -> int: ...
+    @property
+    def transform_matrix(self) -> np.ndarray: ...
+    def mask(self, dilation: float = 0.0) -> np.ndarray: ...
+    def is_on_mask(self, x: Any, y: Any, dilation: float = 0.0) -> np.ndarray: ...
+    def to_pixel_coords(self, x: Any, y: Any) -> Tuple[np.ndarray, np.ndarray]: ...
+    def distance(self, x: Any, y: Any) -> np.ndarray: ...
+    def patch(
+        self,
+        x: float,
+        y: float,
+        yaw: float,
+        size: Tuple[float, float],
+        resolution: Optional[float] = None,
+        dilation: float = 0.0,
+    ) -> np.ndarray: ...
+
+class VectorMap:
+    """Vector map of a location from the map expansion, as `nuscenes.map_expansion.map_api.NuScenesMap`."""
+
+    def __init__(self, dataroot: str = "/data/sets/nuscenes", map_name: str = "singapore-onenorth") -> None: ...
+    @property
+    def map_name(self) -> str: ...
+    @property
+    def version(self) -> str: ...
+    @property
+    def canvas_edge(self) -> Tuple[float, float]: ...
+    @property
+    def layer_names(self) -> List[str]: ...
+    @property
+    def non_geometric_layers(self) -> List[str]: ...
+    @property
+    def non_geometric_polygon_layers(self) -> List[str]: ...
+    @property
+    def non_geometric_line_layers(self) -> List[str]: ...
+    def layer_tokens(self, layer_name: str) -> List[str]: ...
+    def getind(self, layer_name: str, token: str) -> int: ...
+    def get(self, layer_name: str, token: str) -> Dict[str, Any]: ...
+    def extract_polygon(self, polygon_token: str) -> Tuple[np.ndarray, List[np.ndarray]]: ...
+    def extract_line(self, line_token: str) -> np.ndarray: ...
+    def get_bounds(self, layer_name: str, token: str) -> Tuple[float, float, float, float]: ...
+    def is_record_in_patch(
+        self, layer_name: str, token: str, box_coords: Tuple[float, float, float, float], mode: str = "intersect"
+    ) -> bool: ...
+    def get_records_in_patch(
+        self,
+        box_coords: Tuple[float, float, float, float],
+        layer_names: Optional[List[str]] = None,
+        mode: str = "intersect",
+    ) -> Dict[str, List[str]]: ...
+    def get_records_in_radius(
+        self, x: float, y: float, radius: float, layer_names: Optional[List[str]] = None, mode: str = "intersect"
+    ) -> Dict[str, List[str]]: ...
+    def layers_on_point(self, x: float, y: float, layer_names: Optional[List[str]] = None) -> Dict[str, str]: ...
+    def record_on_point(self, x: float, y: float, layer_name: str) -> str: ...
+    def get_closest_lane(self, x: float, y: float, radius: float = 5) -> str: ...
+    def get_outgoing_lane_ids(self, lane_token: str) -> List[str]: ...
+    def get_incoming_lane_ids(self, lane_token: str) -> List[str]: ...
+    def get_arcline_path(self, lane_token: str) -> List[Dict[str, Any]]: ...
+    def length_of_lane(self, lane_token: str) -> float: ...
+    def discretize_lanes(self, tokens: List[str], resolution_meters: float) -> Dict[str, np.ndarray]: ...
+    def discretize_centerlines(self, resolution_meters: float) -> List[np.ndarray]: ...
+    def reachable_lanes(self, lane_token: str, hops: int, direction: str = "outgoing") -> Dict[str, int]: ...
+    def shortest_lane_route(self, start_token: str, goal_token: str) -> Optional[Tuple[List[str], float]]: ...
+    def get_map_mask(
+        self,
+        patch_box: Optional[Tuple[float, float, float, float]],
+        patch_angle: float,
+        layer_names: Optional[List[str]] = None,
+        canvas_size: Optional[Tuple[int, int]] = (100, 100),
+    ) -> np.ndarray: ...
+    def rasterize(
+        self,
+        patch_box: Optional[Tuple[float, float, float, float]],
+        patch_angle: float,
+        layer_names: Optional[List[str]] = None,
+        canvas_size: Optional[Tuple[int, int]] = (100, 100),
+    ) -> Dict[str, Any]: ...
+
+def voxelize(
+    points: np.ndarray,
+    point_cloud_range: Sequence[float],
+    voxel_size: Sequence[float],
+    max_points: int = 32,
+    max_voxels: int = 20000,
+) -> Tuple[np.ndarray, np.ndarray, np.ndarray]: ...
+def transform_matrix(
+    translation: Sequence[float] = ..., rotation: Sequence[float] = ..., inverse: bool = False
+) -> np.ndarray: ...
+def view_points(points: np.ndarray, view: np.ndarray, normalize: bool) -> np.ndarray: ...
+def quaternion_yaw(rotation: Sequence[float]) -> float: ...
+def quaternion_slerp(q0: Sequence[float], q1: Sequence[float], t: float) -> np.ndarray: ...
 
 class Tables:
-    def __init__(self, version: str, dataroot: str) -> None: ...
+    def __init__(
+        self,
+        version: str,
+        dataroot: str,
+        annotation_fields: Optional[List[str]] = None,
+        class_mapping: Optional[Union[str, ClassMapping]] = None,
+    ) -> None:
+        """
+        :param annotation_fields: Denormalized sample annotation fields to compute, out of "category_name",
+            "attribute_names" and "velocity". "category_name" is always computed, and "attribute_names" by default.
+            "velocity" adds the "velocity" and "velocity_ego" of `box_velocity` with the default `max_time_diff`, at
+            the cost of a pass over all annotations when loading.
+        :param class_mapping: Class mapping for the "class_id" and "class_name" of sample annotations. Either a
+            ClassMapping, one of "detection", "tracking" and "lidarseg", or the path to a .json or .toml file.
+        """
+    @property
+    def class_mapping(self) -> Optional[ClassMapping]: ...
+    def label_table(self, mapping: Optional[ClassMapping] = None, fill: Optional[int] = None) -> np.ndarray: ...
     def get(self, table: str, token: str) -> Dict[str, Any]: ...
+    def instance_track(self, instance_token: str) -> List[Dict[str, Any]]: ...
+    def instance_tracks(
+        self, instance_tokens: Optional[List[str]] = None
+    ) -> Dict[str, Tuple[np.ndarray, np.ndarray, np.ndarray]]: ...
+    def scenes_in_log(self, log_token: str) -> List[str]: ...
+    def scenes_in_location(self, location: str) -> List[str]: ...
+    def samples_in_scene(self, scene_token: str) -> List[str]: ...
+    def instances_in_sample(self, sample_token: str) -> List[str]: ...
+    def instances_of_category(self, category_token: str) -> List[str]: ...
+    def sample_data_of_calibrated_sensor(self, calibrated_sensor_token: str) -> List[str]: ...
+    def calibrated_sensors_of_sensor(self, sensor_token: str) -> List[str]: ...
+    def annotations_with_attribute(self, attribute_token: str) -> List[str]: ...
+    def lidarseg_of_sample_data(self, sample_data_token: str) -> Optional[Dict[str, Any]]: ...
+    def panoptic_of_sample_data(self, sample_data_token: str) -> Optional[Dict[str, Any]]: ...
+    def annotations_in_category(self, category: str) -> List[str]: ...
+    def nearest(self, channel: str, timestamp: int, scene: Optional[str] = None) -> Optional[str]: ...
+    def nearest_batch(
+        self, channel: str, timestamps: Sequence[int], scene: Optional[str] = None
+    ) -> List[Optional[str]]: ...
+    def nearest_sample(self, timestamp: int, scene: Optional[str] = None) -> Optional[str]: ...
+    def window(
+        self, start: int, end: int, channels: Optional[List[str]] = None, scene: Optional[str] = None
+    ) -> Dict[str, List[str]]: ...
+    def window_batch(
+        self,
+        starts: Sequence[int],
+        ends: Sequence[int],
+        channels: Optional[List[str]] = None,
+        scene: Optional[str] = None,
+    ) -> List[Dict[str, List[str]]]: ...
+    def sensor_to_ego(self, sample_data_token: str) -> np.ndarray: ...
+    def ego_to_global(self, sample_data_token: str) -> np.ndarray: ...
+    def sensor_to_global(self, sample_data_token: str) -> np.ndarray: ...
+    def global_to_sensor(self, sample_data_token: str) -> np.ndarray: ...
+    def frame_transform(self, sample_data_token: str, source: str, target: str) -> np.ndarray: ...
+    def annotation_boxes(self, sample_annotation_tokens: List[str]) -> Boxes: ...
+    def sample_boxes(self, sample_token: str) -> Boxes: ...
+    def box_velocity(
+        self, sample_annotation_token: str, max_time_diff: float = 1.5, frame: str = "global"
+    ) -> np.ndarray: ...
+    def annotation_velocities(self, frame: str = "global", max_time_diff: float = 1.5) -> np.ndarray: ...
+    def project_points(
+        self, sample_data_token: str, points: np.ndarray, frame: str = "global", min_depth: float = 1.0
+    ) -> Tuple[np.ndarray, np.ndarray, np.ndarray]: ...
+    def project_boxes(
+        self,
+        sample_data_token: str,
+        vis_level: Union[BoxVisibility, int, None] = None,
+        visibilities: Optional[List[str]] = None,
+        categories: Optional[List[str]] = None,
+    ) -> Dict[str, Any]: ...
+    def project_sample_boxes(
+        self,
+        sample_token: str,
+        vis_level: Union[BoxVisibility, int, None] = None,
+        visibilities: Optional[List[str]] = None,
+        categories: Optional[List[str]] = None,
+    ) -> Dict[str, Dict[str, Any]]: ...
+    def load_lidar(self, sample_data_token: str) -> np.ndarray: ...
+    def load_lidar_batch(self, sample_data_tokens: List[str]) -> List[np.ndarray]: ...
+    def load_radar(
+        self,
+        sample_data_token: str,
+        invalid_states: Optional[List[int]] = None,
+        dynprop_states: Optional[List[int]] = None,
+        ambig_states: Optional[List[int]] = None,
+        frame: str = "sensor",
+        structured: bool = True,
+    ) -> np.ndarray: ...
+    def load_radar_batch(
+        self,
+        sample_data_tokens: List[str],
+        invalid_states: Optional[List[int]] = None,
+        dynprop_states: Optional[List[int]] = None,
+        ambig_states: Optional[List[int]] = None,
+        frame: str = "sensor",
+        structured: bool = True,
+    ) -> List[np.ndarray]: ...
+    def load_multisweep(
+        self,
+        sample_token: str,
+        channel: str,
+        ref_channel: str = "LIDAR_TOP",
+        nsweeps: int = 5,
+        min_distance: float = 1.0,
+        invalid_states: Optional[List[int]] = None,
+        dynprop_states: Optional[List[int]] = None,
+        ambig_states: Optional[List[int]] = None,
+    ) -> np.ndarray: ...
+    def load_multisweep_batch(
+        self,
+        sample_tokens: List[str],
+        channel: str,
+        ref_channel: str = "LIDAR_TOP",
+        nsweeps: int = 5,
+        min_distance: float = 1.0,
+        invalid_states: Optional[List[int]] = None,
+        dynprop_states: Optional[List[int]] = None,
+        ambig_states: Optional[List[int]] = None,
+    ) -> List[np.ndarray]: ...
+    def depth_map(self, lidar_token: str, camera_token: str, min_dist: float = 1.0) -> Dict[str, np.ndarray]: ...
+    def sample_depth_maps(
+        self, sample_token: str, channels: Optional[List[str]] = None, min_dist: float = 1.0
+    ) -> Dict[str, Dict[str, np.ndarray]]: ...
+    def paint_points(
+        self, sample_token: str, channels: Optional[List[str]] = None, min_dist: float = 1.0
+    ) -> Dict[str, Any]: ...
+    def rasterize_boxes(
+        self,
+        sample_token: str,
+        x_range: Tuple[float, float] = (-50.0, 50.0),
+        y_range: Tuple[float, float] = (-50.0, 50.0),
+        resolution: float = 0.5,
+        rotation: float = 0.0,
+        mapping: Optional[ClassMapping] = None,
+        fill: Optional[int] = None,
+        visibilities: Optional[List[str]] = None,
+        categories: Optional[List[str]] = None,
+    ) -> Dict[str, Any]: ...
+    def rasterize_lidar(
+        self,
+        sample_token: str,
+        x_range: Tuple[float, float] = (-50.0, 50.0),
+        y_range: Tuple[float, float] = (-50.0, 50.0),
+        resolution: float = 0.5,
+        rotation: float = 0.0,
+        z_range: Optional[Tuple[float, float]] = None,
+    ) -> Dict[str, np.ndarray]: ...
+    def voxelize_lidar(
+        self,
+        sample_token: str,
+        point_cloud_range: Sequence[float],
+        voxel_size: Sequence[float],
+        max_points: int = 32,
+        max_voxels: int = 20000,
+        nsweeps: int = 1,
+        min_distance: float = 1.0,
+    ) -> Tuple[np.ndarray, np.ndarray, np.ndarray]: ...
+    def vector_map(self, log_token: str) -> VectorMap: ...
+    def ego_map_patch(
+        self,
+        sample_data_token: str,
+        size: Tuple[float, float] = (100.0, 100.0),
+        resolution: Optional[float] = None,
+        dilation: float = 0.0,
+    ) -> np.ndarray: ...
+    def ego_map_raster(
+        self,
+        sample_data_token: str,
+        size: Tuple[float, float] = (100.0, 100.0),
+        resolution: float = 0.1,
+        layer_names: Optional[List[str]] = None,
+    ) -> Dict[str, Any]: ...
+    def annotation_map_raster(
+        self,
+        sample_annotation_token: str,
+        size: Tuple[float, float] = (100.0, 100.0),
+        resolution: float = 0.1,
+        layer_names: Optional[List[str]] = None,
+    ) -> Dict[str, Any]: ...
+    def load_lidarseg(self, sample_data_token: str, verify: bool = True) -> np.ndarray: ...
+    def load_panoptic(
+        self, sample_data_token: str, decode: bool = False, verify: bool = True
+    ) -> Union[np.ndarray, Tuple[np.ndarray, np.ndarray]]: ...
+    def box_point_labels(self, sample_data_token: str, wlh_factor: float = 1.0) -> Dict[str, Any]: ...
+    def lidarseg_stats(
+        self, gt_from: str = "lidarseg", split: Optional[str] = None, scenes: Optional[List[str]] = None
+    ) -> Dict[str, Any]: ...
+    def panoptic_stats(self, split: Optional[str] = None, scenes: Optional[List[str]] = None) -> Dict[str, Any]: ...
+    def ego_trajectory(self, token: str) -> Tuple[np.ndarray, np.ndarray, np.ndarray]: ...
+    def ego_poses_at(self, token: str, timestamps: Sequence[int]) -> Tuple[np.ndarray, np.ndarray]: ...
+    def ego_dynamics(
+        self, token: str, timestamps: Optional[Sequence[int]] = None, window: float = 0.1
+    ) -> Dict[str, np.ndarray]: ...
+    def get_boxes(
+        self,
+        sample_data_token: str,
+        frame: str = "global",
+        visibilities: Optional[List[str]] = None,
+        categories: Optional[List[str]] = None,
+    ) -> Boxes: ...
+    def get_boxes_batch(
+        self,
+        sample_data_tokens: List[str],
+        frame: str = "global",
+        visibilities: Optional[List[str]] = None,
+        categories: Optional[List[str]] = None,
+    ) -> List[Boxes]: ...
     @property
     def log(self) -> List[Dict[str, Any]]: ...
     @property
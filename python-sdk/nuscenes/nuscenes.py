@@ -39,20 +39,23 @@ if not PYTHON_VERSION == 3:
 class _RustLoader:
     def __init__(self, version: str, dataroot: str, verbose: bool, map_resolution: float):
         import logging
-        from ._lib import Tables
+        from ._lib import Tables, MapMask as RustMapMask
 
         # The verbosity is controlled via logging
         logger = logging.getLogger("nuscenes")
         logger.setLevel(logging.DEBUG if verbose else logging.INFO)
         self._tables = Tables(version, dataroot)
 
+        # Map records come with their mask at the native resolution, read on first use. Other resolutions get their
+        # own masks, which are just as lazy.
         self._map_masks = dict()
-        for map in self._tables.map:
-            self._map_masks[map["token"]] = MapMask(osp.join(dataroot, map['filename']), resolution=map_resolution)
+        if map_resolution != 0.1:
+            for map in self._tables.map:
+                self._map_masks[map["token"]] = RustMapMask(osp.join(dataroot, map['filename']), map_resolution)
 
     def get(self, table_name: str, token: str) -> Dict[str, Any]:
         entry = self._tables.get(table_name, token)
-        if table_name == "map":
+        if table_name == "map" and entry["token"] in self._map_masks:
             entry["mask"] = self._map_masks[entry["token"]]
         return entry
 
@@ -62,7 +65,9 @@ class _RustLoader:
 
     @property
     def map(self) -> List[Dict[str, Any]]:
-        return self._tables.map
+        if not self._map_masks:
+            return self._tables.map
+        return [dict(map, mask=self._map_masks[map["token"]]) for map in self._tables.map]
 
     @property
     def sensor(self) -> List[Dict[str, Any]]:
@@ -0,0 +1,244 @@
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+use crate::boxes::BoxFilter;
+use crate::classmap::ClassMapping;
+use crate::domain::Sample;
+use crate::geometry::Frame;
+use crate::nusc::Tables;
+use crate::velocity::MAX_TIME_DIFF;
+
+/// Ego-centric bird's-eye-view grid, rotated by `rotation` radians around the z axis of the ego frame.
+///
+/// Columns go along the x axis of the grid and rows along its y axis, from `x_range[0]` and `y_range[0]`.
+#[derive(Clone, Copy, Debug)]
+pub struct BevGrid {
+    pub x_range: [f64; 2],
+    pub y_range: [f64; 2],
+    pub resolution: f64,
+    pub rotation: f64,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl BevGrid {
+    pub fn new(x_range: [f64; 2], y_range: [f64; 2], resolution: f64, rotation: f64) -> PyResult<Self> {
+        let finite = [x_range[0], x_range[1], y_range[0], y_range[1], resolution].iter().all(|v| v.is_finite());
+        if !finite || resolution <= 0.0 || x_range[0] >= x_range[1] || y_range[0] >= y_range[1] {
+            return Err(PyValueError::new_err(format!(
+                "Invalid grid with x range {x_range:?}, y range {y_range:?} and resolution {resolution}"
+            )));
+        }
+        let width = ((x_range[1] - x_range[0]) / resolution).ceil() as usize;
+        let height = ((y_range[1] - y_range[0]) / resolution).ceil() as usize;
+        // The largest raster of the grid, the velocities of the boxes, must fit in an allocation.
+        let bytes = width.checked_mul(height).and_then(|cells| cells.checked_mul(size_of::<[f32; 2]>()));
+        if bytes.is_none_or(|bytes| bytes > isize::MAX as usize) {
+            return Err(PyValueError::new_err(format!(
+                "Grid of {width}x{height} cells with x range {x_range:?}, y range {y_range:?} and resolution \
+                 {resolution} is too large"
+            )));
+        }
+        Ok(Self { x_range, y_range, resolution, rotation, width, height })
+    }
+
+    pub fn len(&self) -> usize {
+        self.width * self.height
+    }
+
+    /// Maps a point of the ego frame to continuous cell coordinates `[col, row]`.
+    pub fn grid_coords(&self, p: [f64; 3]) -> [f64; 2] {
+        let (sin, cos) = self.rotation.sin_cos();
+        let (x, y) = (cos * p[0] + sin * p[1], -sin * p[0] + cos * p[1]);
+        [(x - self.x_range[0]) / self.resolution, (y - self.y_range[0]) / self.resolution]
+    }
+
+    /// Row-major index of the cell containing a point of the ego frame, if it is inside the grid.
+    pub fn cell(&self, p: [f64; 3]) -> Option<usize> {
+        let [col, row] = self.grid_coords(p);
+        let inside = col >= 0.0 && row >= 0.0 && col < self.width as f64 && row < self.height as f64;
+        inside.then(|| row as usize * self.width + col as usize)
+    }
+
+    /// Row-major indices of the cells whose centers are inside a convex polygon given in cell coordinates.
+    fn fill_convex(&self, polygon: &[[f64; 2]]) -> Vec<usize> {
+        let (mut lo, mut hi) = ([f64::INFINITY; 2], [f64::NEG_INFINITY; 2]);
+        for p in polygon {
+            for i in 0..2 {
+                lo[i] = lo[i].min(p[i]);
+                hi[i] = hi[i].max(p[i]);
+            }
+        }
+        // Cell centers are at half-integer coordinates.
+        let range = |lo: f64, hi: f64, len: usize| {
+            let start = (lo - 0.5).ceil().max(0.0) as usize;
+            let end = ((hi - 0.5).floor() + 1.0).clamp(0.0, len as f64) as usize;
+            start..end
+        };
+        // The sign of the cross products is the same for all edges inside, whatever the winding.
+        let side =
+            |a: [f64; 2], b: [f64; 2], p: [f64; 2]| (b[0] - a[0]) * (p[1] - a[1]) - (b[1] - a[1]) * (p[0] - a[0]);
+        let inside = |p: [f64; 2]| {
+            let sides = (0..polygon.len()).map(|i| side(polygon[i], polygon[(i + 1) % polygon.len()], p));
+            let (mut pos, mut neg) = (false, false);
+            for s in sides {
+                pos |= s > 0.0;
+                neg |= s < 0.0;
+            }
+            !(pos && neg)
+        };
+
+        let mut cells = Vec::new();
+        for row in range(lo[1], hi[1], self.height) {
+            for col in range(lo[0], hi[0], self.width) {
+                if inside([col as f64 + 0.5, row as f64 + 0.5]) {
+                    cells.push(row * self.width + col);
+                }
+            }
+        }
+        cells
+    }
+}
+
+/// Annotation boxes rasterized on a BEV grid. Cells outside all boxes have the `fill` class, instance -1 and NaN
+/// heading and velocity. Later boxes of the sample overwrite earlier ones where they overlap.
+#[derive(Clone, Debug)]
+pub struct BoxRaster {
+    pub classes: Vec<u16>,
+    pub instances: Vec<i32>,        // Index in `annotations`
+    pub headings: Vec<f32>,         // Yaw in the grid frame
+    pub velocities: Vec<[f32; 2]>,  // In the grid frame
+    pub annotations: Vec<[u8; 16]>, // Annotation of each drawn box
+    pub instance_tokens: Vec<[u8; 16]>,
+}
+
+/// Lidar points binned on a BEV grid. Empty cells have NaN height and intensity.
+#[derive(Clone, Debug)]
+pub struct LidarRaster {
+    pub counts: Vec<u32>,
+    pub max_height: Vec<f32>,
+    pub intensity: Vec<f32>, // Mean over the points of a cell
+}
+
+impl Tables {
+    /// Rasterizes the annotation boxes of a sample passing `filter` and mapped to a class, in the ego frame of its
+    /// LIDAR_TOP keyframe.
+    pub(crate) fn box_raster(
+        &self, sample: &Sample, grid: &BevGrid, mapping: &ClassMapping, filter: &BoxFilter, fill: u16,
+    ) -> PyResult<BoxRaster> {
+        let lidar = self.sample_lidar_top(sample)?;
+        let mut raster = BoxRaster {
+            classes: vec![fill; grid.len()],
+            instances: vec![-1; grid.len()],
+            headings: vec![f32::NAN; grid.len()],
+            velocities: vec![[f32::NAN; 2]; grid.len()],
+            annotations: Vec::new(),
+            instance_tokens: Vec::new(),
+        };
+        let transform = self.sensor_pose(lidar).between(Frame::Global, Frame::Ego);
+        for ann in sample.anns.iter().map(|tok| self.sample_ann.get(tok).unwrap()) {
+            if !filter.matches(ann) {
+                continue;
+            }
+            let Some(class) = mapping.class_id(ann.category.as_str()) else { continue };
+            let mut b = self.annotation_box(ann);
+            b.velocity = self.annotation_velocity(ann, Frame::Global, MAX_TIME_DIFF).map(|v| v as f64);
+            b.transform(&transform);
+
+            let polygon: Vec<_> = b.bottom_corners().iter().map(|c| grid.grid_coords(*c)).collect();
+            let heading = (b.yaw() - grid.rotation) as f32;
+            let (sin, cos) = grid.rotation.sin_cos();
+            let [vx, vy, _] = b.velocity;
+            let velocity = [(cos * vx + sin * vy) as f32, (-sin * vx + cos * vy) as f32];
+            let instance = raster.annotations.len() as i32;
+            for cell in grid.fill_convex(&polygon) {
+                raster.classes[cell] = class;
+                raster.instances[cell] = instance;
+                raster.headings[cell] = heading;
+                raster.velocities[cell] = velocity;
+            }
+            raster.annotations.push(ann.token);
+            raster.instance_tokens.push(ann.instance_token);
+        }
+        Ok(raster)
+    }
+
+    /// Bins the LIDAR_TOP points of a sample in its ego frame, keeping those with a height within `z_range`.
+    pub(crate) fn lidar_raster(&self, sample: &Sample, grid: &BevGrid, z_range: [f64; 2]) -> PyResult<LidarRaster> {
+        let lidar = self.sample_lidar_top(sample)?;
+        let points = self.lidar_points(lidar, false)?;
+        let transform = self.sensor_pose(lidar).between(Frame::Sensor, Frame::Ego);
+
+        let mut raster = LidarRaster {
+            counts: vec![0; grid.len()],
+            max_height: vec![f32::NAN; grid.len()],
+            intensity: vec![0.0; grid.len()],
+        };
+        for p in points.points() {
+            let q = transform.apply([p[0] as f64, p[1] as f64, p[2] as f64]);
+            if q[2] < z_range[0] || q[2] > z_range[1] {
+                continue;
+            }
+            let Some(cell) = grid.cell(q) else { continue };
+            raster.counts[cell] += 1;
+            raster.max_height[cell] = raster.max_height[cell].max(q[2] as f32);
+            raster.intensity[cell] += p[3];
+        }
+        for (intensity, &count) in raster.intensity.iter_mut().zip(&raster.counts) {
+            *intensity = if count > 0 { *intensity / count as f32 } else { f32::NAN };
+        }
+        Ok(raster)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::FRAC_PI_2;
+
+    use super::*;
+
+    /// Grid of 4x4 cells of 1 meter centered on the ego vehicle.
+    fn grid(rotation: f64) -> BevGrid {
+        BevGrid::new([-2.0, 2.0], [-2.0, 2.0], 1.0, rotation).unwrap()
+    }
+
+    #[test]
+    fn rejects_invalid_or_oversized_grids() {
+        assert!(BevGrid::new([-50.0, f64::INFINITY], [-50.0, 50.0], 0.5, 0.0).is_err());
+        assert!(BevGrid::new([-50.0, 50.0], [f64::NAN, 50.0], 0.5, 0.0).is_err());
+        assert!(BevGrid::new([-50.0, 50.0], [-50.0, 50.0], f64::INFINITY, 0.0).is_err());
+        assert!(BevGrid::new([-50.0, 50.0], [-50.0, 50.0], 1e-300, 0.0).is_err());
+        assert!(BevGrid::new([-1e300, 1e300], [-1e300, 1e300], 1.0, 0.0).is_err());
+        let grid = BevGrid::new([-50.0, 50.0], [-40.0, 40.0], 0.5, 0.0).unwrap();
+        assert_eq!((grid.width, grid.height, grid.len()), (200, 160, 32000));
+    }
+
+    #[test]
+    fn grid_coords_follow_the_rotation() {
+        assert_eq!(grid(0.0).grid_coords([1.0, -0.5, 3.0]), [3.0, 1.5]);
+        // The x axis of the grid is the y axis of the ego frame.
+        let [col, row] = grid(FRAC_PI_2).grid_coords([1.0, -0.5, 3.0]);
+        assert!((col - 1.5).abs() < 1e-12 && (row - 1.0).abs() < 1e-12);
+        assert_eq!(grid(FRAC_PI_2).cell([1.0, -0.5, 3.0]), Some(4 + 1));
+        assert_eq!(grid(0.0).cell([2.5, 0.0, 0.0]), None);
+    }
+
+    #[test]
+    fn fill_convex_ignores_the_winding() {
+        let square = [[1.0, 1.0], [3.0, 1.0], [3.0, 3.0], [1.0, 3.0]];
+        let mut reversed = square;
+        reversed.reverse();
+        assert_eq!(grid(0.0).fill_convex(&square), [5, 6, 9, 10]);
+        assert_eq!(grid(0.0).fill_convex(&reversed), [5, 6, 9, 10]);
+    }
+
+    #[test]
+    fn fill_convex_keeps_cells_along_edges() {
+        // Cell centers on the edges and corners are inside, and cells outside of the grid are dropped.
+        let rectangle = [[0.5, 0.5], [2.5, 0.5], [2.5, 1.5], [0.5, 1.5]];
+        assert_eq!(grid(0.0).fill_convex(&rectangle), [0, 1, 2, 4, 5, 6]);
+        let triangle = [[-3.0, -3.0], [1.5, 0.5], [-3.0, 0.5]];
+        assert_eq!(grid(0.0).fill_convex(&triangle), [0, 1]);
+        assert!(grid(0.0).fill_convex(&[[5.0, 5.0], [6.0, 5.0], [6.0, 6.0]]).is_empty());
+    }
+}
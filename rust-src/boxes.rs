@@ -0,0 +1,559 @@
+use std::sync::Arc;
+
+use ahash::{HashMap, HashMapExt};
+use numpy::ndarray::{Array2, Array3};
+use numpy::{IntoPyArray, PyArray1, PyArray2, PyArray3, PyReadonlyArray2, PyUntypedArrayMethods};
+use pyo3::exceptions::PyValueError;
+use pyo3::types::{PyList, PyType};
+use pyo3::{IntoPyObjectExt, prelude::*};
+use rayon::prelude::*;
+
+use crate::camera::{BoxVisibility, Camera};
+use crate::common::Visibility;
+use crate::domain::{SampleAnnotation, SampleData};
+use crate::geometry::{Frame, Quaternion, Transform, matrix_from_pyarray};
+use crate::nusc::Tables;
+
+// Corner signs along length, width and height. The first four corners face forward, the last four backwards.
+const CORNER_SIGNS: [[f64; 3]; 8] = [
+    [1.0, 1.0, 1.0],
+    [1.0, -1.0, 1.0],
+    [1.0, -1.0, -1.0],
+    [1.0, 1.0, -1.0],
+    [-1.0, 1.0, 1.0],
+    [-1.0, -1.0, 1.0],
+    [-1.0, -1.0, -1.0],
+    [-1.0, 1.0, -1.0],
+];
+
+// Indices of the bottom corners. The first two face forward, the last two backwards.
+const BOTTOM_CORNERS: [usize; 4] = [2, 3, 7, 6];
+
+/// 3D bounding box, mirroring `Box` in `nuscenes/utils/data_classes.py`.
+#[derive(Clone, Debug)]
+pub struct Box3D {
+    pub center: [f64; 3],
+    pub wlh: [f64; 3],
+    pub orientation: Quaternion,
+    pub velocity: [f64; 3],
+    pub label: Option<i64>,
+    pub score: f64,
+    pub name: Option<Arc<str>>,
+    pub token: Option<String>,
+}
+
+impl Box3D {
+    /// Builds the box of an annotation in the global frame, named after its category. The velocity is NaN unless
+    /// velocities are computed for annotations.
+    pub fn from_annotation(ann: &SampleAnnotation, name: Arc<str>) -> Self {
+        Self {
+            center: ann.translation.map(|t| t as f64),
+            wlh: ann.size.map(|s| s as f64),
+            orientation: Quaternion::from_f32(ann.rotation),
+            velocity: ann.velocity.map_or([f64::NAN; 3], |v| v.map(|v| v as f64)),
+            label: None,
+            score: f64::NAN,
+            name: Some(name),
+            token: Some(hex::encode(ann.token)),
+        }
+    }
+
+    pub fn translate(&mut self, x: [f64; 3]) {
+        self.center = [0, 1, 2].map(|i| self.center[i] + x[i]);
+    }
+
+    /// Rotates the box around the origin.
+    pub fn rotate(&mut self, q: &Quaternion) {
+        self.center = q.rotate(self.center);
+        self.orientation = *q * self.orientation;
+        self.velocity = q.rotate(self.velocity);
+    }
+
+    /// Rotates then translates the box.
+    pub fn transform(&mut self, transform: &Transform) {
+        self.rotate(&transform.rotation);
+        self.translate(transform.translation);
+    }
+
+    pub fn yaw(&self) -> f64 {
+        self.orientation.yaw()
+    }
+
+    pub fn corners(&self, wlh_factor: f64) -> [[f64; 3]; 8] {
+        let [w, l, h] = self.wlh.map(|d| d * wlh_factor / 2.0);
+        let rotation = self.orientation.normalize();
+        CORNER_SIGNS.map(|[sx, sy, sz]| {
+            let c = rotation.rotate([sx * l, sy * w, sz * h]);
+            [0, 1, 2].map(|i| c[i] + self.center[i])
+        })
+    }
+
+    pub fn bottom_corners(&self) -> [[f64; 3]; 4] {
+        let corners = self.corners(1.0);
+        BOTTOM_CORNERS.map(|i| corners[i])
+    }
+
+    /// Returns the interior of the box scaled by `wlh_factor`, to test points against.
+    pub fn interior(&self, wlh_factor: f64) -> BoxInterior {
+        let [w, l, h] = self.wlh.map(|d| d * wlh_factor / 2.0);
+        BoxInterior { rotation: self.orientation.rotation_matrix(), center: self.center, half_lwh: [l, w, h] }
+    }
+}
+
+/// Interior of a box, boundaries included.
+#[derive(Clone, Copy, Debug)]
+pub struct BoxInterior {
+    rotation: [[f64; 3]; 3],
+    center: [f64; 3],
+    half_lwh: [f64; 3],
+}
+
+impl BoxInterior {
+    /// Whether a point is inside, as `points_in_box` in `geometry_utils.py`.
+    pub fn contains(&self, point: [f64; 3]) -> bool {
+        let d = [0, 1, 2].map(|i| point[i] - self.center[i]);
+        // Projects onto the box axes, which are the columns of the rotation.
+        (0..3).all(|j| {
+            let along = self.rotation[0][j] * d[0] + self.rotation[1][j] * d[1] + self.rotation[2][j] * d[2];
+            along.abs() <= self.half_lwh[j]
+        })
+    }
+
+    /// Squared distance of a point to the center.
+    pub fn distance_squared(&self, point: [f64; 3]) -> f64 {
+        (0..3).map(|i| (point[i] - self.center[i]).powi(2)).sum()
+    }
+}
+
+/// Selects annotations by visibility and category.
+#[derive(Clone, Debug, Default)]
+pub struct BoxFilter {
+    pub visibilities: Option<Vec<Visibility>>,
+    pub categories: Option<Vec<String>>, // Matching nested categories too
+}
+
+impl BoxFilter {
+    pub fn parse(visibilities: Option<Vec<String>>, categories: Option<Vec<String>>) -> PyResult<Self> {
+        let visibilities = visibilities
+            .map(|vis| vis.iter().map(|v| v.parse()).collect::<Result<_, _>>())
+            .transpose()
+            .map_err(PyValueError::new_err)?;
+        Ok(Self { visibilities, categories })
+    }
+
+    pub fn matches(&self, ann: &SampleAnnotation) -> bool {
+        self.visibilities.as_ref().is_none_or(|vis| vis.contains(&ann.visibility))
+            && self.categories.as_ref().is_none_or(|cats| cats.iter().any(|c| ann.category.is_a(c)))
+    }
+}
+
+impl Tables {
+    pub(crate) fn annotation_box(&self, ann: &SampleAnnotation) -> Box3D {
+        Box3D::from_annotation(ann, Arc::from(ann.category.as_str()))
+    }
+
+    /// Returns the boxes at the time of a sample data, as `NuScenes.get_boxes`.
+    ///
+    /// Keyframes get the boxes of their sample. Sweeps get the boxes of the upcoming sample, interpolated from the
+    /// previous sample for instances annotated in both. Interpolated boxes have a NaN velocity, as in Python.
+    pub(crate) fn sample_data_boxes(&self, sd: &SampleData, frame: Frame, filter: &BoxFilter) -> Vec<Box3D> {
+        let curr = self.sample.get(&sd.sample_token).unwrap();
+        let prev = curr.prev.filter(|_| !sd.is_key_frame).map(|tok| self.sample.get(&tok).unwrap());
+
+        let mut prev_anns = HashMap::new();
+        let mut amount = 0.0;
+        if let Some(prev) = prev {
+            for tok in &prev.anns {
+                let ann = self.sample_ann.get(tok).unwrap();
+                prev_anns.insert(ann.instance_token, ann);
+            }
+            // There are rare situations where the timestamps in the DB are off so ensure that t0 < t < t1.
+            let (t0, t1) = (prev.timestamp as f64, curr.timestamp as f64);
+            let t = (sd.timestamp as f64).clamp(t0, t1);
+            amount = (t - t0) / (t1 - t0);
+        }
+
+        let transform = self.sensor_pose(sd).between(Frame::Global, frame);
+        curr.anns
+            .iter()
+            .map(|tok| self.sample_ann.get(tok).unwrap())
+            .filter(|ann| filter.matches(ann))
+            .map(|ann| {
+                let mut b = self.annotation_box(ann);
+                if let Some(prev_ann) = prev_anns.get(&ann.instance_token) {
+                    let c0 = prev_ann.translation.map(|t| t as f64);
+                    b.center = [0, 1, 2].map(|i| c0[i] + amount * (b.center[i] - c0[i]));
+                    b.orientation = Quaternion::slerp(&Quaternion::from_f32(prev_ann.rotation), &b.orientation, amount);
+                    b.velocity = [f64::NAN; 3];
+                }
+                if frame != Frame::Global {
+                    b.transform(&transform);
+                }
+                b
+            })
+            .collect()
+    }
+}
+
+fn check_rows<const N: usize>(name: &str, array: Option<&PyReadonlyArray2<'_, f64>>, len: usize) -> PyResult<()> {
+    match array.map(|a| a.shape()) {
+        Some(shape) if shape != [len, N] => {
+            Err(PyValueError::new_err(format!("Expected {name} of shape ({len}, {N}), got {shape:?}")))
+        }
+        _ => Ok(()),
+    }
+}
+
+fn row<const N: usize>(array: &PyReadonlyArray2<'_, f64>, i: usize) -> [f64; N] {
+    let a = array.as_array();
+    std::array::from_fn(|j| a[[i, j]])
+}
+
+/// A batch of 3D boxes with vectorized operations, convertible to and from `nuscenes.utils.data_classes.Box`.
+///
+/// Like `Box`, the transform methods modify the boxes in place.
+#[pyclass(module = "nuscenes._lib")]
+#[derive(Clone, Debug, Default)]
+pub struct Boxes {
+    pub boxes: Vec<Box3D>,
+}
+
+impl Boxes {
+    pub fn transform(&mut self, transform: &Transform) {
+        self.boxes.par_iter_mut().for_each(|b| b.transform(transform));
+    }
+}
+
+#[pymethods]
+impl Boxes {
+    #[new]
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3(signature = (centers, sizes, rotations, velocities=None, labels=None, scores=None, names=None, tokens=None))]
+    fn py_new(
+        centers: PyReadonlyArray2<'_, f64>, sizes: PyReadonlyArray2<'_, f64>, rotations: PyReadonlyArray2<'_, f64>,
+        velocities: Option<PyReadonlyArray2<'_, f64>>, labels: Option<Vec<Option<i64>>>, scores: Option<Vec<f64>>,
+        names: Option<Vec<Option<String>>>, tokens: Option<Vec<Option<String>>>,
+    ) -> PyResult<Self> {
+        let len = centers.shape()[0];
+        check_rows::<3>("centers", Some(&centers), len)?;
+        check_rows::<3>("sizes", Some(&sizes), len)?;
+        check_rows::<4>("rotations", Some(&rotations), len)?;
+        check_rows::<3>("velocities", velocities.as_ref(), len)?;
+        let lengths = [
+            labels.as_ref().map(Vec::len),
+            scores.as_ref().map(Vec::len),
+            names.as_ref().map(Vec::len),
+            tokens.as_ref().map(Vec::len),
+        ];
+        if lengths.into_iter().flatten().any(|n| n != len) {
+            return Err(PyValueError::new_err(format!("Expected labels, scores, names and tokens of length {len}")));
+        }
+
+        let boxes = (0..len)
+            .map(|i| Box3D {
+                center: row(&centers, i),
+                wlh: row(&sizes, i),
+                orientation: Quaternion::from_array(row(&rotations, i)),
+                velocity: velocities.as_ref().map_or([f64::NAN; 3], |v| row(v, i)),
+                label: labels.as_ref().and_then(|l| l[i]),
+                score: scores.as_ref().map_or(f64::NAN, |s| s[i]),
+                name: names.as_ref().and_then(|n| n[i].as_deref().map(Arc::from)),
+                token: tokens.as_ref().and_then(|t| t[i].clone()),
+            })
+            .collect();
+        Ok(Self { boxes })
+    }
+
+    /// Converts a list of `nuscenes.utils.data_classes.Box`.
+    #[classmethod]
+    fn from_boxes(_cls: &Bound<'_, PyType>, boxes: &Bound<'_, PyList>) -> PyResult<Self> {
+        let boxes = boxes
+            .iter()
+            .map(|b| {
+                let label: f64 = b.getattr("label")?.extract()?;
+                Ok(Box3D {
+                    center: b.getattr("center")?.extract()?,
+                    wlh: b.getattr("wlh")?.extract()?,
+                    orientation: Quaternion::from_array(b.getattr("orientation")?.getattr("elements")?.extract()?),
+                    velocity: b.getattr("velocity")?.extract()?,
+                    label: (!label.is_nan()).then_some(label as i64),
+                    score: b.getattr("score")?.extract()?,
+                    name: b.getattr("name")?.extract::<Option<String>>()?.map(Arc::from),
+                    token: b.getattr("token")?.extract()?,
+                })
+            })
+            .collect::<PyResult<_>>()?;
+        Ok(Self { boxes })
+    }
+
+    /// Converts the boxes to a list of `nuscenes.utils.data_classes.Box`.
+    fn to_boxes<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyList>> {
+        let box_cls = py.import("nuscenes.utils.data_classes")?.getattr("Box")?;
+        let quaternion_cls = py.import("pyquaternion")?.getattr("Quaternion")?;
+        let boxes = self
+            .boxes
+            .iter()
+            .map(|b| {
+                let orientation = quaternion_cls.call1((b.orientation.to_array(),))?;
+                // `Box` marks a missing label with NaN.
+                let label = b.label.map_or_else(|| f64::NAN.into_py_any(py), |l| l.into_py_any(py))?;
+                box_cls.call1((
+                    b.center,
+                    b.wlh,
+                    orientation,
+                    label,
+                    b.score,
+                    b.velocity,
+                    b.name.as_deref(),
+                    b.token.as_deref(),
+                ))
+            })
+            .collect::<PyResult<Vec<_>>>()?;
+        PyList::new(py, boxes)
+    }
+
+    fn __len__(&self) -> usize {
+        self.boxes.len()
+    }
+
+    #[getter]
+    fn centers<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray2<f64>> {
+        rows_to_pyarray(py, self.boxes.iter().map(|b| b.center))
+    }
+
+    #[getter]
+    fn wlh<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray2<f64>> {
+        rows_to_pyarray(py, self.boxes.iter().map(|b| b.wlh))
+    }
+
+    /// Orientations as `[w, x, y, z]` quaternions.
+    #[getter]
+    fn rotations<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray2<f64>> {
+        rows_to_pyarray(py, self.boxes.iter().map(|b| b.orientation.to_array()))
+    }
+
+    #[getter]
+    fn velocities<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray2<f64>> {
+        rows_to_pyarray(py, self.boxes.iter().map(|b| b.velocity))
+    }
+
+    #[getter]
+    fn yaws<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray1<f64>> {
+        self.boxes.iter().map(Box3D::yaw).collect::<Vec<_>>().into_pyarray(py)
+    }
+
+    #[getter]
+    fn labels(&self) -> Vec<Option<i64>> {
+        self.boxes.iter().map(|b| b.label).collect()
+    }
+
+    #[getter]
+    fn scores<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray1<f64>> {
+        self.boxes.iter().map(|b| b.score).collect::<Vec<_>>().into_pyarray(py)
+    }
+
+    #[getter]
+    fn names(&self) -> Vec<Option<&str>> {
+        self.boxes.iter().map(|b| b.name.as_deref()).collect()
+    }
+
+    #[getter]
+    fn tokens(&self) -> Vec<Option<&str>> {
+        self.boxes.iter().map(|b| b.token.as_deref()).collect()
+    }
+
+    /// Returns the corners of all boxes as an `(N, 8, 3)` array, the first four facing forward.
+    #[pyo3(signature = (wlh_factor=1.0))]
+    fn corners<'py>(&self, py: Python<'py>, wlh_factor: f64) -> Bound<'py, PyArray3<f64>> {
+        let corners: Vec<_> = self.boxes.par_iter().map(|b| b.corners(wlh_factor)).collect();
+        let flat = corners.into_iter().flatten().flatten().collect();
+        Array3::from_shape_vec((self.boxes.len(), 8, 3), flat).unwrap().into_pyarray(py)
+    }
+
+    /// Returns the bottom corners of all boxes as an `(N, 4, 3)` array, the first two facing forward.
+    fn bottom_corners<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray3<f64>> {
+        let corners: Vec<_> = self.boxes.par_iter().map(Box3D::bottom_corners).collect();
+        let flat = corners.into_iter().flatten().flatten().collect();
+        Array3::from_shape_vec((self.boxes.len(), 4, 3), flat).unwrap().into_pyarray(py)
+    }
+
+    /// Returns whether each box is visible in an image of size `(width, height)`, as `box_in_image` in
+    /// `geometry_utils.py`. The boxes must be in the camera frame.
+    #[pyo3(signature = (intrinsic, imsize, vis_level=None))]
+    fn in_image<'py>(
+        &self, py: Python<'py>, intrinsic: [[f64; 3]; 3], imsize: (f64, f64), vis_level: Option<&Bound<'py, PyAny>>,
+    ) -> PyResult<Bound<'py, PyArray1<bool>>> {
+        let vis = vis_level.map(BoxVisibility::extract).transpose()?.unwrap_or_default();
+        let camera = Camera::new(intrinsic, imsize);
+        let mask: Vec<_> = self.boxes.par_iter().map(|b| camera.box_in_image(&b.corners(1.0), vis)).collect();
+        Ok(mask.into_pyarray(py))
+    }
+
+    /// Returns the `(N, 4)` image bounding boxes `[min_x, min_y, max_x, max_y]` of the boxes in the camera frame,
+    /// clipped to an image of size `(width, height)`. Boxes outside of the image get NaN.
+    fn image_bboxes<'py>(
+        &self, py: Python<'py>, intrinsic: [[f64; 3]; 3], imsize: (f64, f64),
+    ) -> Bound<'py, PyArray2<f64>> {
+        let camera = Camera::new(intrinsic, imsize);
+        let bboxes = self.boxes.par_iter().map(|b| camera.bbox_2d(&b.corners(1.0)).unwrap_or([f64::NAN; 4]));
+        rows_to_pyarray(py, bboxes.collect::<Vec<_>>().into_iter())
+    }
+
+    /// Translates all boxes by `x`.
+    #[pyo3(name = "translate")]
+    fn py_translate(&mut self, x: [f64; 3]) {
+        self.boxes.par_iter_mut().for_each(|b| b.translate(x));
+    }
+
+    /// Rotates all boxes around the origin by a `[w, x, y, z]` quaternion.
+    #[pyo3(name = "rotate")]
+    fn py_rotate(&mut self, quaternion: [f64; 4]) {
+        let q = Quaternion::from_array(quaternion).normalize();
+        self.boxes.par_iter_mut().for_each(|b| b.rotate(&q));
+    }
+
+    /// Applies a 4x4 rigid transform matrix to all boxes.
+    #[pyo3(name = "transform")]
+    fn py_transform(&mut self, matrix: PyReadonlyArray2<'_, f64>) -> PyResult<()> {
+        self.transform(&matrix_from_pyarray(matrix)?);
+        Ok(())
+    }
+
+    /// Moves all boxes by a pose given as translation and `[w, x, y, z]` rotation, or by its inverse.
+    #[pyo3(signature = (translation, rotation, inverse=false))]
+    fn transform_by_pose(&mut self, translation: [f64; 3], rotation: [f64; 4], inverse: bool) {
+        let transform = Transform::new(Quaternion::from_array(rotation), translation);
+        self.transform(&if inverse { transform.inverse() } else { transform });
+    }
+}
+
+fn rows_to_pyarray<const N: usize>(
+    py: Python<'_>, rows: impl ExactSizeIterator<Item = [f64; N]>,
+) -> Bound<'_, PyArray2<f64>> {
+    let len = rows.len();
+    let flat = rows.flatten().collect();
+    Array2::from_shape_vec((len, N), flat).unwrap().into_pyarray(py)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::{FRAC_PI_8, SQRT_2};
+
+    use super::*;
+
+    fn unit_box(center: [f64; 3], orientation: Quaternion) -> Box3D {
+        Box3D {
+            center,
+            wlh: [1.0; 3],
+            orientation,
+            velocity: [f64::NAN; 3],
+            label: None,
+            score: f64::NAN,
+            name: None,
+            token: None,
+        }
+    }
+
+    fn assert_corners(actual: &[[f64; 3]], expected: &[[f64; 3]]) {
+        let close = actual.iter().flatten().zip(expected.iter().flatten()).all(|(a, e)| (a - e).abs() < 1e-12);
+        assert!(close && actual.len() == expected.len(), "{actual:?} != {expected:?}");
+    }
+
+    #[test]
+    fn corners_match_box_corners() {
+        // `Box([1, 2, 3], [2, 4, 1], Quaternion(axis=[0, 0, 1], angle=pi / 2)).corners().T`, up to rounding.
+        let b = Box3D {
+            wlh: [2.0, 4.0, 1.0],
+            ..unit_box([1.0, 2.0, 3.0], Quaternion::new(SQRT_2 / 2.0, 0.0, 0.0, SQRT_2 / 2.0))
+        };
+        let expected = [
+            [0.0, 4.0, 3.5],
+            [2.0, 4.0, 3.5],
+            [2.0, 4.0, 2.5],
+            [0.0, 4.0, 2.5],
+            [0.0, 0.0, 3.5],
+            [2.0, 0.0, 3.5],
+            [2.0, 0.0, 2.5],
+            [0.0, 0.0, 2.5],
+        ];
+        assert_corners(&b.corners(1.0), &expected);
+        assert_corners(&b.bottom_corners(), &[expected[2], expected[3], expected[7], expected[6]]);
+        assert_corners(&b.corners(2.0)[..1], &[[-1.0, 6.0, 4.0]]);
+    }
+
+    #[test]
+    fn interior_matches_points_in_box() {
+        // A third of a turn around the diagonal keeps the faces of the unit box exact, so that points on them are
+        // inside, as for `points_in_box`.
+        let interior = unit_box([1.0, 1.0, 0.0], Quaternion::new(0.5, 0.5, 0.5, 0.5)).interior(1.0);
+        let points = [
+            [1.0, 1.0, 0.0],
+            [1.5, 1.0, 0.0],
+            [0.5, 0.5, -0.5],
+            [1.5, 1.5, 0.5],
+            [1.5 + 1e-9, 1.0, 0.0],
+            [1.0, 1.0, 0.5 + 1e-9],
+        ];
+        assert_eq!(points.map(|p| interior.contains(p)), [true, true, true, true, false, false]);
+
+        // Rotated by 45 degrees, the box reaches further along the x axis than its half width.
+        let b = unit_box([0.0; 3], Quaternion::new(FRAC_PI_8.cos(), 0.0, 0.0, FRAC_PI_8.sin()));
+        let points = [[0.7, 0.0, 0.0], [0.72, 0.0, 0.0], [0.0, -0.7, 0.5], [0.5, 0.5, 0.0]];
+        assert_eq!(points.map(|p| b.interior(1.0).contains(p)), [true, false, true, false]);
+        assert!(b.interior(1.1).contains([0.72, 0.0, 0.0]));
+    }
+
+    #[test]
+    fn boxes_round_trip_through_box() {
+        Python::initialize();
+        Python::attach(|py| {
+            // Stand-ins for `pyquaternion` and the constructor of `Box`, which need numpy.
+            py.run(
+                cr#"
+import math, sys, types
+
+class Quaternion:
+    def __init__(self, elements):
+        self.elements = list(elements)
+
+class Box:
+    def __init__(self, center, size, orientation, label=math.nan, score=math.nan, velocity=(math.nan,) * 3,
+                 name=None, token=None):
+        assert type(orientation) == Quaternion
+        self.center, self.wlh, self.orientation = list(center), list(size), orientation
+        self.label = int(label) if not math.isnan(label) else label
+        self.score = float(score) if not math.isnan(score) else score
+        self.velocity, self.name, self.token = list(velocity), name, token
+
+for name in ["nuscenes", "nuscenes.utils", "nuscenes.utils.data_classes", "pyquaternion"]:
+    sys.modules[name] = types.ModuleType(name)
+sys.modules["nuscenes.utils.data_classes"].Box = Box
+sys.modules["pyquaternion"].Quaternion = Quaternion
+"#,
+                None,
+                None,
+            )
+            .unwrap();
+
+            let labeled = Box3D {
+                velocity: [1.0, -2.0, 0.0],
+                label: Some(3),
+                score: 0.5,
+                name: Some(Arc::from("vehicle.car")),
+                token: Some("ab".repeat(16)),
+                ..unit_box([1.0, 2.0, 3.0], Quaternion::new(0.5, 0.5, 0.5, 0.5))
+            };
+            let boxes = Boxes { boxes: vec![labeled, unit_box([0.0; 3], Quaternion::IDENTITY)] };
+            let list = boxes.to_boxes(py).unwrap();
+            assert_eq!(list.get_item(0).unwrap().getattr("label").unwrap().extract::<i64>().unwrap(), 3);
+
+            let back = Boxes::from_boxes(&py.get_type::<Boxes>(), &list).unwrap();
+            for (a, b) in boxes.boxes.iter().zip(&back.boxes) {
+                assert_eq!((a.center, a.wlh, a.orientation), (b.center, b.wlh, b.orientation));
+                assert_eq!((a.label, &a.name, &a.token), (b.label, &b.name, &b.token));
+                let same = |x: f64, y: f64| x == y || (x.is_nan() && y.is_nan());
+                assert!(same(a.score, b.score) && (0..3).all(|i| same(a.velocity[i], b.velocity[i])));
+            }
+        });
+    }
+}
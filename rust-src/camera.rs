@@ -0,0 +1,315 @@
+use numpy::ndarray::Array2;
+use numpy::{IntoPyArray, PyArray2, PyReadonlyArray2, PyUntypedArrayMethods};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+use crate::boxes::Box3D;
+use crate::common::SensorModality;
+use crate::domain::SampleData;
+use crate::nusc::Tables;
+
+// Corners closer to the camera than this, in meters, do not count as visible.
+const MIN_VISIBLE_DEPTH: f64 = 1.0;
+// All corners must be at least this far in front of the camera, in meters.
+const MIN_FRONT_DEPTH: f64 = 0.1;
+
+/// How much of a box must be inside the image, as `BoxVisibility` in `geometry_utils.py`.
+#[pyclass(eq, eq_int, frozen, hash, module = "nuscenes._lib", rename_all = "SCREAMING_SNAKE_CASE")]
+#[derive(Clone, Copy, Debug, Default, Hash, PartialEq, Eq)]
+pub enum BoxVisibility {
+    /// All corners are inside the image.
+    All = 0,
+    /// At least one corner is inside the image.
+    #[default]
+    Any = 1,
+    /// The box may be fully outside the image.
+    None = 2,
+}
+
+impl BoxVisibility {
+    /// Accepts this enum as well as the integer values of `BoxVisibility` in `geometry_utils.py`.
+    pub fn extract(value: &Bound<'_, PyAny>) -> PyResult<Self> {
+        if let Ok(vis) = value.cast::<Self>() {
+            return Ok(*vis.get());
+        }
+        match value.extract::<i64>()? {
+            0 => Ok(Self::All),
+            1 => Ok(Self::Any),
+            2 => Ok(Self::None),
+            other => Err(PyValueError::new_err(format!("vis_level: {other} not valid"))),
+        }
+    }
+}
+
+/// Maps points with a view matrix of up to 4x4, padded with the identity, as `view_points` in `geometry_utils.py`.
+/// With `normalize`, the points are divided by their third coordinate as in a perspective projection.
+pub fn view_points(points: &[[f64; 3]], view: &[[f64; 4]; 4], normalize: bool) -> Vec<[f64; 3]> {
+    points
+        .iter()
+        .map(|p| {
+            let q = [0, 1, 2].map(|i| view[i][0] * p[0] + view[i][1] * p[1] + view[i][2] * p[2] + view[i][3]);
+            if normalize { q.map(|c| c / q[2]) } else { q }
+        })
+        .collect()
+}
+
+/// Pads a 3x3 matrix to 4x4 with the identity.
+pub fn pad_view(m: &[[f64; 3]; 3]) -> [[f64; 4]; 4] {
+    let mut view = [[0.0, 0.0, 0.0, 0.0], [0.0, 0.0, 0.0, 0.0], [0.0, 0.0, 0.0, 0.0], [0.0, 0.0, 0.0, 1.0]];
+    for i in 0..3 {
+        view[i][..3].copy_from_slice(&m[i]);
+    }
+    view
+}
+
+/// Pinhole camera with its image size.
+#[derive(Clone, Copy, Debug)]
+pub struct Camera {
+    pub intrinsic: [[f64; 3]; 3],
+    pub width: f64,
+    pub height: f64,
+}
+
+impl Camera {
+    pub fn new(intrinsic: [[f64; 3]; 3], (width, height): (f64, f64)) -> Self {
+        Self { intrinsic, width, height }
+    }
+
+    /// Projects points in the camera frame to pixels, returning the pixel coordinates and depths.
+    pub fn project(&self, points: &[[f64; 3]]) -> Vec<([f64; 2], f64)> {
+        view_points(points, &pad_view(&self.intrinsic), true)
+            .into_iter()
+            .zip(points)
+            .map(|(uv, p)| ([uv[0], uv[1]], p[2]))
+            .collect()
+    }
+
+    pub fn in_image(&self, uv: [f64; 2]) -> bool {
+        uv[0] > 0.0 && uv[0] < self.width && uv[1] > 0.0 && uv[1] < self.height
+    }
+
+    /// Whether the corners of a box in the camera frame satisfy `vis`, as `box_in_image` in `geometry_utils.py`.
+    pub fn box_in_image(&self, corners: &[[f64; 3]; 8], vis: BoxVisibility) -> bool {
+        let projected = self.project(corners);
+        let mut visible = projected.iter().map(|(uv, depth)| self.in_image(*uv) && *depth > MIN_VISIBLE_DEPTH);
+        let in_front = corners.iter().all(|c| c[2] > MIN_FRONT_DEPTH);
+        match vis {
+            BoxVisibility::All => visible.all(|v| v) && in_front,
+            BoxVisibility::Any => visible.any(|v| v) && in_front,
+            BoxVisibility::None => true,
+        }
+    }
+
+    /// Returns the 2D bounding box `[min_x, min_y, max_x, max_y]` of the corners in front of the camera, clipped to
+    /// the image as `post_process_coords` in `export_2d_annotations_as_json.py`, or `None` if it is outside.
+    pub fn bbox_2d(&self, corners: &[[f64; 3]; 8]) -> Option<[f64; 4]> {
+        let in_front: Vec<_> = corners.iter().copied().filter(|c| c[2] > 0.0).collect();
+        let pixels = self.project(&in_front).into_iter().map(|(uv, _)| uv).collect();
+        let hull = convex_hull(pixels);
+        let clipped = clip_to_rect(&hull, self.width, self.height);
+        if clipped.is_empty() {
+            return None;
+        }
+        let init = [f64::INFINITY, f64::INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY];
+        Some(clipped.iter().fold(init, |[x0, y0, x1, y1], p| [x0.min(p[0]), y0.min(p[1]), x1.max(p[0]), y1.max(p[1])]))
+    }
+}
+
+fn cross_2d(o: [f64; 2], a: [f64; 2], b: [f64; 2]) -> f64 {
+    (a[0] - o[0]) * (b[1] - o[1]) - (a[1] - o[1]) * (b[0] - o[0])
+}
+
+/// Counter-clockwise convex hull with Andrew's monotone chain.
+fn convex_hull(mut points: Vec<[f64; 2]>) -> Vec<[f64; 2]> {
+    points.sort_by(|a, b| a[0].total_cmp(&b[0]).then(a[1].total_cmp(&b[1])));
+    points.dedup();
+    if points.len() < 3 {
+        return points;
+    }
+    let mut hull: Vec<[f64; 2]> = Vec::with_capacity(points.len() * 2);
+    for pass in [points.clone(), points.into_iter().rev().collect()] {
+        let start = hull.len();
+        for p in pass {
+            while hull.len() >= start + 2 && cross_2d(hull[hull.len() - 2], hull[hull.len() - 1], p) <= 0.0 {
+                hull.pop();
+            }
+            hull.push(p);
+        }
+        hull.pop();
+    }
+    hull
+}
+
+/// Clips a convex polygon to `[0, width] x [0, height]` with the Sutherland-Hodgman algorithm.
+fn clip_to_rect(polygon: &[[f64; 2]], width: f64, height: f64) -> Vec<[f64; 2]> {
+    // Each edge keeps the points with `sign * p[axis] <= bound`.
+    let edges = [(0, -1.0, 0.0), (0, 1.0, width), (1, -1.0, 0.0), (1, 1.0, height)];
+    let mut output = polygon.to_vec();
+    for (axis, sign, bound) in edges {
+        let inside = |p: &[f64; 2]| sign * p[axis] <= sign * bound;
+        let input = std::mem::take(&mut output);
+        for (i, curr) in input.iter().enumerate() {
+            let prev = &input[(i + input.len() - 1) % input.len()];
+            if inside(curr) != inside(prev) {
+                let t = (bound - prev[axis]) / (curr[axis] - prev[axis]);
+                output.push([prev[0] + t * (curr[0] - prev[0]), prev[1] + t * (curr[1] - prev[1])]);
+            }
+            if inside(curr) {
+                output.push(*curr);
+            }
+        }
+    }
+    output
+}
+
+impl Tables {
+    /// Returns the camera of a camera sample data.
+    pub(crate) fn camera(&self, sd: &SampleData) -> PyResult<Camera> {
+        if sd.modality != SensorModality::Camera {
+            return Err(PyValueError::new_err(format!("{} is not a camera sample data", hex::encode(sd.token))));
+        }
+        let cal = self.calib.get(&sd.calibrated_sensor_token).unwrap();
+        let intrinsic = cal
+            .camera_intrinsic
+            .ok_or_else(|| PyValueError::new_err(format!("{} has no camera intrinsic", hex::encode(cal.token))))?;
+        Ok(Camera::new(intrinsic.map(|row| row.map(|v| v as f64)), (sd.width as f64, sd.height as f64)))
+    }
+}
+
+/// Boxes projected into a camera.
+#[derive(Clone, Debug, Default)]
+pub struct ProjectedBoxes {
+    pub boxes: Vec<Box3D>,           // In the camera frame
+    pub corners: Vec<[[f64; 2]; 8]>, // Pixel coordinates
+    pub bboxes: Vec<[f64; 4]>,       // NaN if outside of the image
+}
+
+impl ProjectedBoxes {
+    pub fn new(camera: &Camera, boxes: Vec<Box3D>, vis: BoxVisibility) -> Self {
+        let mut projected = Self::default();
+        for b in boxes {
+            let corners = b.corners(1.0);
+            if !camera.box_in_image(&corners, vis) {
+                continue;
+            }
+            let pixels = camera.project(&corners);
+            projected.corners.push(std::array::from_fn(|i| pixels[i].0));
+            projected.bboxes.push(camera.bbox_2d(&corners).unwrap_or([f64::NAN; 4]));
+            projected.boxes.push(b);
+        }
+        projected
+    }
+}
+
+/// Maps `(3, n)` points with a view matrix of up to 4x4, as `view_points` in `geometry_utils.py`.
+#[pyfunction]
+#[pyo3(name = "view_points")]
+pub fn py_view_points<'py>(
+    py: Python<'py>, points: PyReadonlyArray2<'py, f64>, view: PyReadonlyArray2<'py, f64>, normalize: bool,
+) -> PyResult<Bound<'py, PyArray2<f64>>> {
+    let (points, view) = (points.as_array(), view.as_array());
+    if points.shape()[0] != 3 || view.shape()[0] > 4 || view.shape()[1] > 4 {
+        return Err(PyValueError::new_err("Expected points of shape (3, n) and a view of at most 4x4"));
+    }
+    let mut padded = [[1.0, 0.0, 0.0, 0.0], [0.0, 1.0, 0.0, 0.0], [0.0, 0.0, 1.0, 0.0], [0.0, 0.0, 0.0, 1.0]];
+    for ((i, j), v) in view.indexed_iter() {
+        padded[i][j] = *v;
+    }
+    let columns: Vec<_> = points.columns().into_iter().map(|c| [c[0], c[1], c[2]]).collect();
+    let mapped = view_points(&columns, &padded, normalize);
+    let mut out = Array2::zeros((3, mapped.len()));
+    for (j, p) in mapped.iter().enumerate() {
+        for i in 0..3 {
+            out[[i, j]] = p[i];
+        }
+    }
+    Ok(out.into_pyarray(py))
+}
+
+/// Reads `(N, 3)` points.
+pub fn points_from_pyarray(points: &PyReadonlyArray2<'_, f64>) -> PyResult<Vec<[f64; 3]>> {
+    if points.shape()[1] != 3 {
+        return Err(PyValueError::new_err(format!("Expected points of shape (N, 3), got {:?}", points.shape())));
+    }
+    Ok(points.as_array().rows().into_iter().map(|r| [r[0], r[1], r[2]]).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::{FRAC_PI_8, SQRT_2};
+
+    use super::*;
+    use crate::geometry::Quaternion;
+
+    /// Camera with a focal length of 100 pixels and its principal point at the center of a 100x100 image.
+    fn camera() -> Camera {
+        Camera::new([[100.0, 0.0, 50.0], [0.0, 100.0, 50.0], [0.0, 0.0, 1.0]], (100.0, 100.0))
+    }
+
+    fn corners(center: [f64; 3], wlh: [f64; 3], orientation: Quaternion) -> [[f64; 3]; 8] {
+        let b = Box3D {
+            center,
+            wlh,
+            orientation,
+            velocity: [f64::NAN; 3],
+            label: None,
+            score: f64::NAN,
+            name: None,
+            token: None,
+        };
+        b.corners(1.0)
+    }
+
+    fn assert_close(actual: &[f64], expected: &[f64]) {
+        let close = actual.iter().zip(expected).all(|(a, e)| (a - e).abs() < 1e-9);
+        assert!(close && actual.len() == expected.len(), "{actual:?} != {expected:?}");
+    }
+
+    // The expected boxes are those of `post_process_coords` on the corners in front of the camera, worked out by hand
+    // as the hulls are rectangles or a diamond.
+
+    #[test]
+    fn bbox_2d_of_a_box_in_view() {
+        let bbox = camera().bbox_2d(&corners([0.0, 0.0, 5.0], [1.0; 3], Quaternion::IDENTITY)).unwrap();
+        let near = 50.0 / 4.5;
+        assert_close(&bbox, &[50.0 - near, 50.0 - near, 50.0 + near, 50.0 + near]);
+    }
+
+    #[test]
+    fn bbox_2d_of_a_box_partly_behind_the_camera() {
+        // Only the four corners at a depth of 0.75 are kept, spanning [10, 143.3] x [-3.3, 130] before clipping.
+        let bbox = camera().bbox_2d(&corners([0.2, 0.1, 0.25], [1.0; 3], Quaternion::IDENTITY)).unwrap();
+        assert_close(&bbox, &[10.0, 0.0, 100.0, 100.0]);
+    }
+
+    #[test]
+    fn bbox_2d_of_a_box_cut_by_the_image_border() {
+        // A flat square turned by 45 degrees projects to a diamond centered on (90, 50) with a radius of 40 pixels,
+        // whose right tip is cut at x = 100.
+        let orientation = Quaternion::new(FRAC_PI_8.cos(), 0.0, 0.0, FRAC_PI_8.sin());
+        let bbox = camera().bbox_2d(&corners([0.8, 0.0, 2.0], [0.8 * SQRT_2, 0.8 * SQRT_2, 0.0], orientation));
+        assert_close(&bbox.unwrap(), &[50.0, 10.0, 100.0, 90.0]);
+    }
+
+    #[test]
+    fn bbox_2d_of_a_box_outside_of_the_image() {
+        assert_eq!(camera().bbox_2d(&corners([5.0, 0.0, 5.0], [1.0; 3], Quaternion::IDENTITY)), None);
+        assert_eq!(camera().bbox_2d(&corners([0.0, 0.0, -5.0], [1.0; 3], Quaternion::IDENTITY)), None);
+    }
+
+    #[test]
+    fn convex_hull_drops_inner_and_collinear_points() {
+        let points = vec![[0.0, 0.0], [2.0, 2.0], [1.0, 1.0], [2.0, 0.0], [1.0, 0.0], [0.0, 2.0], [2.0, 0.0]];
+        assert_eq!(convex_hull(points), [[0.0, 0.0], [2.0, 0.0], [2.0, 2.0], [0.0, 2.0]]);
+        assert_eq!(convex_hull(vec![[1.0, 1.0], [0.0, 0.0], [1.0, 1.0]]), [[0.0, 0.0], [1.0, 1.0]]);
+    }
+
+    #[test]
+    fn clip_to_rect_cuts_polygons_at_the_borders() {
+        let triangle = [[-10.0, 50.0], [50.0, -10.0], [50.0, 50.0]];
+        let clipped = clip_to_rect(&triangle, 100.0, 100.0);
+        assert_close(&clipped.concat(), &[0.0, 50.0, 0.0, 40.0, 40.0, 0.0, 50.0, 0.0, 50.0, 50.0]);
+        assert!(clip_to_rect(&[[110.0, 10.0], [120.0, 10.0], [110.0, 20.0]], 100.0, 100.0).is_empty());
+        assert!(clip_to_rect(&[], 100.0, 100.0).is_empty());
+    }
+}
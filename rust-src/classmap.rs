@@ -0,0 +1,370 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use ahash::HashMap;
+use pyo3::exceptions::{PyFileNotFoundError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyTuple, PyType};
+use serde::Deserialize;
+
+const DETECTION_CLASSES: [&str; 10] = [
+    "car",
+    "truck",
+    "bus",
+    "trailer",
+    "construction_vehicle",
+    "pedestrian",
+    "motorcycle",
+    "bicycle",
+    "traffic_cone",
+    "barrier",
+];
+
+const DETECTION_MAPPING: [(&str, &str); 14] = [
+    ("movable_object.barrier", "barrier"),
+    ("vehicle.bicycle", "bicycle"),
+    ("vehicle.bus.bendy", "bus"),
+    ("vehicle.bus.rigid", "bus"),
+    ("vehicle.car", "car"),
+    ("vehicle.construction", "construction_vehicle"),
+    ("vehicle.motorcycle", "motorcycle"),
+    ("human.pedestrian.adult", "pedestrian"),
+    ("human.pedestrian.child", "pedestrian"),
+    ("human.pedestrian.construction_worker", "pedestrian"),
+    ("human.pedestrian.police_officer", "pedestrian"),
+    ("movable_object.trafficcone", "traffic_cone"),
+    ("vehicle.trailer", "trailer"),
+    ("vehicle.truck", "truck"),
+];
+
+const TRACKING_CLASSES: [&str; 7] = ["bicycle", "bus", "car", "motorcycle", "pedestrian", "trailer", "truck"];
+
+const TRACKING_MAPPING: [(&str, &str); 11] = [
+    ("vehicle.bicycle", "bicycle"),
+    ("vehicle.bus.bendy", "bus"),
+    ("vehicle.bus.rigid", "bus"),
+    ("vehicle.car", "car"),
+    ("vehicle.motorcycle", "motorcycle"),
+    ("human.pedestrian.adult", "pedestrian"),
+    ("human.pedestrian.child", "pedestrian"),
+    ("human.pedestrian.construction_worker", "pedestrian"),
+    ("human.pedestrian.police_officer", "pedestrian"),
+    ("vehicle.trailer", "trailer"),
+    ("vehicle.truck", "truck"),
+];
+
+// Class 0 is the ignore class of the nuScenes-lidarseg challenge.
+const LIDARSEG_CLASSES: [&str; 17] = [
+    "ignore",
+    "barrier",
+    "bicycle",
+    "bus",
+    "car",
+    "construction_vehicle",
+    "motorcycle",
+    "pedestrian",
+    "traffic_cone",
+    "trailer",
+    "truck",
+    "driveable_surface",
+    "other_flat",
+    "sidewalk",
+    "terrain",
+    "manmade",
+    "vegetation",
+];
+
+const LIDARSEG_MAPPING: [(&str, &str); 32] = [
+    ("noise", "ignore"),
+    ("human.pedestrian.adult", "pedestrian"),
+    ("human.pedestrian.child", "pedestrian"),
+    ("human.pedestrian.wheelchair", "ignore"),
+    ("human.pedestrian.stroller", "ignore"),
+    ("human.pedestrian.personal_mobility", "ignore"),
+    ("human.pedestrian.police_officer", "pedestrian"),
+    ("human.pedestrian.construction_worker", "pedestrian"),
+    ("animal", "ignore"),
+    ("vehicle.car", "car"),
+    ("vehicle.motorcycle", "motorcycle"),
+    ("vehicle.bicycle", "bicycle"),
+    ("vehicle.bus.bendy", "bus"),
+    ("vehicle.bus.rigid", "bus"),
+    ("vehicle.truck", "truck"),
+    ("vehicle.construction", "construction_vehicle"),
+    ("vehicle.emergency.ambulance", "ignore"),
+    ("vehicle.emergency.police", "ignore"),
+    ("vehicle.trailer", "trailer"),
+    ("movable_object.barrier", "barrier"),
+    ("movable_object.trafficcone", "traffic_cone"),
+    ("movable_object.pushable_pullable", "ignore"),
+    ("movable_object.debris", "ignore"),
+    ("static_object.bicycle_rack", "ignore"),
+    ("flat.driveable_surface", "driveable_surface"),
+    ("flat.sidewalk", "sidewalk"),
+    ("flat.terrain", "terrain"),
+    ("flat.other", "other_flat"),
+    ("static.manmade", "manmade"),
+    ("static.vegetation", "vegetation"),
+    ("static.other", "ignore"),
+    ("vehicle.ego", "ignore"),
+];
+
+/// Layout of a class mapping file in JSON or TOML.
+#[derive(Deserialize)]
+struct ClassMappingFile {
+    name: Option<String>,
+    classes: Vec<String>,
+    mapping: HashMap<String, String>,
+}
+
+#[pyclass(module = "nuscenes._lib", frozen)]
+#[derive(Clone, Debug)]
+/// Maps raw category names to a smaller set of classes, such as the 10 detection classes.
+///
+/// Keys of the mapping may also be parent categories, e.g. `vehicle.bus` for both bus categories; the most specific
+/// key wins. Categories without a key are left unmapped.
+pub struct ClassMapping {
+    name: String,
+    classes: Box<[Arc<str>]>,
+    mapping: HashMap<String, u16>,
+}
+
+impl ClassMapping {
+    pub fn new(name: String, classes: Vec<String>, mapping: HashMap<String, String>) -> Result<Self, String> {
+        if classes.len() > u16::MAX as usize {
+            return Err(format!("Too many classes in mapping '{name}'"));
+        }
+        let mut class_ids: HashMap<&str, u16> = HashMap::default();
+        for (i, class) in classes.iter().enumerate() {
+            if class_ids.insert(class.as_str(), i as u16).is_some() {
+                return Err(format!("Class '{class}' is listed twice in the classes of '{name}'"));
+            }
+        }
+        let mapping = mapping
+            .into_iter()
+            .map(|(category, class)| match class_ids.get(class.as_str()) {
+                Some(&id) => Ok((category, id)),
+                None => {
+                    Err(format!("Class '{class}' of category '{category}' is not listed in the classes of '{name}'"))
+                }
+            })
+            .collect::<Result<_, _>>()?;
+        let classes = classes.into_iter().map(Arc::from).collect();
+        Ok(Self { name, classes, mapping })
+    }
+
+    fn from_static(name: &str, classes: &[&str], mapping: &[(&str, &str)]) -> Self {
+        let classes = classes.iter().map(|c| c.to_string()).collect();
+        let mapping = mapping.iter().map(|(cat, class)| (cat.to_string(), class.to_string())).collect();
+        Self::new(name.to_string(), classes, mapping).unwrap()
+    }
+
+    /// The 10 classes of the nuScenes detection challenge.
+    pub fn detection() -> Self {
+        Self::from_static("detection", &DETECTION_CLASSES, &DETECTION_MAPPING)
+    }
+
+    /// The 7 classes of the nuScenes tracking challenge.
+    pub fn tracking() -> Self {
+        Self::from_static("tracking", &TRACKING_CLASSES, &TRACKING_MAPPING)
+    }
+
+    /// The 16 classes of the nuScenes-lidarseg challenge, plus the ignore class 0.
+    pub fn lidarseg() -> Self {
+        Self::from_static("lidarseg", &LIDARSEG_CLASSES, &LIDARSEG_MAPPING)
+    }
+
+    /// Loads a mapping from a `.json` or `.toml` file.
+    pub fn load(path: &Path) -> PyResult<Self> {
+        if !path.exists() {
+            return Err(PyFileNotFoundError::new_err(format!("Class mapping not found at {}", path.display())));
+        }
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| PyValueError::new_err(format!("Failed to read {}: {e}", path.display())))?;
+        let file: ClassMappingFile = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => serde_json::from_str(&content).map_err(|e| e.to_string()),
+            Some("toml") => toml::from_str(&content).map_err(|e| e.to_string()),
+            _ => Err("expected a .json or .toml file".to_string()),
+        }
+        .map_err(|e| PyValueError::new_err(format!("Failed to parse {}: {e}", path.display())))?;
+
+        let name = file.name.unwrap_or_else(|| path.file_stem().unwrap_or_default().to_string_lossy().into_owned());
+        Self::new(name, file.classes, file.mapping).map_err(PyValueError::new_err)
+    }
+
+    /// Resolves a built-in mapping by name, or loads one from a file otherwise.
+    pub fn resolve(spec: &str) -> PyResult<Self> {
+        match spec {
+            "detection" => Ok(Self::detection()),
+            "tracking" => Ok(Self::tracking()),
+            "lidarseg" => Ok(Self::lidarseg()),
+            path => Self::load(Path::new(path)),
+        }
+    }
+
+    /// Returns the class of a category, falling back to its closest mapped parent category.
+    pub fn class_id(&self, category: &str) -> Option<u16> {
+        let mut name = category;
+        loop {
+            if let Some(&id) = self.mapping.get(name) {
+                return Some(id);
+            }
+            name = name.rsplit_once('.')?.0;
+        }
+    }
+
+    pub fn class_name(&self, class_id: u16) -> &Arc<str> {
+        &self.classes[class_id as usize]
+    }
+
+    /// Builds a lookup table from lidarseg category indices to class ids, filling unmapped indices with `fill`.
+    pub fn label_table<'a>(&self, categories: impl Iterator<Item = (u32, &'a str)>, fill: u16) -> Vec<u16> {
+        let categories: Vec<_> = categories.collect();
+        let len = categories.iter().map(|(index, _)| *index as usize + 1).max().unwrap_or_default();
+        let mut table = vec![fill; len];
+        for (index, name) in categories {
+            table[index as usize] = self.class_id(name).unwrap_or(fill);
+        }
+        table
+    }
+}
+
+#[pymethods]
+impl ClassMapping {
+    #[new]
+    fn py_new(name: String, classes: Vec<String>, mapping: HashMap<String, String>) -> PyResult<Self> {
+        Self::new(name, classes, mapping).map_err(PyValueError::new_err)
+    }
+
+    #[classmethod]
+    #[pyo3(name = "detection")]
+    fn py_detection(_cls: &Bound<'_, PyType>) -> Self {
+        Self::detection()
+    }
+
+    #[classmethod]
+    #[pyo3(name = "tracking")]
+    fn py_tracking(_cls: &Bound<'_, PyType>) -> Self {
+        Self::tracking()
+    }
+
+    #[classmethod]
+    #[pyo3(name = "lidarseg")]
+    fn py_lidarseg(_cls: &Bound<'_, PyType>) -> Self {
+        Self::lidarseg()
+    }
+
+    /// Loads a mapping with `name`, `classes` and `mapping` keys from a `.json` or `.toml` file.
+    #[classmethod]
+    fn from_file(_cls: &Bound<'_, PyType>, path: &str) -> PyResult<Self> {
+        Self::load(Path::new(path))
+    }
+
+    #[getter]
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    #[getter]
+    fn classes(&self) -> Vec<&str> {
+        self.classes.iter().map(|c| c.as_ref()).collect()
+    }
+
+    #[getter]
+    fn mapping(&self) -> HashMap<&str, &str> {
+        self.mapping.iter().map(|(cat, &id)| (cat.as_str(), self.class_name(id).as_ref())).collect()
+    }
+
+    /// Returns the class id of a category, or `None` if it is not mapped.
+    #[pyo3(name = "class_id")]
+    fn py_class_id(&self, category: &str) -> Option<u16> {
+        self.class_id(category)
+    }
+
+    /// Returns the class name of a category, or `None` if it is not mapped.
+    fn class_name_of(&self, category: &str) -> Option<&str> {
+        self.class_id(category).map(|id| self.class_name(id).as_ref())
+    }
+
+    fn __len__(&self) -> usize {
+        self.classes.len()
+    }
+
+    fn __reduce__<'py>(slf: &Bound<'py, Self>) -> PyResult<(Bound<'py, PyType>, Bound<'py, PyTuple>)> {
+        let this = slf.get();
+        let classes: Vec<_> = this.classes.iter().map(|c| c.to_string()).collect();
+        let mapping: HashMap<_, _> =
+            this.mapping.iter().map(|(cat, &id)| (cat.clone(), this.class_name(id).to_string())).collect();
+        Ok((slf.get_type(), (this.name.clone(), classes, mapping).into_pyobject(slf.py())?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mapping(classes: &[&str], mapping: &[(&str, &str)]) -> Result<ClassMapping, String> {
+        let classes = classes.iter().map(|c| c.to_string()).collect();
+        let mapping = mapping.iter().map(|(cat, class)| (cat.to_string(), class.to_string())).collect();
+        ClassMapping::new("test".to_string(), classes, mapping)
+    }
+
+    #[test]
+    fn rejects_duplicate_and_unknown_classes() {
+        let err = mapping(&["car", "bus", "car"], &[("vehicle.car", "car")]).unwrap_err();
+        assert!(err.contains("'car' is listed twice"), "{err}");
+        assert!(mapping(&["car"], &[("vehicle.bus", "bus")]).is_err());
+    }
+
+    #[test]
+    fn class_id_falls_back_to_the_closest_parent() {
+        let mapping = mapping(
+            &["vehicle", "bus", "rigid_bus"],
+            &[("vehicle", "vehicle"), ("vehicle.bus", "bus"), ("vehicle.bus.rigid", "rigid_bus")],
+        )
+        .unwrap();
+        assert_eq!(mapping.class_id("vehicle.bus.rigid"), Some(2));
+        assert_eq!(mapping.class_id("vehicle.bus.bendy"), Some(1));
+        assert_eq!(mapping.class_id("vehicle.car"), Some(0));
+        assert_eq!(mapping.class_id("vehicles"), None);
+        assert_eq!(mapping.class_id("human.pedestrian.adult"), None);
+        let detection = ClassMapping::detection();
+        assert_eq!(detection.class_id("vehicle.bus.bendy").map(|id| detection.class_name(id).as_ref()), Some("bus"));
+    }
+
+    #[test]
+    fn loads_json_and_toml_files() {
+        let dir = std::env::temp_dir().join(format!("nuscenes-classmap-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let json = dir.join("vehicles.json");
+        std::fs::write(
+            &json,
+            r#"{"classes": ["car", "bus"], "mapping": {"vehicle.car": "car", "vehicle.bus": "bus"}}"#,
+        )
+        .unwrap();
+        let toml = dir.join("small.toml");
+        std::fs::write(&toml, "name = \"small\"\nclasses = [\"car\"]\n[mapping]\n\"vehicle.car\" = \"car\"\n").unwrap();
+        let yaml = dir.join("small.yaml");
+        std::fs::write(&yaml, "classes: []").unwrap();
+
+        let loaded = ClassMapping::resolve(json.to_str().unwrap()).unwrap();
+        assert_eq!((loaded.name.as_str(), loaded.classes.len()), ("vehicles", 2));
+        assert_eq!(loaded.class_id("vehicle.bus.rigid"), Some(1));
+        let loaded = ClassMapping::load(&toml).unwrap();
+        assert_eq!((loaded.name.as_str(), loaded.class_id("vehicle.car")), ("small", Some(0)));
+        assert!(ClassMapping::load(&yaml).is_err());
+        assert!(ClassMapping::load(&dir.join("missing.json")).is_err());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn label_table_maps_lidarseg_indices() {
+        let categories = [(0, "noise"), (17, "vehicle.car"), (3, "human.pedestrian.wheelchair"), (5, "flat.unknown")];
+        let table = ClassMapping::lidarseg().label_table(categories.into_iter(), u16::MAX);
+        assert_eq!(table.len(), 18);
+        assert_eq!((table[0], table[17], table[3]), (0, 4, 0));
+        // Unmapped categories and missing indices are filled.
+        assert_eq!((table[5], table[1]), (u16::MAX, u16::MAX));
+        assert!(ClassMapping::lidarseg().label_table(std::iter::empty(), 0).is_empty());
+    }
+}
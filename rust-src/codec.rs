@@ -0,0 +1,7 @@
+mod inflate;
+mod npz;
+mod png;
+
+pub use inflate::*;
+pub use npz::*;
+pub use png::*;
@@ -0,0 +1,9 @@
+// Decompression of DEFLATE (RFC 1951) streams with `miniz_oxide`, bounded by the expected size of the output so that
+// corrupt sizes cannot exhaust memory.
+
+use miniz_oxide::inflate::decompress_to_vec_with_limit;
+
+/// Decompresses a raw DEFLATE stream into at most `max_size` bytes.
+pub fn inflate(data: &[u8], max_size: usize) -> Result<Vec<u8>, String> {
+    decompress_to_vec_with_limit(data, max_size).map_err(|e| format!("Corrupt DEFLATE stream: {e}"))
+}
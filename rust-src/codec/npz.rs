@@ -0,0 +1,286 @@
+// Reader of the arrays in `.npz` files as written by `numpy.savez_compressed`, which are zip archives of `.npy` files.
+
+use super::inflate;
+
+const EOCD_SIGNATURE: u32 = 0x0605_4b50;
+const ZIP64_LOCATOR_SIGNATURE: u32 = 0x0706_4b50;
+const ZIP64_EOCD_SIGNATURE: u32 = 0x0606_4b50;
+const CENTRAL_SIGNATURE: u32 = 0x0201_4b50;
+const LOCAL_SIGNATURE: u32 = 0x0403_4b50;
+const ZIP64_EXTRA_ID: u16 = 0x0001;
+const NPY_MAGIC: &[u8] = b"\x93NUMPY";
+
+/// Returns `len` bytes at `at`, or an error if the archive is too short.
+fn slice(bytes: &[u8], at: usize, len: usize) -> Result<&[u8], String> {
+    at.checked_add(len).and_then(|end| bytes.get(at..end)).ok_or_else(|| "Truncated zip archive".to_string())
+}
+
+fn read_u16(bytes: &[u8], at: usize) -> Result<u16, String> {
+    slice(bytes, at, 2).map(|b| u16::from_le_bytes(b.try_into().unwrap()))
+}
+
+fn read_u32(bytes: &[u8], at: usize) -> Result<u32, String> {
+    slice(bytes, at, 4).map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+}
+
+fn read_u64(bytes: &[u8], at: usize) -> Result<u64, String> {
+    slice(bytes, at, 8).map(|b| u64::from_le_bytes(b.try_into().unwrap()))
+}
+
+/// A file in a zip archive, from its central directory entry.
+#[derive(Clone, Debug)]
+struct ZipEntry {
+    name: String,
+    method: u16,
+    compressed_size: u64,
+    size: u64,
+    local_offset: u64,
+}
+
+/// Finds the offset and number of entries of the central directory.
+fn central_directory(bytes: &[u8]) -> Result<(usize, usize), String> {
+    // The end of central directory record is last, followed by a comment of up to 64 KiB.
+    let min = bytes.len().saturating_sub(22 + u16::MAX as usize);
+    let eocd = (min..=bytes.len().saturating_sub(22))
+        .rev()
+        .find(|&i| read_u32(bytes, i) == Ok(EOCD_SIGNATURE))
+        .ok_or("Not a zip archive")?;
+    let entries = read_u16(bytes, eocd + 10)? as u64;
+    let offset = read_u32(bytes, eocd + 16)? as u64;
+    if (entries == u16::MAX as u64 || offset == u32::MAX as u64)
+        && eocd >= 20
+        && read_u32(bytes, eocd - 20)? == ZIP64_LOCATOR_SIGNATURE
+    {
+        let zip64 = read_u64(bytes, eocd - 12)?;
+        if zip64 >= bytes.len() as u64 || read_u32(bytes, zip64 as usize)? != ZIP64_EOCD_SIGNATURE {
+            return Err("Corrupt zip64 end of central directory".to_string());
+        }
+        let zip64 = zip64 as usize;
+        return checked_directory(bytes, read_u64(bytes, zip64 + 48)?, read_u64(bytes, zip64 + 32)?);
+    }
+    checked_directory(bytes, offset, entries)
+}
+
+/// Checks that the central directory lies within the archive and can hold its entries, which bounds the offsets read
+/// from it.
+fn checked_directory(bytes: &[u8], offset: u64, entries: u64) -> Result<(usize, usize), String> {
+    let len = bytes.len() as u64;
+    if offset > len || entries > (len - offset) / 46 {
+        return Err("Corrupt zip central directory".to_string());
+    }
+    Ok((offset as usize, entries as usize))
+}
+
+fn zip_entries(bytes: &[u8]) -> Result<Vec<ZipEntry>, String> {
+    let (mut at, count) = central_directory(bytes)?;
+    let mut entries = Vec::with_capacity(count);
+    for _ in 0..count {
+        if read_u32(bytes, at)? != CENTRAL_SIGNATURE {
+            return Err("Corrupt zip central directory".to_string());
+        }
+        let name_len = read_u16(bytes, at + 28)? as usize;
+        let extra_len = read_u16(bytes, at + 30)? as usize;
+        let comment_len = read_u16(bytes, at + 32)? as usize;
+        let name = slice(bytes, at + 46, name_len)?;
+        let mut entry = ZipEntry {
+            name: String::from_utf8_lossy(name).into_owned(),
+            method: read_u16(bytes, at + 10)?,
+            compressed_size: read_u32(bytes, at + 20)? as u64,
+            size: read_u32(bytes, at + 24)? as u64,
+            local_offset: read_u32(bytes, at + 42)? as u64,
+        };
+
+        // Sizes and offsets that overflow 32 bits are moved to the zip64 extra field, in this order.
+        let extra = slice(bytes, at + 46 + name_len, extra_len)?;
+        let mut i = 0;
+        while i + 4 <= extra.len() {
+            let (id, len) = (read_u16(extra, i)?, read_u16(extra, i + 2)? as usize);
+            if id == ZIP64_EXTRA_ID {
+                let mut field = i + 4;
+                for value in [&mut entry.size, &mut entry.compressed_size, &mut entry.local_offset] {
+                    if *value == u32::MAX as u64 {
+                        *value = read_u64(extra, field)?;
+                        field += 8;
+                    }
+                }
+            }
+            i += 4 + len;
+        }
+        entries.push(entry);
+        at += 46 + name_len + extra_len + comment_len;
+    }
+    Ok(entries)
+}
+
+/// Extracts a file from a zip archive, which may be stored or deflated.
+fn zip_extract(bytes: &[u8], entry: &ZipEntry) -> Result<Vec<u8>, String> {
+    if entry.local_offset >= bytes.len() as u64 {
+        return Err(format!("Corrupt offset of {} in zip archive", entry.name));
+    }
+    let local = entry.local_offset as usize;
+    if read_u32(bytes, local)? != LOCAL_SIGNATURE {
+        return Err(format!("Corrupt local header of {} in zip archive", entry.name));
+    }
+    // The local header may have its own extra field, so only its lengths are taken from it.
+    let start = local + 30 + read_u16(bytes, local + 26)? as usize + read_u16(bytes, local + 28)? as usize;
+    let data = slice(bytes, start, entry.compressed_size as usize)?;
+    let out = match entry.method {
+        0 => data.to_vec(),
+        8 => inflate(data, entry.size as usize)?,
+        method => return Err(format!("Unsupported compression method {method} of {} in zip archive", entry.name)),
+    };
+    if out.len() as u64 != entry.size {
+        return Err(format!("Size of {} does not match the zip archive", entry.name));
+    }
+    Ok(out)
+}
+
+/// An array read from a `.npy` file, with its raw little- or big-endian data.
+#[derive(Clone, Debug)]
+pub struct NpyArray {
+    pub descr: String,
+    pub shape: Vec<usize>,
+    pub data: Vec<u8>,
+}
+
+/// Returns the text of a value in the header dictionary of a `.npy` file, e.g. `'<u2'` for `descr`.
+fn header_value<'h>(header: &'h str, key: &str) -> Result<&'h str, String> {
+    let start = header.find(&format!("'{key}':")).ok_or_else(|| format!("Missing '{key}' in .npy header"))?;
+    let value = header[start + key.len() + 3..].trim_start();
+    let end = match value.as_bytes().first() {
+        Some(b'(') => value.find(')').map(|i| i + 1),
+        Some(b'\'') => value[1..].find('\'').map(|i| i + 2),
+        _ => value.find([',', '}']),
+    };
+    end.map(|end| &value[..end]).ok_or_else(|| format!("Malformed '{key}' in .npy header"))
+}
+
+impl NpyArray {
+    /// Parses a `.npy` file, which must be in C order.
+    pub fn parse(mut bytes: Vec<u8>) -> Result<Self, String> {
+        if !bytes.starts_with(NPY_MAGIC) || bytes.len() < 10 {
+            return Err("Not a .npy file".to_string());
+        }
+        let (header_len, offset) = match bytes[6] {
+            1 => (read_u16(&bytes, 8)? as usize, 10),
+            _ => (read_u32(&bytes, 8)? as usize, 12),
+        };
+        let header = bytes.get(offset..offset + header_len).ok_or("Truncated .npy header")?;
+        let header = std::str::from_utf8(header).map_err(|_| "Invalid .npy header")?;
+
+        let descr = header_value(header, "descr")?.trim_matches('\'').to_string();
+        if header_value(header, "fortran_order")? != "False" {
+            return Err("Fortran-ordered .npy arrays are not supported".to_string());
+        }
+        let shape = header_value(header, "shape")?
+            .trim_matches(['(', ')'])
+            .split(',')
+            .map(str::trim)
+            .filter(|dim| !dim.is_empty())
+            .map(|dim| dim.parse().map_err(|_| format!("Invalid dimension {dim} in .npy header")))
+            .collect::<Result<Vec<usize>, _>>()?;
+
+        bytes.drain(..offset + header_len);
+        let array = Self { descr, shape, data: bytes };
+        let size = array.shape.iter().try_fold(array.item_size()?, |size, &dim| size.checked_mul(dim));
+        if size != Some(array.data.len()) {
+            return Err(format!("Size of .npy data does not match its shape {:?}", array.shape));
+        }
+        Ok(array)
+    }
+
+    fn item_size(&self) -> Result<usize, String> {
+        self.descr
+            .get(2..)
+            .and_then(|size| size.parse().ok())
+            .ok_or_else(|| format!("Unsupported dtype {}", self.descr))
+    }
+
+    /// Converts integers of any width and byte order that fit in a `u16`, as the labels of panoptic files.
+    pub fn to_u16(&self) -> Result<Vec<u16>, String> {
+        let size = self.item_size()?;
+        let (order, kind) = match self.descr.as_bytes() {
+            [order, kind, ..] => (*order, *kind),
+            _ => return Err(format!("Unsupported dtype {}", self.descr)),
+        };
+        if !matches!(kind, b'u' | b'i') || ![1, 2, 4, 8].contains(&size) {
+            return Err(format!("Expected an integer array, got dtype {}", self.descr));
+        }
+        self.data
+            .chunks_exact(size)
+            .map(|item| {
+                let mut buf = [0u8; 8];
+                if order == b'>' {
+                    buf[..size].copy_from_slice(item);
+                    buf[..size].reverse();
+                } else {
+                    buf[..size].copy_from_slice(item);
+                }
+                // Sign-extend negative values so that they are rejected.
+                if kind == b'i' && buf[size - 1] & 0x80 != 0 {
+                    buf[size..].fill(0xff);
+                }
+                u16::try_from(i64::from_le_bytes(buf))
+                    .map_err(|_| format!("Value out of the range of uint16 in {}", self.descr))
+            })
+            .collect()
+    }
+}
+
+/// Reads the array `name` of an `.npz` file from its bytes.
+pub fn read_npz(bytes: &[u8], name: &str) -> Result<NpyArray, String> {
+    let file = format!("{name}.npy");
+    let entries = zip_entries(bytes)?;
+    let entry = entries.iter().find(|e| e.name == file).ok_or_else(|| format!("No array '{name}' in .npz file"))?;
+    NpyArray::parse(zip_extract(bytes, entry)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Written by `testdata/make_npz.py` as `numpy.savez` and `numpy.savez_compressed`.
+    const STORED: &[u8] = include_bytes!("testdata/stored.npz");
+    const DEFLATED: &[u8] = include_bytes!("testdata/deflated.npz");
+    const ZIP64: &[u8] = include_bytes!("testdata/zip64.npz");
+    const CORRUPT: &[u8] = include_bytes!("testdata/corrupt.npz");
+
+    #[test]
+    fn reads_stored_and_deflated_entries() {
+        for bytes in [STORED, DEFLATED] {
+            let data = read_npz(bytes, "data").unwrap();
+            assert_eq!((data.descr.as_str(), data.shape.as_slice()), ("<u2", [2, 3].as_slice()));
+            assert_eq!(data.to_u16().unwrap(), [0, 1, 2, 3, 4, 5]);
+            assert_eq!(read_npz(bytes, "big").unwrap().to_u16().unwrap(), [7, 300, 65535, 0]);
+        }
+    }
+
+    #[test]
+    fn reads_zip64_entries() {
+        let (offset, count) = central_directory(ZIP64).unwrap();
+        assert_eq!((offset, count), (178, 1));
+        let entry = &zip_entries(ZIP64).unwrap()[0];
+        assert_eq!((entry.size, entry.compressed_size, entry.local_offset), (140, 140, 0));
+        assert_eq!(read_npz(ZIP64, "data").unwrap().to_u16().unwrap(), [0, 1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn rejects_missing_arrays_and_negative_values() {
+        assert_eq!(read_npz(STORED, "labels").unwrap_err(), "No array 'labels' in .npz file");
+        assert!(read_npz(STORED, "negative").unwrap().to_u16().is_err());
+    }
+
+    #[test]
+    fn rejects_corrupt_archives() {
+        assert!(read_npz(CORRUPT, "data").unwrap_err().starts_with("Corrupt DEFLATE stream"));
+        // Truncated or damaged archives give errors rather than panics.
+        for len in 0..DEFLATED.len() {
+            assert!(read_npz(&DEFLATED[..len], "data").is_err());
+        }
+        for i in 0..ZIP64.len() {
+            let mut bytes = ZIP64.to_vec();
+            bytes[i] ^= 0xff;
+            let _ = read_npz(&bytes, "data");
+        }
+    }
+}
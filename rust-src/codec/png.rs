@@ -0,0 +1,97 @@
+// Decoding of the PNG images of nuScenes maps with the `png` crate, to 8-bit samples.
+
+use std::io::Cursor;
+
+use png::{BitDepth, ColorType, Decoder, Limits, Transformations};
+
+/// 8-bit image in row-major order, with the channels of each pixel interleaved.
+#[derive(Clone, Debug, Default)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub channels: usize,
+    pub data: Vec<u8>,
+}
+
+/// Decodes a PNG image with all of its channels, as `np.array(Image.open(...))`: palette images keep their indices.
+/// Unlike Pillow, 16-bit samples keep their most significant byte. Sub-byte grayscale samples are scaled to the full
+/// 8-bit range.
+pub fn decode_png(bytes: &[u8]) -> Result<Image, String> {
+    // Map images are far larger than the default limit on allocations, and only bounded by their header.
+    let mut decoder = Decoder::new_with_limits(Cursor::new(bytes), Limits { bytes: usize::MAX });
+    decoder.set_transformations(Transformations::STRIP_16);
+    let mut reader = decoder.read_info().map_err(|e| format!("Invalid PNG image: {e}"))?;
+    let mut buf = vec![0; reader.output_buffer_size().ok_or("PNG image is too large")?];
+    let info = reader.next_frame(&mut buf).map_err(|e| format!("Corrupt PNG image: {e}"))?;
+
+    let (width, height) = (info.width as usize, info.height as usize);
+    let (depth, channels) = (info.bit_depth as usize, info.color_type.samples());
+    let mut data = Vec::with_capacity(width * height * channels);
+    for row in buf.chunks_exact(info.line_size).take(height) {
+        if info.bit_depth == BitDepth::Eight {
+            data.extend_from_slice(&row[..width * channels]);
+        } else {
+            // Only grayscale and palette images have sub-byte samples.
+            // Grayscale is scaled to 8 bits, whereas palette indices are kept.
+            let max = (1u16 << depth) - 1;
+            let scale = if info.color_type == ColorType::Grayscale { 255 / max } else { 1 };
+            data.extend((0..width).map(|x| {
+                let bit = x * depth;
+                let value = (row[bit / 8] >> (8 - depth - bit % 8)) as u16 & max;
+                (value * scale) as u8
+            }));
+        }
+    }
+    Ok(Image { width, height, channels, data })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Written by `testdata/make_png.py`, with rows using every filter type and several IDAT chunks.
+    const GRAY1: &[u8] = include_bytes!("testdata/gray1.png");
+    const GRAY2: &[u8] = include_bytes!("testdata/gray2.png");
+    const GRAY4: &[u8] = include_bytes!("testdata/gray4.png");
+    const GRAY8: &[u8] = include_bytes!("testdata/gray8.png");
+    const GRAY16: &[u8] = include_bytes!("testdata/gray16.png");
+    const RGB8: &[u8] = include_bytes!("testdata/rgb8.png");
+    const PALETTE4: &[u8] = include_bytes!("testdata/palette4.png");
+
+    /// Values of the 13x7 fixtures with `bits` bits, times `scale`.
+    fn pixels(bits: u32, scale: usize) -> Vec<u8> {
+        (0..7).flat_map(|y| (0..13).map(move |x| ((x * 37 + y * 91) % (1 << bits) * scale) as u8)).collect()
+    }
+
+    #[test]
+    fn decodes_sub_byte_depths() {
+        assert_eq!(decode_png(GRAY1).unwrap().data, pixels(1, 255));
+        assert_eq!(decode_png(GRAY2).unwrap().data, pixels(2, 85));
+        assert_eq!(decode_png(GRAY4).unwrap().data, pixels(4, 17));
+        assert_eq!(decode_png(PALETTE4).unwrap().data, pixels(4, 1));
+    }
+
+    #[test]
+    fn keeps_the_high_byte() {
+        for bytes in [GRAY8, GRAY16] {
+            let image = decode_png(bytes).unwrap();
+            assert_eq!((image.width, image.height, image.channels), (13, 7, 1));
+            assert_eq!(image.data, pixels(8, 1));
+        }
+    }
+
+    #[test]
+    fn keeps_all_channels() {
+        let image = decode_png(RGB8).unwrap();
+        assert_eq!((image.width, image.height, image.channels), (13, 7, 3));
+        let expected: Vec<_> =
+            pixels(8, 1).into_iter().flat_map(|v| [v, v.wrapping_add(1), v.wrapping_add(2)]).collect();
+        assert_eq!(image.data, expected);
+    }
+
+    #[test]
+    fn rejects_corrupt_images() {
+        assert!(decode_png(&GRAY8[..GRAY8.len() / 2]).is_err());
+        assert!(decode_png(b"GIF89a").is_err());
+    }
+}
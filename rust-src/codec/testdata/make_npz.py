@@ -0,0 +1,73 @@
+"""Writes the `.npz` fixtures of the tests of `npz.rs`.
+
+The archives are written as `numpy.savez` (stored) and `numpy.savez_compressed` (deflated) do in `numpy.lib.npyio._savez`:
+one `<name>.npy` entry per array, opened with `force_zip64=True`, holding a version 1.0 `.npy` file. The script only
+needs the standard library so that the fixtures can be regenerated without numpy.
+"""
+
+import struct
+import zipfile
+from pathlib import Path
+
+HERE = Path(__file__).parent
+
+
+def npy(descr, shape, data):
+    """A version 1.0 `.npy` file, with the header padded to a multiple of 64 bytes as `numpy.lib.format`."""
+    header = "{'descr': '%s', 'fortran_order': False, 'shape': %r, }" % (descr, shape)
+    header += " " * (63 - (len(header) + 10) % 64) + "\n"
+    return b"\x93NUMPY\x01\x00" + struct.pack("<H", len(header)) + header.encode("latin1") + data
+
+
+# `data` is an arange of shape (2, 3) of uint16, `big` a big-endian int32 vector and `negative` an int8 with -1.
+ARRAYS = {
+    "data": npy("<u2", (2, 3), struct.pack("<6H", *range(6))),
+    "big": npy(">i4", (4,), struct.pack(">4i", 7, 300, 65535, 0)),
+    "negative": npy("|i1", (2,), struct.pack("<2b", 1, -1)),
+}
+
+
+def savez(path, compression):
+    with zipfile.ZipFile(path, "w", compression=compression, allowZip64=True) as zipf:
+        for name, contents in ARRAYS.items():
+            with zipf.open(name + ".npy", "w", force_zip64=True) as f:
+                f.write(contents)
+
+
+def zip64(path):
+    """A stored archive whose central directory is only found through the zip64 end of central directory, and whose
+    entry has its sizes and offset in a zip64 extra field, as written for arrays and archives over 4 GiB."""
+    name, contents = b"data.npy", ARRAYS["data"]
+    crc = zipfile.crc32(contents)
+    local = struct.pack("<IHHHHHIIIHH", 0x04034B50, 45, 0, 0, 0, 0, crc, len(contents), len(contents), len(name), 0)
+    local += name + contents
+    extra = struct.pack("<HHQQQ", 0x0001, 24, len(contents), len(contents), 0)
+    central = struct.pack(
+        "<IHHHHHHIIIHHHHHII", 0x02014B50, 45, 45, 0, 0, 0, 0, crc, 0xFFFFFFFF, 0xFFFFFFFF, len(name), len(extra), 0, 0,
+        0, 0, 0xFFFFFFFF,
+    )
+    central += name + extra
+    eocd64 = struct.pack("<IQHHIIQQQQ", 0x06064B50, 44, 45, 45, 0, 0, 1, 1, len(central), len(local))
+    locator = struct.pack("<IIQI", 0x07064B50, 0, len(local) + len(central), 1)
+    eocd = struct.pack("<IHHHHIIH", 0x06054B50, 0, 0, 0xFFFF, 0xFFFF, 0xFFFFFFFF, 0xFFFFFFFF, 0)
+    path.write_bytes(local + central + eocd64 + locator + eocd)
+
+
+def corrupt(path):
+    """A deflated archive whose stream of `data.npy` is damaged in the middle."""
+    savez(path, zipfile.ZIP_DEFLATED)
+    archive = bytearray(path.read_bytes())
+    with zipfile.ZipFile(path) as zipf:
+        info = zipf.getinfo("data.npy")
+    # The lengths of the name and extra field are those of the local header, whose extra field differs from the central one.
+    start = info.header_offset + 30 + sum(struct.unpack("<HH", archive[info.header_offset + 26 : info.header_offset + 30]))
+    for i in range(start + 2, start + info.compress_size - 2):
+        archive[i] ^= 0xFF
+    path.write_bytes(bytes(archive))
+
+
+if __name__ == "__main__":
+    savez(HERE / "stored.npz", zipfile.ZIP_STORED)
+    savez(HERE / "deflated.npz", zipfile.ZIP_DEFLATED)
+    zip64(HERE / "zip64.npz")
+    corrupt(HERE / "corrupt.npz")
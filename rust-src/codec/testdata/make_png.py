@@ -0,0 +1,73 @@
+"""Writes the `.png` fixtures of the tests of `png.rs`.
+
+Each image holds `pixel(x, y)` of the tests, in the high byte of 16-bit samples, with every row filtered by a different filter type and the image data
+split over several IDAT chunks. The script only needs the standard library.
+"""
+
+import struct
+import zlib
+from pathlib import Path
+
+HERE = Path(__file__).parent
+
+
+def pixel(x, y, bits):
+    return (x * 37 + y * 91) % (1 << bits)
+
+
+def paeth(a, b, c):
+    p = a + b - c
+    pa, pb, pc = abs(p - a), abs(p - b), abs(p - c)
+    return a if pa <= pb and pa <= pc else b if pb <= pc else c
+
+
+def filtered(rows, step):
+    """Filters each row with the filter type of its index modulo 5."""
+    out, prev = b"", bytes(len(rows[0]))
+    for y, row in enumerate(rows):
+        kind = y % 5
+        line = bytearray([kind])
+        for i, value in enumerate(row):
+            a = row[i - step] if i >= step else 0
+            b = prev[i]
+            c = prev[i - step] if i >= step else 0
+            predictor = [0, a, b, (a + b) // 2, paeth(a, b, c)][kind]
+            line.append((value - predictor) % 256)
+        out, prev = out + bytes(line), row
+    return out
+
+
+def chunk(kind, data):
+    return struct.pack(">I", len(data)) + kind + data + struct.pack(">I", zlib.crc32(kind + data))
+
+
+def write(name, width, height, depth, color_type, samples, palette=None):
+    rows = []
+    for y in range(height):
+        if depth < 8:
+            bits = "".join(format(pixel(x, y, depth), "0%db" % depth) for x in range(width))
+            bits += "0" * (-len(bits) % 8)
+            rows.append(bytes(int(bits[i : i + 8], 2) for i in range(0, len(bits), 8)))
+        else:
+            # Later samples of a pixel and the low bytes of 16-bit samples differ from its value.
+            values = [(pixel(x, y, 8) + s) % 256 for x in range(width) for s in range(samples)]
+            if depth == 16:
+                values = [v * 256 + 255 - i % 256 for i, v in enumerate(values)]
+            rows.append(b"".join(v.to_bytes(depth // 8, "big") for v in values))
+    data = zlib.compress(filtered(rows, max(1, samples * depth // 8)))
+    png = b"\x89PNG\r\n\x1a\n" + chunk(b"IHDR", struct.pack(">IIBBBBB", width, height, depth, color_type, 0, 0, 0))
+    if palette:
+        png += chunk(b"PLTE", bytes(range(3 * palette)))
+    for i in range(0, len(data), 16):
+        png += chunk(b"IDAT", data[i : i + 16])
+    (HERE / name).write_bytes(png + chunk(b"IEND", b""))
+
+
+if __name__ == "__main__":
+    write("gray1.png", 13, 7, 1, 0, 1)
+    write("gray2.png", 13, 7, 2, 0, 1)
+    write("gray4.png", 13, 7, 4, 0, 1)
+    write("gray8.png", 13, 7, 8, 0, 1)
+    write("gray16.png", 13, 7, 16, 0, 1)
+    write("rgb8.png", 13, 7, 8, 2, 3)
+    write("palette4.png", 13, 7, 4, 3, 1, palette=16)
@@ -1,6 +1,8 @@
 use std::fmt::Display;
+use std::str::FromStr;
 
 use enum_map::Enum;
+use pyo3::prelude::*;
 use serde::Deserialize;
 
 #[derive(Clone, Copy, Debug, Deserialize, Enum, Hash, PartialEq, Eq)]
@@ -59,6 +61,28 @@ impl Display for SensorChannel {
     }
 }
 
+impl FromStr for SensorChannel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "CAM_BACK" => Ok(Self::CamBack),
+            "CAM_BACK_LEFT" => Ok(Self::CamBackLeft),
+            "CAM_BACK_RIGHT" => Ok(Self::CamBackRight),
+            "CAM_FRONT" => Ok(Self::CamFront),
+            "CAM_FRONT_LEFT" => Ok(Self::CamFrontLeft),
+            "CAM_FRONT_RIGHT" => Ok(Self::CamFrontRight),
+            "LIDAR_TOP" => Ok(Self::LidarTop),
+            "RADAR_BACK_LEFT" => Ok(Self::RadarBackLeft),
+            "RADAR_BACK_RIGHT" => Ok(Self::RadarBackRight),
+            "RADAR_FRONT" => Ok(Self::RadarFront),
+            "RADAR_FRONT_LEFT" => Ok(Self::RadarFrontLeft),
+            "RADAR_FRONT_RIGHT" => Ok(Self::RadarFrontRight),
+            _ => Err(format!("Unknown sensor channel '{s}'")),
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug, Deserialize, Hash, PartialEq, Eq)]
 pub enum Visibility {
     #[serde(rename = "1")]
@@ -71,6 +95,28 @@ pub enum Visibility {
     V80_100,
 }
 
+impl Visibility {
+    /// Returns the visibility level from 1 (least visible) to 4 (most visible).
+    pub fn level(&self) -> u8 {
+        match self {
+            Self::V0_40 => 1,
+            Self::V40_60 => 2,
+            Self::V60_80 => 3,
+            Self::V80_100 => 4,
+        }
+    }
+
+    /// Returns the bounds of the visible fraction of the object across all cameras.
+    pub fn range(&self) -> (f32, f32) {
+        match self {
+            Self::V0_40 => (0.0, 0.4),
+            Self::V40_60 => (0.4, 0.6),
+            Self::V60_80 => (0.6, 0.8),
+            Self::V80_100 => (0.8, 1.0),
+        }
+    }
+}
+
 impl Display for Visibility {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let name = match self {
@@ -82,3 +128,217 @@ impl Display for Visibility {
         write!(f, "{name}")
     }
 }
+
+impl FromStr for Visibility {
+    type Err = String;
+
+    /// Parses either the visibility token, e.g. `4`, or its name, e.g. `v80-100`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "1" | "v0-40" => Ok(Self::V0_40),
+            "2" | "v40-60" => Ok(Self::V40_60),
+            "3" | "v60-80" => Ok(Self::V60_80),
+            "4" | "v80-100" => Ok(Self::V80_100),
+            _ => Err(format!("Unknown visibility '{s}'")),
+        }
+    }
+}
+
+/// Whether a hierarchical `name` equals `prefix` or is nested below it.
+pub fn is_nested(name: &str, prefix: &str, sep: &str) -> bool {
+    name.strip_prefix(prefix).is_some_and(|rest| rest.is_empty() || rest.starts_with(sep))
+}
+
+/// Defines a taxonomy enum, with a fallback `Other` variant keeping names outside of the taxonomy, and its Python view
+/// named `$py_name`, whose class attributes are the members. Names are hierarchical, with levels joined by `$sep`.
+macro_rules! define_taxonomy {
+    (
+        $(#[$meta:meta])* $name:ident, $py:ident, $py_name:literal, $sep:literal,
+        { $($variant:ident as $member:ident => $value:literal),+ $(,)? }
+    ) => {
+        $(#[$meta])*
+        #[derive(Clone, Debug, Hash, PartialEq, Eq)]
+        pub enum $name {
+            $($variant,)+
+            Other(String),
+        }
+
+        impl $name {
+            /// All members of the taxonomy, excluding `Other`.
+            pub const MEMBERS: &[Self] = &[$(Self::$variant),+];
+
+            pub fn from_name(name: &str) -> Self {
+                match name {
+                    $($value => Self::$variant,)+
+                    _ => Self::Other(name.to_string()),
+                }
+            }
+
+            pub fn as_str(&self) -> &str {
+                match self {
+                    $(Self::$variant => $value,)+
+                    Self::Other(name) => name,
+                }
+            }
+
+            /// Whether the name equals `prefix` or is nested below it, e.g. `vehicle.bus.rigid` is a `vehicle.bus`.
+            pub fn is_a(&self, prefix: &str) -> bool {
+                is_nested(self.as_str(), prefix, $sep)
+            }
+
+            /// Name of the member in Python, e.g. `VEHICLE_CAR`.
+            fn member_name(&self) -> &'static str {
+                match self {
+                    $(Self::$variant => stringify!($member),)+
+                    Self::Other(_) => "OTHER",
+                }
+            }
+        }
+
+        impl Display for $name {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                write!(f, "{}", self.as_str())
+            }
+        }
+
+        impl<'py> IntoPyObject<'py> for $name {
+            type Target = $py;
+            type Output = Bound<'py, $py>;
+            type Error = PyErr;
+
+            fn into_pyobject(self, py: Python<'py>) -> PyResult<Self::Output> {
+                Bound::new(py, $py(self))
+            }
+        }
+
+        #[doc = concat!("Python view of [`", stringify!($name), "`], with a class attribute per member.")]
+        #[pyclass(eq, frozen, hash, module = "nuscenes._lib", name = $py_name)]
+        #[derive(Clone, Debug, Hash, PartialEq, Eq)]
+        pub struct $py(pub $name);
+
+        #[pymethods]
+        impl $py {
+            $(
+                #[classattr]
+                #[allow(non_snake_case)]
+                fn $member() -> Self {
+                    Self($name::$variant)
+                }
+            )+
+
+            /// Parses a name, returning an `OTHER` kind keeping the name if it is outside of the taxonomy.
+            #[staticmethod]
+            fn from_name(name: &str) -> Self {
+                Self($name::from_name(name))
+            }
+
+            /// Returns all members of the taxonomy, excluding `OTHER` kinds.
+            #[staticmethod]
+            fn members() -> Vec<Self> {
+                $name::MEMBERS.iter().cloned().map(Self).collect()
+            }
+
+            /// The name of the member, e.g. `VEHICLE_CAR`, or `OTHER` for names outside of the taxonomy.
+            #[getter]
+            fn name(&self) -> &'static str {
+                self.0.member_name()
+            }
+
+            /// The parsed name, kept as is for `OTHER` kinds.
+            #[getter]
+            fn value(&self) -> &str {
+                self.0.as_str()
+            }
+
+            /// The top level of the name, e.g. `vehicle` for `vehicle.bus.rigid`.
+            #[getter]
+            fn root(&self) -> &str {
+                self.0.as_str().split($sep).next().unwrap()
+            }
+
+            /// The name one level up, e.g. `vehicle.bus` for `vehicle.bus.rigid`, or `None` at the top level.
+            #[getter]
+            fn parent(&self) -> Option<&str> {
+                self.0.as_str().rsplit_once($sep).map(|(parent, _)| parent)
+            }
+
+            fn is_a(&self, prefix: &str) -> bool {
+                self.0.is_a(prefix)
+            }
+
+            fn __str__(&self) -> &str {
+                self.0.as_str()
+            }
+
+            fn __repr__(&self) -> String {
+                match &self.0 {
+                    $name::Other(name) => format!("{}.OTHER({name:?})", $py_name),
+                    kind => format!("{}.{}", $py_name, kind.member_name()),
+                }
+            }
+        }
+    };
+}
+
+define_taxonomy!(
+    /// Object categories of nuScenes, plus the background categories of nuScenes-lidarseg.
+    CategoryKind, PyCategoryKind, "CategoryKind", ".", {
+        Animal as ANIMAL => "animal",
+        HumanPedestrianAdult as HUMAN_PEDESTRIAN_ADULT => "human.pedestrian.adult",
+        HumanPedestrianChild as HUMAN_PEDESTRIAN_CHILD => "human.pedestrian.child",
+        HumanPedestrianConstructionWorker as HUMAN_PEDESTRIAN_CONSTRUCTION_WORKER => "human.pedestrian.construction_worker",
+        HumanPedestrianPersonalMobility as HUMAN_PEDESTRIAN_PERSONAL_MOBILITY => "human.pedestrian.personal_mobility",
+        HumanPedestrianPoliceOfficer as HUMAN_PEDESTRIAN_POLICE_OFFICER => "human.pedestrian.police_officer",
+        HumanPedestrianStroller as HUMAN_PEDESTRIAN_STROLLER => "human.pedestrian.stroller",
+        HumanPedestrianWheelchair as HUMAN_PEDESTRIAN_WHEELCHAIR => "human.pedestrian.wheelchair",
+        MovableObjectBarrier as MOVABLE_OBJECT_BARRIER => "movable_object.barrier",
+        MovableObjectDebris as MOVABLE_OBJECT_DEBRIS => "movable_object.debris",
+        MovableObjectPushablePullable as MOVABLE_OBJECT_PUSHABLE_PULLABLE => "movable_object.pushable_pullable",
+        MovableObjectTrafficcone as MOVABLE_OBJECT_TRAFFICCONE => "movable_object.trafficcone",
+        StaticObjectBicycleRack as STATIC_OBJECT_BICYCLE_RACK => "static_object.bicycle_rack",
+        VehicleBicycle as VEHICLE_BICYCLE => "vehicle.bicycle",
+        VehicleBusBendy as VEHICLE_BUS_BENDY => "vehicle.bus.bendy",
+        VehicleBusRigid as VEHICLE_BUS_RIGID => "vehicle.bus.rigid",
+        VehicleCar as VEHICLE_CAR => "vehicle.car",
+        VehicleConstruction as VEHICLE_CONSTRUCTION => "vehicle.construction",
+        VehicleEmergencyAmbulance as VEHICLE_EMERGENCY_AMBULANCE => "vehicle.emergency.ambulance",
+        VehicleEmergencyPolice as VEHICLE_EMERGENCY_POLICE => "vehicle.emergency.police",
+        VehicleMotorcycle as VEHICLE_MOTORCYCLE => "vehicle.motorcycle",
+        VehicleTrailer as VEHICLE_TRAILER => "vehicle.trailer",
+        VehicleTruck as VEHICLE_TRUCK => "vehicle.truck",
+        // Only in nuScenes-lidarseg
+        Noise as NOISE => "noise",
+        FlatDriveableSurface as FLAT_DRIVEABLE_SURFACE => "flat.driveable_surface",
+        FlatOther as FLAT_OTHER => "flat.other",
+        FlatSidewalk as FLAT_SIDEWALK => "flat.sidewalk",
+        FlatTerrain as FLAT_TERRAIN => "flat.terrain",
+        StaticManmade as STATIC_MANMADE => "static.manmade",
+        StaticOther as STATIC_OTHER => "static.other",
+        StaticVegetation as STATIC_VEGETATION => "static.vegetation",
+        VehicleEgo as VEHICLE_EGO => "vehicle.ego",
+    }
+);
+
+define_taxonomy!(
+    /// Attributes of sample annotations.
+    AttributeKind, PyAttributeKind, "AttributeKind", ".", {
+        VehicleMoving as VEHICLE_MOVING => "vehicle.moving",
+        VehicleStopped as VEHICLE_STOPPED => "vehicle.stopped",
+        VehicleParked as VEHICLE_PARKED => "vehicle.parked",
+        CycleWithRider as CYCLE_WITH_RIDER => "cycle.with_rider",
+        CycleWithoutRider as CYCLE_WITHOUT_RIDER => "cycle.without_rider",
+        PedestrianSittingLyingDown as PEDESTRIAN_SITTING_LYING_DOWN => "pedestrian.sitting_lying_down",
+        PedestrianStanding as PEDESTRIAN_STANDING => "pedestrian.standing",
+        PedestrianMoving as PEDESTRIAN_MOVING => "pedestrian.moving",
+    }
+);
+
+define_taxonomy!(
+    /// Locations at which logs were recorded, each with its own map.
+    MapLocation, PyMapLocation, "MapLocation", "-", {
+        SingaporeOnenorth as SINGAPORE_ONENORTH => "singapore-onenorth",
+        SingaporeHollandvillage as SINGAPORE_HOLLANDVILLAGE => "singapore-hollandvillage",
+        SingaporeQueenstown as SINGAPORE_QUEENSTOWN => "singapore-queenstown",
+        BostonSeaport as BOSTON_SEAPORT => "boston-seaport",
+    }
+);
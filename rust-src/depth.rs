@@ -0,0 +1,137 @@
+use pyo3::prelude::*;
+use rayon::prelude::*;
+
+use crate::camera::Camera;
+use crate::common::{SensorChannel, SensorModality};
+use crate::domain::{Sample, SampleData};
+use crate::geometry::Frame;
+use crate::nusc::Tables;
+
+// Points within this many pixels of the image border are dropped, as in `map_pointcloud_to_image`.
+const IMAGE_MARGIN: f64 = 1.0;
+
+/// Lidar points projected into a camera, as `map_pointcloud_to_image` in `nuscenes.py`.
+#[derive(Clone, Debug)]
+pub struct DepthProjection {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<[f32; 2]>, // Pixel coordinates of every point
+    pub depths: Vec<f32>,      // Depth of every point in the camera frame
+    pub mask: Vec<bool>,       // Whether a point is in the image and farther than the minimum distance
+}
+
+impl DepthProjection {
+    fn new(camera: &Camera, points: &[[f64; 3]], min_dist: f64) -> Self {
+        let projected = camera.project(points);
+        let in_image = |uv: [f64; 2]| {
+            uv[0] > IMAGE_MARGIN
+                && uv[0] < camera.width - IMAGE_MARGIN
+                && uv[1] > IMAGE_MARGIN
+                && uv[1] < camera.height - IMAGE_MARGIN
+        };
+        Self {
+            width: camera.width as usize,
+            height: camera.height as usize,
+            pixels: projected.iter().map(|(uv, _)| uv.map(|c| c as f32)).collect(),
+            depths: projected.iter().map(|(_, depth)| *depth as f32).collect(),
+            mask: projected.iter().map(|(uv, depth)| *depth > min_dist && in_image(*uv)).collect(),
+        }
+    }
+
+    /// Row-major index of the pixel containing each point, or `None` if it is masked out.
+    pub fn pixel_index(&self, point: usize) -> Option<usize> {
+        let [u, v] = self.pixels[point];
+        self.mask[point].then(|| v as usize * self.width + u as usize)
+    }
+
+    /// Row-major `height x width` sparse depth image, 0 where no point falls and the nearest depth where several do.
+    pub fn depth_map(&self) -> Vec<f32> {
+        let mut depth = vec![0.0f32; self.width * self.height];
+        for (i, &d) in self.depths.iter().enumerate() {
+            if let Some(pixel) = self.pixel_index(i)
+                && (depth[pixel] == 0.0 || d < depth[pixel])
+            {
+                depth[pixel] = d;
+            }
+        }
+        depth
+    }
+}
+
+/// Camera pixels painted onto lidar points, from the first camera in which each point is visible.
+#[derive(Clone, Debug)]
+pub struct PaintedPoints {
+    pub channels: Vec<SensorChannel>,
+    pub camera: Vec<i8>,       // Index in `channels`, -1 if not visible in any camera
+    pub pixels: Vec<[f32; 2]>, // NaN if not visible in any camera
+    pub pixel_index: Vec<i64>, // Row-major, -1 if not visible in any camera
+}
+
+impl PaintedPoints {
+    pub fn new(projections: &[(SensorChannel, DepthProjection)], num_points: usize) -> Self {
+        let mut painted = Self {
+            channels: projections.iter().map(|(channel, _)| *channel).collect(),
+            camera: vec![-1; num_points],
+            pixels: vec![[f32::NAN; 2]; num_points],
+            pixel_index: vec![-1; num_points],
+        };
+        for (c, (_, projection)) in projections.iter().enumerate() {
+            for i in 0..num_points {
+                if painted.camera[i] >= 0 {
+                    continue;
+                }
+                if let Some(pixel) = projection.pixel_index(i) {
+                    painted.camera[i] = c as i8;
+                    painted.pixels[i] = projection.pixels[i];
+                    painted.pixel_index[i] = pixel as i64;
+                }
+            }
+        }
+        painted
+    }
+}
+
+impl Tables {
+    /// Lidar points in the sensor frame of a camera, through the global frame at the timestamps of both sample data.
+    fn lidar_in_camera(&self, points: &[[f64; 3]], lidar: &SampleData, cam: &SampleData) -> Vec<[f64; 3]> {
+        let transform = self.sensor_pose(cam).between(Frame::Global, Frame::Sensor)
+            * self.sensor_pose(lidar).between(Frame::Sensor, Frame::Global);
+        points.iter().map(|p| transform.apply(*p)).collect()
+    }
+
+    fn lidar_xyz(&self, lidar: &SampleData) -> PyResult<Vec<[f64; 3]>> {
+        let points = self.lidar_points(lidar, false)?;
+        Ok(points.points().iter().map(|p| [p[0] as f64, p[1] as f64, p[2] as f64]).collect())
+    }
+
+    /// Projects the points of a lidar sample data into a camera sample data, dropping those closer than `min_dist`.
+    pub(crate) fn depth_projection(
+        &self, lidar: &SampleData, cam: &SampleData, min_dist: f64,
+    ) -> PyResult<DepthProjection> {
+        let camera = self.camera(cam)?;
+        let points = self.lidar_xyz(lidar)?;
+        Ok(DepthProjection::new(&camera, &self.lidar_in_camera(&points, lidar, cam), min_dist))
+    }
+
+    /// Projects the LIDAR_TOP points of a sample into its cameras among `channels` in parallel, reading the points
+    /// once. Returns the projections in the order of `channels` and the number of points.
+    pub(crate) fn sample_depth_projections(
+        &self, sample: &Sample, channels: &[SensorChannel], min_dist: f64,
+    ) -> PyResult<(Vec<(SensorChannel, DepthProjection)>, usize)> {
+        let lidar = self.sample_lidar_top(sample)?;
+        let points = self.lidar_xyz(lidar)?;
+        let cameras: Vec<_> = channels
+            .iter()
+            .filter_map(|&channel| self.sample_data.get(&sample.data[channel]))
+            .filter(|sd| sd.modality == SensorModality::Camera)
+            .collect();
+        let projections = cameras
+            .par_iter()
+            .map(|cam| {
+                let camera = self.camera(cam)?;
+                Ok((cam.channel, DepthProjection::new(&camera, &self.lidar_in_camera(&points, lidar, cam), min_dist)))
+            })
+            .collect::<PyResult<_>>()?;
+        Ok((projections, points.len()))
+    }
+}
@@ -1,10 +1,11 @@
 use std::borrow::Cow;
+use std::sync::Arc;
 
 use pyo3::prelude::*;
 use pyo3::types::PyDict;
 
 use super::ToPyDict;
-use crate::common::Visibility;
+use crate::common::{CategoryKind, Visibility};
 use crate::model::{InstanceModel, SampleAnnotationModel};
 use crate::table::AsRefToken;
 
@@ -16,6 +17,8 @@ pub struct Instance {
     pub nbr_annotations: u32,
     pub first_annotation_token: [u8; 16],
     pub last_annotation_token: [u8; 16],
+
+    pub anns: Box<[[u8; 16]]>, // From sample annotations, in temporal order
 }
 
 #[derive(Clone, Debug)]
@@ -36,11 +39,59 @@ pub struct SampleAnnotation<'a> {
     pub num_lidar_pts: u32,
     pub num_radar_pts: u32,
 
-    pub category_name: Cow<'a, str>, // From instance -> category
+    pub category: CategoryKind,                       // From instance -> category
+    pub category_name: Cow<'a, str>,                  // From instance -> category
+    pub attribute_names: Option<Box<[Cow<'a, str>]>>, // From attributes
+    pub class_id: Option<u16>,                        // From class mapping
+    pub class_name: Option<Arc<str>>,                 // From class mapping
+    pub velocity: Option<[f32; 3]>,                   // From prev/next annotations
+    pub velocity_ego: Option<[f32; 3]>,               // From prev/next annotations and ego pose
+}
+
+/// Selects which denormalized fields are computed for sample annotations. `category_name` is always computed, since
+/// `nuscenes.py` reads it from every annotation, and velocities are opt-in as they need a pass over all annotations.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AnnotationFields {
+    pub attribute_names: bool,
+    pub velocity: bool,
+}
+
+impl Default for AnnotationFields {
+    fn default() -> Self {
+        Self { attribute_names: true, velocity: false }
+    }
+}
+
+impl AnnotationFields {
+    pub const NAMES: [&'static str; 3] = ["category_name", "attribute_names", "velocity"];
+
+    pub fn parse<S: AsRef<str>>(names: &[S]) -> Result<Self, String> {
+        let mut fields = Self { attribute_names: false, velocity: false };
+        for name in names {
+            match name.as_ref() {
+                "category_name" => {}
+                "attribute_names" => fields.attribute_names = true,
+                "velocity" => fields.velocity = true,
+                other => {
+                    return Err(format!("Unknown annotation field '{other}', expected one of {:?}", Self::NAMES));
+                }
+            }
+        }
+        Ok(fields)
+    }
+
+    pub fn names(&self) -> Vec<&'static str> {
+        let flags = [true, self.attribute_names, self.velocity];
+        Self::NAMES.into_iter().zip(flags).filter_map(|(name, set)| set.then_some(name)).collect()
+    }
 }
 
 impl<'a> SampleAnnotation<'a> {
-    pub fn from_model(category_name: Cow<'a, str>, model: SampleAnnotationModel) -> Self {
+    pub fn from_model(
+        category: CategoryKind, category_name: Cow<'a, str>, attribute_names: Option<Box<[Cow<'a, str>]>>,
+        class: Option<(u16, Arc<str>)>, model: SampleAnnotationModel,
+    ) -> Self {
+        let (class_id, class_name) = class.unzip();
         Self {
             token: model.token,
             sample_token: model.sample_token,
@@ -54,19 +105,26 @@ impl<'a> SampleAnnotation<'a> {
             size: model.size,
             num_lidar_pts: model.num_lidar_pts,
             num_radar_pts: model.num_radar_pts,
+            category,
             category_name,
+            attribute_names,
+            class_id,
+            class_name,
+            velocity: None,
+            velocity_ego: None,
         }
     }
 }
 
-impl From<InstanceModel> for Instance {
-    fn from(model: InstanceModel) -> Self {
+impl Instance {
+    pub fn from_model(anns: Box<[[u8; 16]]>, model: InstanceModel) -> Self {
         Self {
             token: model.token,
             category_token: model.category_token,
             nbr_annotations: model.nbr_annotations,
             first_annotation_token: model.first_annotation_token,
             last_annotation_token: model.last_annotation_token,
+            anns,
         }
     }
 }
@@ -86,6 +144,7 @@ impl ToPyDict for Instance {
         dict.set_item("nbr_annotations", self.nbr_annotations)?;
         dict.set_item("first_annotation_token", hex::encode(self.first_annotation_token))?;
         dict.set_item("last_annotation_token", hex::encode(self.last_annotation_token))?;
+        dict.set_item("anns", self.anns.iter().map(hex::encode).collect::<Vec<_>>())?;
 
         Ok(dict)
     }
@@ -108,12 +167,25 @@ impl<'a> ToPyDict for SampleAnnotation<'a> {
         dict.set_item("prev", self.prev.map(hex::encode).unwrap_or_default())?;
         dict.set_item("next", self.next.map(hex::encode).unwrap_or_default())?;
         dict.set_item("visibility", self.visibility.to_string())?;
+        dict.set_item("visibility_level", self.visibility.level())?;
+        dict.set_item("visibility_range", self.visibility.range())?;
         dict.set_item("translation", self.translation)?;
         dict.set_item("rotation", self.rotation)?;
         dict.set_item("size", self.size)?;
         dict.set_item("num_lidar_pts", self.num_lidar_pts)?;
         dict.set_item("num_radar_pts", self.num_radar_pts)?;
         dict.set_item("category_name", self.category_name.as_ref())?;
+        if let Some(attribute_names) = &self.attribute_names {
+            dict.set_item("attribute_names", attribute_names.iter().map(|n| n.as_ref()).collect::<Vec<_>>())?;
+        }
+        if let (Some(class_id), Some(class_name)) = (self.class_id, &self.class_name) {
+            dict.set_item("class_id", class_id)?;
+            dict.set_item("class_name", class_name.as_ref())?;
+        }
+        if let (Some(velocity), Some(velocity_ego)) = (self.velocity, self.velocity_ego) {
+            dict.set_item("velocity", velocity)?;
+            dict.set_item("velocity_ego", velocity_ego)?;
+        }
 
         Ok(dict)
     }
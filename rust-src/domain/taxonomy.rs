@@ -4,6 +4,7 @@ use pyo3::prelude::*;
 use pyo3::types::PyDict;
 
 use super::ToPyDict;
+use crate::common::{AttributeKind, CategoryKind};
 use crate::model::{AttributeModel, CategoryModel};
 use crate::table::AsRefToken;
 
@@ -13,6 +14,8 @@ pub struct Attribute<'a> {
 
     pub name: Cow<'a, str>,
     pub desc: Cow<'a, str>,
+
+    pub kind: AttributeKind, // Parsed from name
 }
 
 #[derive(Clone, Debug)]
@@ -23,11 +26,14 @@ pub struct Category<'a> {
     pub desc: Cow<'a, str>,
 
     pub index: Option<u32>, // Only for lidarseg
+
+    pub kind: CategoryKind, // Parsed from name
 }
 
 impl<'a> From<AttributeModel<'a>> for Attribute<'a> {
     fn from(model: AttributeModel<'a>) -> Self {
-        Self { token: model.token, name: model.name, desc: model.description }
+        let kind = AttributeKind::from_name(&model.name);
+        Self { token: model.token, name: model.name, desc: model.description, kind }
     }
 }
 
@@ -44,6 +50,7 @@ impl<'a> ToPyDict for Attribute<'a> {
         dict.set_item("token", hex::encode(self.token))?;
         dict.set_item("name", self.name.as_ref())?;
         dict.set_item("description", self.desc.as_ref())?;
+        dict.set_item("kind", self.kind.clone())?;
 
         Ok(dict)
     }
@@ -51,7 +58,8 @@ impl<'a> ToPyDict for Attribute<'a> {
 
 impl<'a> From<CategoryModel<'a>> for Category<'a> {
     fn from(model: CategoryModel<'a>) -> Self {
-        Self { token: model.token, name: model.name, desc: model.description, index: model.index }
+        let kind = CategoryKind::from_name(&model.name);
+        Self { token: model.token, name: model.name, desc: model.description, index: model.index, kind }
     }
 }
 
@@ -71,6 +79,7 @@ impl<'a> ToPyDict for Category<'a> {
         if let Some(index) = self.index {
             dict.set_item("index", index)?;
         }
+        dict.set_item("kind", self.kind.clone())?;
 
         Ok(dict)
     }
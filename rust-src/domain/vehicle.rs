@@ -1,10 +1,12 @@
 use std::borrow::Cow;
+use std::sync::Arc;
 
 use pyo3::prelude::*;
 use pyo3::types::PyDict;
 
 use super::ToPyDict;
-use crate::common::{SensorChannel, SensorModality};
+use crate::common::{MapLocation, SensorChannel, SensorModality};
+use crate::mapmask::{MapMask, MaskSource};
 use crate::model::{CalibratedSensorModel, LogModel, MapModel, SensorModel};
 use crate::table::AsRefToken;
 
@@ -27,7 +29,8 @@ pub struct Log<'a> {
     pub location: Cow<'a, str>,
     pub date_captured: Cow<'a, str>,
 
-    pub map_token: [u8; 16], // From Map
+    pub map_location: MapLocation, // Parsed from location
+    pub map_token: [u8; 16],       // From Map
 }
 
 #[derive(Clone, Debug)]
@@ -38,7 +41,8 @@ pub struct Map<'a> {
     // [TODO]: Change to enums
     pub category: Cow<'a, str>,
     pub filename: Cow<'a, str>,
-    // pub mask: MapMask,  // From where?
+    pub mask: Arc<MaskSource>, // From filename, at native resolution
+    pub location: MapLocation, // From Log
 }
 
 #[derive(Clone, Debug)]
@@ -52,6 +56,7 @@ pub struct Sensor {
 impl<'a> Log<'a> {
     pub fn from_model(map_token: [u8; 16], model: LogModel<'a>) -> Self {
         Self {
+            map_location: MapLocation::from_name(&model.location),
             token: model.token,
             logfile: model.logfile,
             vehicle: model.vehicle,
@@ -103,6 +108,7 @@ impl<'a> ToPyDict for Log<'a> {
     fn to_py_dict<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
         let dict = PyDict::new(py);
         dict.set_item("token", hex::encode(self.token))?;
+        dict.set_item("logfile", self.logfile.as_ref())?;
         dict.set_item("vehicle", self.vehicle.as_ref())?;
         dict.set_item("date_captured", self.date_captured.as_ref())?;
         dict.set_item("location", self.location.as_ref())?;
@@ -112,13 +118,15 @@ impl<'a> ToPyDict for Log<'a> {
     }
 }
 
-impl<'a> From<MapModel<'a>> for Map<'a> {
-    fn from(model: MapModel<'a>) -> Self {
+impl<'a> Map<'a> {
+    pub fn from_model(location: MapLocation, mask: MaskSource, model: MapModel<'a>) -> Self {
         Self {
             token: model.token,
             log_tokens: model.log_tokens,
             category: model.category,
             filename: model.filename,
+            mask: Arc::new(mask),
+            location,
         }
     }
 }
@@ -136,6 +144,8 @@ impl<'a> ToPyDict for Map<'a> {
         dict.set_item("log_tokens", self.log_tokens.iter().map(hex::encode).collect::<Vec<_>>())?;
         dict.set_item("category", self.category.as_ref())?;
         dict.set_item("filename", self.filename.as_ref())?;
+        dict.set_item("mask", MapMask { source: self.mask.clone() })?;
+        dict.set_item("location", self.location.clone())?;
 
         Ok(dict)
     }
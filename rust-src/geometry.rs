@@ -0,0 +1,430 @@
+use std::ops::{Mul, Neg};
+use std::str::FromStr;
+
+use numpy::ndarray::arr2;
+use numpy::{PyArray1, PyArray2, PyReadonlyArray2, ToPyArray};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+use crate::domain::SampleData;
+use crate::nusc::Tables;
+
+/// Quaternion in `[w, x, y, z]` order, as stored in the tables.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Quaternion {
+    pub w: f64,
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+impl Quaternion {
+    pub const IDENTITY: Self = Self { w: 1.0, x: 0.0, y: 0.0, z: 0.0 };
+
+    pub fn new(w: f64, x: f64, y: f64, z: f64) -> Self {
+        Self { w, x, y, z }
+    }
+
+    pub fn from_array(q: [f64; 4]) -> Self {
+        Self::new(q[0], q[1], q[2], q[3])
+    }
+
+    pub fn from_f32(q: [f32; 4]) -> Self {
+        Self::new(q[0] as f64, q[1] as f64, q[2] as f64, q[3] as f64)
+    }
+
+    /// Builds the quaternion of a rotation matrix with Shepperd's method.
+    pub fn from_rotation_matrix(m: &[[f64; 3]; 3]) -> Self {
+        let trace = m[0][0] + m[1][1] + m[2][2];
+        let q = if trace > 0.0 {
+            let s = (trace + 1.0).sqrt() * 2.0;
+            Self::new(s / 4.0, (m[2][1] - m[1][2]) / s, (m[0][2] - m[2][0]) / s, (m[1][0] - m[0][1]) / s)
+        } else if m[0][0] > m[1][1] && m[0][0] > m[2][2] {
+            let s = (1.0 + m[0][0] - m[1][1] - m[2][2]).sqrt() * 2.0;
+            Self::new((m[2][1] - m[1][2]) / s, s / 4.0, (m[0][1] + m[1][0]) / s, (m[0][2] + m[2][0]) / s)
+        } else if m[1][1] > m[2][2] {
+            let s = (1.0 + m[1][1] - m[0][0] - m[2][2]).sqrt() * 2.0;
+            Self::new((m[0][2] - m[2][0]) / s, (m[0][1] + m[1][0]) / s, s / 4.0, (m[1][2] + m[2][1]) / s)
+        } else {
+            let s = (1.0 + m[2][2] - m[0][0] - m[1][1]).sqrt() * 2.0;
+            Self::new((m[1][0] - m[0][1]) / s, (m[0][2] + m[2][0]) / s, (m[1][2] + m[2][1]) / s, s / 4.0)
+        };
+        q.normalize()
+    }
+
+    pub fn to_array(self) -> [f64; 4] {
+        [self.w, self.x, self.y, self.z]
+    }
+
+    pub fn dot(&self, other: &Self) -> f64 {
+        self.w * other.w + self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    pub fn norm(&self) -> f64 {
+        self.dot(self).sqrt()
+    }
+
+    pub fn normalize(&self) -> Self {
+        let norm = self.norm();
+        if norm == 0.0 {
+            return Self::IDENTITY;
+        }
+        Self::new(self.w / norm, self.x / norm, self.y / norm, self.z / norm)
+    }
+
+    pub fn conjugate(&self) -> Self {
+        Self::new(self.w, -self.x, -self.y, -self.z)
+    }
+
+    /// Inverse rotation, assuming a unit quaternion.
+    pub fn inverse(&self) -> Self {
+        self.conjugate()
+    }
+
+    /// Rotates a vector, assuming a unit quaternion.
+    pub fn rotate(&self, v: [f64; 3]) -> [f64; 3] {
+        // v' = v + 2w (u x v) + 2 u x (u x v), with u the vector part.
+        let u = [self.x, self.y, self.z];
+        let t = cross(u, v).map(|c| 2.0 * c);
+        let ut = cross(u, t);
+        [v[0] + self.w * t[0] + ut[0], v[1] + self.w * t[1] + ut[1], v[2] + self.w * t[2] + ut[2]]
+    }
+
+    pub fn rotation_matrix(&self) -> [[f64; 3]; 3] {
+        let Self { w, x, y, z } = self.normalize();
+        [
+            [1.0 - 2.0 * (y * y + z * z), 2.0 * (x * y - w * z), 2.0 * (x * z + w * y)],
+            [2.0 * (x * y + w * z), 1.0 - 2.0 * (x * x + z * z), 2.0 * (y * z - w * x)],
+            [2.0 * (x * z - w * y), 2.0 * (y * z + w * x), 1.0 - 2.0 * (x * x + y * y)],
+        ]
+    }
+
+    /// Yaw angle of the rotated x-axis projected into the xy-plane, as in `quaternion_yaw` of the eval code.
+    /// Only meaningful for rotations in the lidar, ego or global frames, not in camera frames.
+    pub fn yaw(&self) -> f64 {
+        let v = self.normalize().rotate([1.0, 0.0, 0.0]);
+        v[1].atan2(v[0])
+    }
+
+    /// Spherical linear interpolation along the shortest path, with `t = 0` giving `a` and `t = 1` giving `b`.
+    pub fn slerp(a: &Self, b: &Self, t: f64) -> Self {
+        let a = a.normalize();
+        let mut b = b.normalize();
+        let mut dot = a.dot(&b);
+        if dot < 0.0 {
+            b = -b;
+            dot = -dot;
+        }
+
+        // Fall back to linear interpolation when the quaternions are too close for a stable division.
+        if dot > 0.9995 {
+            let lerp =
+                Self::new(a.w + t * (b.w - a.w), a.x + t * (b.x - a.x), a.y + t * (b.y - a.y), a.z + t * (b.z - a.z));
+            return lerp.normalize();
+        }
+
+        let theta_0 = dot.acos();
+        let theta = theta_0 * t;
+        let s1 = theta.sin() / theta_0.sin();
+        let s0 = theta.cos() - dot * s1;
+        Self::new(s0 * a.w + s1 * b.w, s0 * a.x + s1 * b.x, s0 * a.y + s1 * b.y, s0 * a.z + s1 * b.z)
+    }
+}
+
+impl Mul for Quaternion {
+    type Output = Self;
+
+    /// Hamilton product, i.e. `self * other` applies `other` first.
+    fn mul(self, o: Self) -> Self {
+        Self::new(
+            self.w * o.w - self.x * o.x - self.y * o.y - self.z * o.z,
+            self.w * o.x + self.x * o.w + self.y * o.z - self.z * o.y,
+            self.w * o.y - self.x * o.z + self.y * o.w + self.z * o.x,
+            self.w * o.z + self.x * o.y - self.y * o.x + self.z * o.w,
+        )
+    }
+}
+
+impl Neg for Quaternion {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self::new(-self.w, -self.x, -self.y, -self.z)
+    }
+}
+
+pub fn cross(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [a[1] * b[2] - a[2] * b[1], a[2] * b[0] - a[0] * b[2], a[0] * b[1] - a[1] * b[0]]
+}
+
+/// Rigid transform that rotates then translates, mapping points from a source frame into a target frame.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transform {
+    pub rotation: Quaternion,
+    pub translation: [f64; 3],
+}
+
+impl Transform {
+    pub const IDENTITY: Self = Self { rotation: Quaternion::IDENTITY, translation: [0.0; 3] };
+
+    pub fn new(rotation: Quaternion, translation: [f64; 3]) -> Self {
+        Self { rotation: rotation.normalize(), translation }
+    }
+
+    /// Builds the transform from a `translation` and `rotation` record, e.g. an ego pose or calibrated sensor.
+    pub fn from_pose(translation: [f32; 3], rotation: [f32; 4]) -> Self {
+        Self::new(Quaternion::from_f32(rotation), translation.map(|t| t as f64))
+    }
+
+    pub fn inverse(&self) -> Self {
+        let rotation = self.rotation.inverse();
+        let translation = rotation.rotate(self.translation).map(|t| -t);
+        Self { rotation, translation }
+    }
+
+    /// Transforms a point.
+    pub fn apply(&self, p: [f64; 3]) -> [f64; 3] {
+        let r = self.rotation.rotate(p);
+        [r[0] + self.translation[0], r[1] + self.translation[1], r[2] + self.translation[2]]
+    }
+
+    /// Linear interpolation of the translation and spherical interpolation of the rotation.
+    pub fn interpolate(a: &Self, b: &Self, t: f64) -> Self {
+        let translation = [0, 1, 2].map(|i| a.translation[i] + t * (b.translation[i] - a.translation[i]));
+        Self { rotation: Quaternion::slerp(&a.rotation, &b.rotation, t), translation }
+    }
+
+    /// The 4x4 homogeneous matrix of the transform.
+    pub fn matrix(&self) -> [[f64; 4]; 4] {
+        let r = self.rotation.rotation_matrix();
+        let t = self.translation;
+        [
+            [r[0][0], r[0][1], r[0][2], t[0]],
+            [r[1][0], r[1][1], r[1][2], t[1]],
+            [r[2][0], r[2][1], r[2][2], t[2]],
+            [0.0, 0.0, 0.0, 1.0],
+        ]
+    }
+}
+
+impl Mul for Transform {
+    type Output = Self;
+
+    /// Composition, i.e. `self * other` applies `other` first.
+    fn mul(self, other: Self) -> Self {
+        Self { rotation: (self.rotation * other.rotation).normalize(), translation: self.apply(other.translation) }
+    }
+}
+
+/// Coordinate frames in which data can be expressed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Frame {
+    Sensor,
+    Ego,
+    Global,
+}
+
+impl FromStr for Frame {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sensor" => Ok(Self::Sensor),
+            "ego" => Ok(Self::Ego),
+            "global" => Ok(Self::Global),
+            _ => Err(format!("Unknown frame '{s}', expected one of 'sensor', 'ego' and 'global'")),
+        }
+    }
+}
+
+/// Poses of a sample data: the calibrated sensor in the ego frame and the ego vehicle in the global frame.
+#[derive(Clone, Copy, Debug)]
+pub struct SensorPose {
+    pub sensor_to_ego: Transform,
+    pub ego_to_global: Transform,
+}
+
+impl SensorPose {
+    pub fn sensor_to_global(&self) -> Transform {
+        self.ego_to_global * self.sensor_to_ego
+    }
+
+    /// Returns the transform from `source` to `target`.
+    pub fn between(&self, source: Frame, target: Frame) -> Transform {
+        let to_global = |frame| match frame {
+            Frame::Sensor => self.sensor_to_global(),
+            Frame::Ego => self.ego_to_global,
+            Frame::Global => Transform::IDENTITY,
+        };
+        to_global(target).inverse() * to_global(source)
+    }
+}
+
+impl Tables {
+    pub(crate) fn sensor_pose(&self, sd: &SampleData) -> SensorPose {
+        let cal = self.calib.get(&sd.calibrated_sensor_token).unwrap();
+        let ego = self.ego_pose.get(&sd.ego_pose_token).unwrap();
+        SensorPose {
+            sensor_to_ego: Transform::from_pose(cal.translation, cal.rotation),
+            ego_to_global: Transform::from_pose(ego.translation, ego.rotation),
+        }
+    }
+}
+
+pub fn matrix_to_pyarray<'py>(py: Python<'py>, m: &[[f64; 4]; 4]) -> Bound<'py, PyArray2<f64>> {
+    arr2(m).to_pyarray(py)
+}
+
+/// Reads a 4x4 homogeneous rigid transform matrix.
+pub fn matrix_from_pyarray(matrix: PyReadonlyArray2<'_, f64>) -> PyResult<Transform> {
+    let m = matrix.as_array();
+    if m.shape() != [4, 4] {
+        return Err(PyValueError::new_err(format!("Expected a 4x4 matrix, got shape {:?}", m.shape())));
+    }
+    let rotation = [0, 1, 2].map(|i| [0, 1, 2].map(|j| m[[i, j]]));
+    let translation = [0, 1, 2].map(|i| m[[i, 3]]);
+    Ok(Transform::new(Quaternion::from_rotation_matrix(&rotation), translation))
+}
+
+/// Converts a pose to a 4x4 homogeneous transformation matrix, as `transform_matrix` in `geometry_utils.py`.
+#[pyfunction]
+#[pyo3(signature = (translation=[0.0; 3], rotation=[1.0, 0.0, 0.0, 0.0], inverse=false))]
+pub fn transform_matrix(
+    py: Python<'_>, translation: [f64; 3], rotation: [f64; 4], inverse: bool,
+) -> Bound<'_, PyArray2<f64>> {
+    let transform = Transform::new(Quaternion::from_array(rotation), translation);
+    let transform = if inverse { transform.inverse() } else { transform };
+    matrix_to_pyarray(py, &transform.matrix())
+}
+
+/// Returns the yaw angle in radians of a `[w, x, y, z]` rotation, as `quaternion_yaw` in the eval code.
+#[pyfunction]
+pub fn quaternion_yaw(rotation: [f64; 4]) -> f64 {
+    Quaternion::from_array(rotation).yaw()
+}
+
+/// Spherically interpolates between two `[w, x, y, z]` rotations, with `t = 0` giving `q0` and `t = 1` giving `q1`.
+#[pyfunction]
+pub fn quaternion_slerp(py: Python<'_>, q0: [f64; 4], q1: [f64; 4], t: f64) -> Bound<'_, PyArray1<f64>> {
+    let q = Quaternion::slerp(&Quaternion::from_array(q0), &Quaternion::from_array(q1), t);
+    q.to_array().to_pyarray(py)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::{FRAC_PI_2, FRAC_PI_4, FRAC_PI_8};
+
+    use super::*;
+
+    fn assert_close(actual: &[f64], expected: &[f64]) {
+        let close = actual.iter().zip(expected).all(|(a, e)| (a - e).abs() < 1e-12);
+        assert!(close && actual.len() == expected.len(), "{actual:?} != {expected:?}");
+    }
+
+    /// Quaternions equal up to sign represent the same rotation, and `pyquaternion` picks either.
+    fn assert_same_rotation(actual: Quaternion, expected: [f64; 4]) {
+        let sign = if actual.dot(&Quaternion::from_array(expected)) < 0.0 { -1.0 } else { 1.0 };
+        assert_close(&actual.to_array().map(|c| sign * c), &expected);
+    }
+
+    /// Rotation of `angle` around the unit `axis`, as `Quaternion(axis=axis, angle=angle)` in `pyquaternion`.
+    fn axis_angle(axis: [f64; 3], angle: f64) -> Quaternion {
+        let s = (angle / 2.0).sin();
+        Quaternion::new((angle / 2.0).cos(), s * axis[0], s * axis[1], s * axis[2])
+    }
+
+    #[test]
+    fn slerp_interpolates_along_the_great_arc() {
+        let yaw_90 = axis_angle([0.0, 0.0, 1.0], FRAC_PI_2);
+        let q = Quaternion::slerp(&Quaternion::IDENTITY, &yaw_90, 0.5);
+        assert_close(&q.to_array(), &[FRAC_PI_8.cos(), 0.0, 0.0, FRAC_PI_8.sin()]);
+        assert_close(&Quaternion::slerp(&Quaternion::IDENTITY, &yaw_90, 0.0).to_array(), &[1.0, 0.0, 0.0, 0.0]);
+        assert_close(&Quaternion::slerp(&Quaternion::IDENTITY, &yaw_90, 1.0).to_array(), &yaw_90.to_array());
+        // Unnormalized inputs are normalized first.
+        let q = Quaternion::slerp(&Quaternion::new(2.0, 0.0, 0.0, 0.0), &yaw_90, 0.5);
+        assert_close(&q.to_array(), &[FRAC_PI_8.cos(), 0.0, 0.0, FRAC_PI_8.sin()]);
+    }
+
+    #[test]
+    fn slerp_takes_the_shortest_path_between_antipodal_quaternions() {
+        // `-yaw_90` is the same rotation as `yaw_90`, so the midpoint is still a yaw of 45 degrees and not 135.
+        let q = Quaternion::slerp(&Quaternion::IDENTITY, &-axis_angle([0.0, 0.0, 1.0], FRAC_PI_2), 0.5);
+        assert_same_rotation(q, [FRAC_PI_8.cos(), 0.0, 0.0, FRAC_PI_8.sin()]);
+        assert_close(&[q.yaw()], &[FRAC_PI_4]);
+    }
+
+    #[test]
+    fn slerp_falls_back_to_lerp_for_near_parallel_quaternions() {
+        // A dot product of cos(0.005) is above the 0.9995 threshold, where the normalized midpoint is exact.
+        let q = Quaternion::slerp(&Quaternion::IDENTITY, &axis_angle([0.0, 0.0, 1.0], 0.01), 0.5);
+        assert_close(&q.to_array(), &[0.0025f64.cos(), 0.0, 0.0, 0.0025f64.sin()]);
+        let q = Quaternion::slerp(&Quaternion::IDENTITY, &Quaternion::IDENTITY, 0.3);
+        assert_close(&q.to_array(), &[1.0, 0.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn from_rotation_matrix_covers_each_branch() {
+        // Positive trace.
+        let m = [[0.0, -1.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, 1.0]];
+        assert_same_rotation(Quaternion::from_rotation_matrix(&m), [FRAC_PI_4.cos(), 0.0, 0.0, FRAC_PI_4.sin()]);
+        // Rotations of 150 degrees have a negative trace, with the largest diagonal entry on their axis.
+        let angle = 150f64.to_radians();
+        for axis in [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]] {
+            let q = axis_angle(axis, angle);
+            let m = q.rotation_matrix();
+            assert!(m[0][0] + m[1][1] + m[2][2] < 0.0);
+            assert_same_rotation(Quaternion::from_rotation_matrix(&m), q.to_array());
+        }
+        // Half turns have a zero w component.
+        let m = [[-1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, -1.0]];
+        assert_same_rotation(Quaternion::from_rotation_matrix(&m), [0.0, 0.0, 1.0, 0.0]);
+    }
+
+    #[test]
+    fn rotate_matches_the_rotation_matrix() {
+        // A third of a turn around the diagonal cycles the axes: x -> y -> z -> x.
+        let q = Quaternion::new(0.5, 0.5, 0.5, 0.5);
+        assert_close(&q.rotate([1.0, 0.0, 0.0]), &[0.0, 1.0, 0.0]);
+        assert_close(&q.rotate([0.0, 0.0, 2.0]), &[2.0, 0.0, 0.0]);
+        assert_close(&q.rotation_matrix().concat(), &[0.0, 0.0, 1.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0]);
+        assert_close(&q.inverse().rotate(q.rotate([0.3, -1.2, 4.0])), &[0.3, -1.2, 4.0]);
+    }
+
+    #[test]
+    fn transform_inverse_matches_transform_matrix() {
+        // `transform_matrix([1, 2, 3], Quaternion(0.5, 0.5, 0.5, 0.5), inverse=True)` is [R^T, -R^T t].
+        let transform = Transform::new(Quaternion::new(0.5, 0.5, 0.5, 0.5), [1.0, 2.0, 3.0]);
+        let expected = [0.0, 1.0, 0.0, -2.0, 0.0, 0.0, 1.0, -3.0, 1.0, 0.0, 0.0, -1.0, 0.0, 0.0, 0.0, 1.0];
+        assert_close(&transform.inverse().matrix().concat(), &expected);
+        assert_close(&transform.inverse().apply(transform.apply([0.3, -1.2, 4.0])), &[0.3, -1.2, 4.0]);
+    }
+
+    #[test]
+    fn transform_composition_applies_the_right_operand_first() {
+        let a = Transform::new(axis_angle([0.0, 0.0, 1.0], FRAC_PI_2), [1.0, 0.0, 0.0]);
+        let b = Transform::new(axis_angle([1.0, 0.0, 0.0], FRAC_PI_2), [0.0, 2.0, 0.0]);
+        let p = [1.0, 2.0, 3.0];
+        assert_close(&(a * b).apply(p), &a.apply(b.apply(p)));
+        // b maps p to (1, -1, 2) and a to (2, 1, 2).
+        assert_close(&(a * b).apply(p), &[2.0, 1.0, 2.0]);
+        // The matrix of the composition is the product of the matrices.
+        let (ma, mb) = (a.matrix(), b.matrix());
+        let product = [0, 1, 2, 3].map(|i| [0, 1, 2, 3].map(|j| (0..4).map(|k| ma[i][k] * mb[k][j]).sum::<f64>()));
+        assert_close(&(a * b).matrix().concat(), &product.concat());
+    }
+
+    #[test]
+    fn sensor_pose_between_chains_the_calibration_and_ego_pose() {
+        let pose = SensorPose {
+            sensor_to_ego: Transform::new(axis_angle([0.0, 0.0, 1.0], FRAC_PI_2), [1.0, 0.0, 1.5]),
+            ego_to_global: Transform::new(axis_angle([0.0, 0.0, 1.0], -FRAC_PI_2), [100.0, 200.0, 0.0]),
+        };
+        let p = [2.0, 0.0, 0.0];
+        // The sensor is turned left on the ego vehicle, which is turned right in the global frame.
+        assert_close(&pose.between(Frame::Sensor, Frame::Ego).apply(p), &[1.0, 2.0, 1.5]);
+        assert_close(&pose.between(Frame::Sensor, Frame::Global).apply(p), &[102.0, 199.0, 1.5]);
+        assert_close(&pose.between(Frame::Global, Frame::Sensor).apply([102.0, 199.0, 1.5]), &p);
+        assert_close(&pose.between(Frame::Ego, Frame::Global).apply([1.0, 2.0, 1.5]), &[102.0, 199.0, 1.5]);
+        assert_close(&pose.between(Frame::Ego, Frame::Ego).matrix().concat(), &Transform::IDENTITY.matrix().concat());
+    }
+}
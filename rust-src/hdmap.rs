@@ -0,0 +1,576 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use numpy::ndarray::{Array2, Array3};
+use numpy::{IntoPyArray, PyArray2, PyArray3};
+use pyo3::exceptions::{PyFileNotFoundError, PyKeyError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList, PyTuple};
+use rayon::prelude::*;
+use serde_json::Value;
+
+use crate::common::MapLocation;
+use crate::nusc::Tables;
+
+mod arcline;
+mod lanegraph;
+mod layers;
+mod queries;
+mod raster;
+mod rtree;
+mod shapes;
+
+use arcline::{ArclinePath, Pose, discretize_lane};
+use lanegraph::LaneDirection;
+pub use layers::*;
+use raster::RasterFrame;
+use shapes::Bounds;
+
+// Layers rasterized by `rasterize` by default, the static layers of `StaticLayerRasterizer` with lanes and stop lines.
+const RASTER_LAYERS: [MapLayer; 5] =
+    [MapLayer::DrivableArea, MapLayer::Lane, MapLayer::PedCrossing, MapLayer::Walkway, MapLayer::StopLine];
+
+// Pixels per meter of `get_map_mask` without a canvas size.
+const MAP_MASK_SCALE: f64 = 10.0;
+
+/// Converts a JSON value to the equivalent Python object, as `json.load`.
+fn json_to_py<'py>(py: Python<'py>, value: &Value) -> PyResult<Bound<'py, PyAny>> {
+    Ok(match value {
+        Value::Null => py.None().into_bound(py),
+        Value::Bool(b) => b.into_pyobject(py)?.to_owned().into_any(),
+        Value::Number(n) => match n.as_i64() {
+            Some(i) => i.into_pyobject(py)?.into_any(),
+            None => n.as_f64().unwrap_or(f64::NAN).into_pyobject(py)?.into_any(),
+        },
+        Value::String(s) => s.into_pyobject(py)?.into_any(),
+        Value::Array(values) => {
+            PyList::new(py, values.iter().map(|v| json_to_py(py, v)).collect::<PyResult<Vec<_>>>()?)?.into_any()
+        }
+        Value::Object(fields) => {
+            let dict = PyDict::new(py);
+            for (key, v) in fields {
+                dict.set_item(key, json_to_py(py, v)?)?;
+            }
+            dict.into_any()
+        }
+    })
+}
+
+fn parse_layer(name: &str) -> PyResult<MapLayer> {
+    name.parse().map_err(PyValueError::new_err)
+}
+
+fn parse_layers(names: Option<Vec<String>>, default: &[MapLayer]) -> PyResult<Vec<MapLayer>> {
+    match names {
+        Some(names) => names.iter().map(|name| parse_layer(name)).collect(),
+        None => Ok(default.to_vec()),
+    }
+}
+
+fn is_positive(value: f64) -> bool {
+    value.is_finite() && value > 0.0
+}
+
+/// Checks that lanes are discretized with a positive and finite spacing.
+fn check_resolution(resolution_meters: f64) -> PyResult<()> {
+    if !is_positive(resolution_meters) {
+        return Err(PyValueError::new_err(format!("Invalid resolution {resolution_meters}")));
+    }
+    Ok(())
+}
+
+/// Checks that the rasters of a `(rows, cols)` canvas, at most the two float32 channels of the lane directions, fit
+/// in an allocation.
+fn check_canvas((rows, cols): (usize, usize)) -> PyResult<()> {
+    let bytes = rows.checked_mul(cols).and_then(|size| size.checked_mul(2 * size_of::<f32>()));
+    if bytes.is_none_or(|bytes| bytes > isize::MAX as usize) {
+        return Err(PyValueError::new_err(format!("Canvas of {rows}x{cols} pixels is too large")));
+    }
+    Ok(())
+}
+
+fn patch_bounds((x_min, y_min, x_max, y_max): (f64, f64, f64, f64)) -> Bounds {
+    Bounds([x_min, y_min, x_max, y_max])
+}
+
+fn poses_to_pyarray<'py>(py: Python<'py>, poses: &[Pose]) -> Bound<'py, PyArray2<f64>> {
+    Array2::from_shape_vec((poses.len(), 3), poses.concat()).unwrap().into_pyarray(py)
+}
+
+fn points_to_pyarray<'py>(py: Python<'py>, points: &[[f64; 2]]) -> Bound<'py, PyArray2<f64>> {
+    Array2::from_shape_vec((points.len(), 2), points.concat()).unwrap().into_pyarray(py)
+}
+
+/// Stacks the masks of layers in a raster frame into a `(C, H, W)` uint8 array, leaving traffic lights out.
+fn layer_masks_to_pyarray<'py>(
+    py: Python<'py>, map: &HdMap, layers: &[MapLayer], frame: &RasterFrame,
+) -> PyResult<Bound<'py, PyArray3<u8>>> {
+    let masks = py.detach(|| map.layer_masks(layers, frame)).map_err(PyValueError::new_err)?;
+    let data = masks.iter().flat_map(|(_, mask)| mask).copied().collect();
+    Ok(Array3::from_shape_vec((masks.len(), frame.rows, frame.cols), data).unwrap().into_pyarray(py))
+}
+
+/// Rasterizes layers and lane directions in a raster frame to a dict with the names of the rasterized layers, their
+/// `(C, H, W)` uint8 masks and the `(2, H, W)` float32 cosine and sine of the lane directions.
+fn raster_to_dict<'py>(
+    py: Python<'py>, map: &HdMap, layers: &[MapLayer], frame: &RasterFrame,
+) -> PyResult<Bound<'py, PyDict>> {
+    let (masks, directions) =
+        py.detach(|| rayon::join(|| map.layer_masks(layers, frame), || map.lane_directions(frame)));
+    let masks = masks.map_err(PyValueError::new_err)?;
+    let names: Vec<_> = masks.iter().map(|(layer, _)| layer.as_str()).collect();
+    let data = masks.iter().flat_map(|(_, mask)| mask).copied().collect();
+    let dict = PyDict::new(py);
+    dict.set_item("layer_names", names)?;
+    dict.set_item(
+        "masks",
+        Array3::from_shape_vec((masks.len(), frame.rows, frame.cols), data).unwrap().into_pyarray(py),
+    )?;
+    dict.set_item(
+        "lane_direction",
+        Array3::from_shape_vec((2, frame.rows, frame.cols), directions).unwrap().into_pyarray(py),
+    )?;
+    Ok(dict)
+}
+
+/// Loads the map expansion of a location, raising `FileNotFoundError` if it is missing.
+fn load_map(py: Python<'_>, dataroot: &Path, location: &MapLocation) -> PyResult<HdMap> {
+    if let MapLocation::Other(name) = location {
+        return Err(PyValueError::new_err(format!("Unknown map location {name:?}")));
+    }
+    let path = dataroot.join("maps").join("expansion").join(format!("{location}.json"));
+    if !path.exists() {
+        return Err(PyFileNotFoundError::new_err(format!("Map expansion not found at {}", path.display())));
+    }
+    py.detach(|| HdMap::load(dataroot, location.as_str())).map_err(PyValueError::new_err)
+}
+
+/// Vector map of a location from the map expansion, as `NuScenesMap` in `map_api.py`.
+///
+/// Records of the non-geometric layers are indexed by token and by an R-tree over their bounds. Records are returned
+/// as dicts with the shortcuts of `NuScenesMap` to the nodes of their geometry.
+#[pyclass(module = "nuscenes._lib", frozen)]
+#[derive(Clone, Debug)]
+pub struct VectorMap {
+    pub map: Arc<HdMap>,
+}
+
+impl VectorMap {
+    fn record<'a>(&'a self, layer: MapLayer, token: &str) -> PyResult<&'a MapRecord> {
+        self.map
+            .layer(layer)
+            .map_err(PyValueError::new_err)?
+            .get(token)
+            .ok_or_else(|| PyKeyError::new_err(token.to_string()))
+    }
+
+    fn connectivity(&self, lane_token: &str) -> PyResult<&Connectivity> {
+        self.map
+            .connectivity
+            .get(lane_token)
+            .ok_or_else(|| PyValueError::new_err(format!("{lane_token} is not a valid lane.")))
+    }
+
+    fn arcline_paths(&self, lane_token: &str) -> PyResult<&[ArclinePath]> {
+        match self.map.arcline_paths.get(lane_token) {
+            Some(paths) if !paths.is_empty() => Ok(paths),
+            _ => Err(PyValueError::new_err(format!(
+                "Error: Lane with token {lane_token} does not have a valid arcline path!"
+            ))),
+        }
+    }
+
+    fn graph_lane(&self, lane_token: &str) -> PyResult<usize> {
+        self.map.lane_graph.index.get(lane_token).copied().ok_or_else(|| PyKeyError::new_err(lane_token.to_string()))
+    }
+
+    fn node_tokens(&self, nodes: &[usize]) -> Vec<&str> {
+        nodes.iter().map(|&n| self.map.node_tokens[n].as_str()).collect()
+    }
+
+    /// Raster frame of `get_map_mask`, covering the whole map if `patch_box` is `None` and sampled every 0.1 meters
+    /// if `canvas_size` is `None`.
+    fn patch_frame(
+        &self, patch_box: Option<(f64, f64, f64, f64)>, patch_angle: f64, canvas_size: Option<(usize, usize)>,
+    ) -> PyResult<RasterFrame> {
+        let [map_width, map_height] = self.map.canvas_edge;
+        let (x, y, height, width) = patch_box.unwrap_or((map_width / 2.0, map_height / 2.0, map_height, map_width));
+        if !is_positive(height) || !is_positive(width) {
+            return Err(PyValueError::new_err(format!("Invalid patch size ({height}, {width})")));
+        }
+        let canvas = canvas_size
+            .unwrap_or(((height * MAP_MASK_SCALE).round() as usize, (width * MAP_MASK_SCALE).round() as usize));
+        check_canvas(canvas)?;
+        Ok(RasterFrame::patch([x, y], patch_angle.to_radians(), (height, width), canvas))
+    }
+
+    fn holes_to_py<'py>(&self, py: Python<'py>, holes: &[Vec<usize>]) -> PyResult<Vec<Bound<'py, PyDict>>> {
+        holes
+            .iter()
+            .map(|hole| {
+                let dict = PyDict::new(py);
+                dict.set_item("node_tokens", self.node_tokens(hole))?;
+                Ok(dict)
+            })
+            .collect()
+    }
+}
+
+#[pymethods]
+impl VectorMap {
+    #[new]
+    #[pyo3(signature = (dataroot="/data/sets/nuscenes", map_name="singapore-onenorth"))]
+    fn new(py: Python<'_>, dataroot: &str, map_name: &str) -> PyResult<Self> {
+        let location = MapLocation::from_name(map_name);
+        if let MapLocation::Other(_) = location {
+            return Err(PyValueError::new_err(format!("Unknown map name {map_name}!")));
+        }
+        Ok(Self { map: Arc::new(load_map(py, Path::new(dataroot), &location)?) })
+    }
+
+    #[getter]
+    fn map_name(&self) -> &str {
+        &self.map.name
+    }
+
+    #[getter]
+    fn version(&self) -> &str {
+        &self.map.version
+    }
+
+    #[getter]
+    fn canvas_edge(&self) -> (f64, f64) {
+        self.map.canvas_edge.into()
+    }
+
+    #[getter]
+    fn layer_names(&self) -> Vec<&'static str> {
+        MapLayer::ALL.iter().map(MapLayer::as_str).collect()
+    }
+
+    #[getter]
+    fn non_geometric_layers(&self) -> Vec<&'static str> {
+        MapLayer::NON_GEOMETRIC.iter().map(MapLayer::as_str).collect()
+    }
+
+    #[getter]
+    fn non_geometric_polygon_layers(&self) -> Vec<&'static str> {
+        MapLayer::POLYGON_LAYERS.iter().map(MapLayer::as_str).collect()
+    }
+
+    #[getter]
+    fn non_geometric_line_layers(&self) -> Vec<&'static str> {
+        MapLayer::LINE_LAYERS.iter().map(MapLayer::as_str).collect()
+    }
+
+    /// Returns the tokens of the records of a layer, in order.
+    fn layer_tokens(&self, layer_name: &str) -> PyResult<Vec<&str>> {
+        let layer = parse_layer(layer_name)?;
+        Ok((0..self.map.layer_len(layer)).map(|i| self.map.record_token(layer, i)).collect())
+    }
+
+    /// Returns the index of a record in its layer.
+    fn getind(&self, layer_name: &str, token: &str) -> PyResult<usize> {
+        self.map.record_index(parse_layer(layer_name)?, token).ok_or_else(|| PyKeyError::new_err(token.to_string()))
+    }
+
+    /// Returns a record of a layer as a dict.
+    fn get<'py>(&self, py: Python<'py>, layer_name: &str, token: &str) -> PyResult<Bound<'py, PyDict>> {
+        let layer = parse_layer(layer_name)?;
+        let index = self.map.record_index(layer, token).ok_or_else(|| PyKeyError::new_err(token.to_string()))?;
+        let dict = PyDict::new(py);
+        dict.set_item("token", token)?;
+        match layer {
+            MapLayer::Node => {
+                let [x, y] = self.map.nodes[index];
+                dict.set_item("x", x)?;
+                dict.set_item("y", y)?;
+            }
+            MapLayer::Line => dict.set_item("node_tokens", self.node_tokens(&self.map.lines[index].nodes))?,
+            MapLayer::Polygon => {
+                let polygon = &self.map.polygons[index];
+                dict.set_item("exterior_node_tokens", self.node_tokens(&polygon.exterior))?;
+                dict.set_item("holes", self.holes_to_py(py, &polygon.holes)?)?;
+            }
+            _ => {
+                let record = &self.map.layers[&layer].records[index];
+                for (key, value) in &record.fields {
+                    dict.set_item(key, json_to_py(py, value)?)?;
+                }
+                match record.shape {
+                    Shape::Line(line) => dict.set_item("node_tokens", self.node_tokens(&self.map.lines[line].nodes))?,
+                    Shape::Polygons(ref polys)
+                        if MapLayer::POLYGON_LAYERS.contains(&layer) && layer != MapLayer::DrivableArea =>
+                    {
+                        let polygon = &self.map.polygons[polys[0]];
+                        dict.set_item("exterior_node_tokens", self.node_tokens(&polygon.exterior))?;
+                        dict.set_item("holes", self.holes_to_py(py, &polygon.holes)?)?;
+                    }
+                    Shape::Polygons(_) => {}
+                }
+            }
+        }
+        Ok(dict)
+    }
+
+    /// Returns the exterior of a polygon as an `(N, 2)` array and its non-empty holes.
+    fn extract_polygon<'py>(&self, py: Python<'py>, polygon_token: &str) -> PyResult<Bound<'py, PyTuple>> {
+        let index =
+            self.map.polygon_index.get(polygon_token).ok_or_else(|| PyKeyError::new_err(polygon_token.to_string()))?;
+        let polygon = &self.map.polygons[*index].shape;
+        let holes: Vec<_> = polygon.holes.iter().map(|hole| points_to_pyarray(py, hole)).collect();
+        (points_to_pyarray(py, &polygon.exterior), holes).into_pyobject(py)
+    }
+
+    /// Returns the nodes of a line as an `(N, 2)` array.
+    fn extract_line<'py>(&self, py: Python<'py>, line_token: &str) -> PyResult<Bound<'py, PyArray2<f64>>> {
+        let index = self.map.line_index.get(line_token).ok_or_else(|| PyKeyError::new_err(line_token.to_string()))?;
+        let nodes: Vec<_> = self.map.lines[*index].nodes.iter().map(|&n| self.map.nodes[n]).collect();
+        Ok(points_to_pyarray(py, &nodes))
+    }
+
+    /// Returns the `(x_min, y_min, x_max, y_max)` bounds of the geometry of a non-geometric record.
+    fn get_bounds(&self, layer_name: &str, token: &str) -> PyResult<(f64, f64, f64, f64)> {
+        let record = self.record(parse_layer(layer_name)?, token)?;
+        let Bounds([x0, y0, x1, y1]) =
+            record.bounds.ok_or_else(|| PyValueError::new_err(format!("Record {token} has no geometry")))?;
+        Ok((x0, y0, x1, y1))
+    }
+
+    /// Whether a record intersects or lies within a patch `(x_min, y_min, x_max, y_max)`, depending on `mode`, which
+    /// is `intersect` or `within`.
+    #[pyo3(signature = (layer_name, token, box_coords, mode="intersect"))]
+    fn is_record_in_patch(
+        &self, layer_name: &str, token: &str, box_coords: (f64, f64, f64, f64), mode: &str,
+    ) -> PyResult<bool> {
+        let layer = parse_layer(layer_name)?;
+        let mode = mode.parse().map_err(PyValueError::new_err)?;
+        if !layer.is_polygonal() && !layer.is_linear() {
+            return Err(PyValueError::new_err(format!("{layer} is not a valid layer")));
+        }
+        Ok(self.map.record_in_patch(layer, self.record(layer, token)?, &patch_bounds(box_coords), mode))
+    }
+
+    /// Returns the tokens of the records of each layer that intersect or lie within a patch
+    /// `(x_min, y_min, x_max, y_max)`. Searches all non-geometric layers by default.
+    #[pyo3(signature = (box_coords, layer_names=None, mode="intersect"))]
+    fn get_records_in_patch<'py>(
+        &self, py: Python<'py>, box_coords: (f64, f64, f64, f64), layer_names: Option<Vec<String>>, mode: &str,
+    ) -> PyResult<Bound<'py, PyDict>> {
+        let layers = parse_layers(layer_names, &MapLayer::NON_GEOMETRIC)?;
+        let mode: PatchMode = mode.parse().map_err(PyValueError::new_err)?;
+        let patch = patch_bounds(box_coords);
+        let dict = PyDict::new(py);
+        for layer in layers {
+            dict.set_item(
+                layer.as_str(),
+                self.map.records_in_patch(layer, &patch, mode).map_err(PyValueError::new_err)?,
+            )?;
+        }
+        Ok(dict)
+    }
+
+    /// Returns the tokens of the records of each layer that intersect or lie within the square of half side `radius`
+    /// centered on `(x, y)`.
+    #[pyo3(signature = (x, y, radius, layer_names=None, mode="intersect"))]
+    fn get_records_in_radius<'py>(
+        &self, py: Python<'py>, x: f64, y: f64, radius: f64, layer_names: Option<Vec<String>>, mode: &str,
+    ) -> PyResult<Bound<'py, PyDict>> {
+        self.get_records_in_patch(py, (x - radius, y - radius, x + radius, y + radius), layer_names, mode)
+    }
+
+    /// Returns the token of the first record of each polygon layer containing `(x, y)`, or an empty string.
+    #[pyo3(signature = (x, y, layer_names=None))]
+    fn layers_on_point<'py>(
+        &self, py: Python<'py>, x: f64, y: f64, layer_names: Option<Vec<String>>,
+    ) -> PyResult<Bound<'py, PyDict>> {
+        let dict = PyDict::new(py);
+        for layer in parse_layers(layer_names, &MapLayer::POLYGON_LAYERS)? {
+            dict.set_item(layer.as_str(), self.record_on_point(x, y, layer.as_str())?)?;
+        }
+        Ok(dict)
+    }
+
+    /// Returns the token of the first record of a polygon layer containing `(x, y)`, or an empty string.
+    fn record_on_point(&self, x: f64, y: f64, layer_name: &str) -> PyResult<&str> {
+        let layer = parse_layer(layer_name)?;
+        Ok(self.map.record_on_point(layer, [x, y]).map_err(PyValueError::new_err)?.unwrap_or_default())
+    }
+
+    /// Returns the token of the lane or lane connector within `radius` of `(x, y)` closest to it, or an empty string.
+    #[pyo3(signature = (x, y, radius=5.0))]
+    fn get_closest_lane(&self, x: f64, y: f64, radius: f64) -> PyResult<&str> {
+        Ok(self.map.closest_lane([x, y], radius).map_err(PyValueError::new_err)?.unwrap_or_default())
+    }
+
+    /// Returns the lanes and lane connectors starting at the end of a lane.
+    fn get_outgoing_lane_ids(&self, lane_token: &str) -> PyResult<Vec<&str>> {
+        Ok(self.connectivity(lane_token)?.outgoing.iter().map(String::as_str).collect())
+    }
+
+    /// Returns the lanes and lane connectors ending at the start of a lane.
+    fn get_incoming_lane_ids(&self, lane_token: &str) -> PyResult<Vec<&str>> {
+        Ok(self.connectivity(lane_token)?.incoming.iter().map(String::as_str).collect())
+    }
+
+    /// Returns the arcline paths of a lane as dicts.
+    fn get_arcline_path<'py>(&self, py: Python<'py>, lane_token: &str) -> PyResult<Vec<Bound<'py, PyDict>>> {
+        self.arcline_paths(lane_token)?
+            .iter()
+            .map(|path| {
+                let dict = PyDict::new(py);
+                dict.set_item("start_pose", path.start_pose)?;
+                dict.set_item("end_pose", path.end_pose)?;
+                dict.set_item("shape", &path.shape)?;
+                dict.set_item("radius", path.radius)?;
+                dict.set_item("segment_length", path.segment_length)?;
+                Ok(dict)
+            })
+            .collect()
+    }
+
+    /// Returns the length in meters of the arcline paths of a lane.
+    fn length_of_lane(&self, lane_token: &str) -> PyResult<f64> {
+        Ok(self.arcline_paths(lane_token)?.iter().map(ArclinePath::length).sum())
+    }
+
+    /// Discretizes lanes and lane connectors in parallel, returning the `(N, 3)` poses `(x, y, yaw)` along each at
+    /// most `resolution_meters` apart. Lanes without arcline paths have no poses.
+    fn discretize_lanes<'py>(
+        &self, py: Python<'py>, tokens: Vec<String>, resolution_meters: f64,
+    ) -> PyResult<Bound<'py, PyDict>> {
+        check_resolution(resolution_meters)?;
+        let poses: Vec<Vec<Pose>> = py.detach(|| {
+            tokens
+                .par_iter()
+                .map(|token| {
+                    self.map
+                        .arcline_paths
+                        .get(token)
+                        .map_or(Vec::new(), |paths| discretize_lane(paths, resolution_meters))
+                })
+                .collect()
+        });
+        let dict = PyDict::new(py);
+        for (token, poses) in tokens.iter().zip(poses) {
+            dict.set_item(token, poses_to_pyarray(py, &poses))?;
+        }
+        Ok(dict)
+    }
+
+    /// Discretizes all lanes and lane connectors, in the order of their layers.
+    fn discretize_centerlines<'py>(
+        &self, py: Python<'py>, resolution_meters: f64,
+    ) -> PyResult<Vec<Bound<'py, PyArray2<f64>>>> {
+        check_resolution(resolution_meters)?;
+        let tokens: Vec<&str> = [MapLayer::Lane, MapLayer::LaneConnector]
+            .iter()
+            .flat_map(|layer| self.map.layers[layer].records.iter().map(|record| record.token.as_str()))
+            .collect();
+        let poses: Vec<Vec<Pose>> = py.detach(|| {
+            tokens
+                .par_iter()
+                .map(|token| {
+                    self.map
+                        .arcline_paths
+                        .get(*token)
+                        .map_or(Vec::new(), |paths| discretize_lane(paths, resolution_meters))
+                })
+                .collect()
+        });
+        Ok(poses.iter().map(|poses| poses_to_pyarray(py, poses)).collect())
+    }
+
+    /// Returns the lanes reachable from a lane in at most `hops` steps along the lane graph, following `outgoing` or
+    /// `incoming` connections, mapped to their number of steps. The lane itself has 0 steps.
+    #[pyo3(signature = (lane_token, hops, direction="outgoing"))]
+    fn reachable_lanes<'py>(
+        &self, py: Python<'py>, lane_token: &str, hops: usize, direction: &str,
+    ) -> PyResult<Bound<'py, PyDict>> {
+        let direction = match direction {
+            "outgoing" => LaneDirection::Outgoing,
+            "incoming" => LaneDirection::Incoming,
+            _ => return Err(PyValueError::new_err(format!("direction must be outgoing or incoming, got {direction}"))),
+        };
+        let graph = &self.map.lane_graph;
+        let dict = PyDict::new(py);
+        for (lane, steps) in graph.reachable(self.graph_lane(lane_token)?, hops, direction) {
+            dict.set_item(&graph.tokens[lane], steps)?;
+        }
+        Ok(dict)
+    }
+
+    /// Returns the shortest route of connected lanes from one lane to another, from the start of the first to the end
+    /// of the last, with its length in meters. `None` if the goal cannot be reached.
+    fn shortest_lane_route(&self, start_token: &str, goal_token: &str) -> PyResult<Option<(Vec<&str>, f64)>> {
+        let graph = &self.map.lane_graph;
+        let (start, goal) = (self.graph_lane(start_token)?, self.graph_lane(goal_token)?);
+        Ok(graph
+            .shortest_route(start, goal)
+            .map(|(route, length)| (route.iter().map(|&lane| graph.tokens[lane].as_str()).collect(), length)))
+    }
+
+    /// Rasterizes layers into binary masks for a patch `(x_center, y_center, height, width)` rotated by `patch_angle`
+    /// degrees, as `NuScenesMapExplorer.get_map_mask`. Returns a `(C, H, W)` uint8 array on a canvas of `(H, W)`
+    /// pixels, 10 per meter if `canvas_size` is `None`, with rows along the rotated y axis. The whole map is covered if
+    /// `patch_box` is `None` and all non-geometric layers are rasterized by default, except traffic lights.
+    #[pyo3(signature = (patch_box, patch_angle, layer_names=None, canvas_size=Some((100, 100))))]
+    fn get_map_mask<'py>(
+        &self, py: Python<'py>, patch_box: Option<(f64, f64, f64, f64)>, patch_angle: f64,
+        layer_names: Option<Vec<String>>, canvas_size: Option<(usize, usize)>,
+    ) -> PyResult<Bound<'py, PyArray3<u8>>> {
+        let layers = parse_layers(layer_names, &MapLayer::NON_GEOMETRIC)?;
+        layer_masks_to_pyarray(py, &self.map, &layers, &self.patch_frame(patch_box, patch_angle, canvas_size)?)
+    }
+
+    /// Rasterizes a patch as `get_map_mask`, with the drivable area, lanes, crossings, walkways and stop lines by
+    /// default, and the directions of the lanes. Returns a dict with the `layer_names` of the masks, their `masks` and
+    /// the `lane_direction`, a `(2, H, W)` float32 array with the cosine and sine of the yaw of the lanes relative to
+    /// the patch angle, zero off the lanes.
+    #[pyo3(signature = (patch_box, patch_angle, layer_names=None, canvas_size=Some((100, 100))))]
+    fn rasterize<'py>(
+        &self, py: Python<'py>, patch_box: Option<(f64, f64, f64, f64)>, patch_angle: f64,
+        layer_names: Option<Vec<String>>, canvas_size: Option<(usize, usize)>,
+    ) -> PyResult<Bound<'py, PyDict>> {
+        let layers = parse_layers(layer_names, &RASTER_LAYERS)?;
+        raster_to_dict(py, &self.map, &layers, &self.patch_frame(patch_box, patch_angle, canvas_size)?)
+    }
+
+    fn __repr__(&self) -> String {
+        format!("VectorMap(map_name={:?}, version={:?})", self.map.name, self.map.version)
+    }
+}
+
+impl Tables {
+    /// Returns the vector map of a location, loading it on first use.
+    pub(crate) fn location_vector_map(&self, py: Python<'_>, location: &MapLocation) -> PyResult<Arc<HdMap>> {
+        let cell = self
+            .vector_maps
+            .get(location)
+            .ok_or_else(|| PyValueError::new_err(format!("Unknown map location {:?}", location.as_str())))?;
+        if let Some(map) = cell.get() {
+            return Ok(map.clone());
+        }
+        let map = Arc::new(load_map(py, Path::new(&self.dataroot), location)?);
+        Ok(cell.get_or_init(|| map).clone())
+    }
+
+    /// Rasterizes the vector map of the log of a sample as `VectorMap.rasterize`, in a `(length, width)` patch in
+    /// meters centered on a global position and aligned with a heading, sampled every `resolution` meters.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn heading_map_raster<'py>(
+        &self, py: Python<'py>, sample_token: &[u8; 16], center: [f64; 2], yaw: f64, size: (f64, f64), resolution: f64,
+        layer_names: Option<Vec<String>>,
+    ) -> PyResult<Bound<'py, PyDict>> {
+        if ![resolution, size.0, size.1].into_iter().all(is_positive) {
+            return Err(PyValueError::new_err(format!("Invalid patch size {size:?} or resolution {resolution}")));
+        }
+        check_canvas(((size.0 / resolution).round() as usize, (size.1 / resolution).round() as usize))?;
+        let layers = parse_layers(layer_names, &RASTER_LAYERS)?;
+        let sample = self.sample.get(sample_token).unwrap();
+        let scene = self.scene.get(&sample.scene_token).unwrap();
+        let log = self.log.get(&scene.log_token).unwrap();
+        let map = self.location_vector_map(py, &log.map_location)?;
+        raster_to_dict(py, &map, &layers, &RasterFrame::heading(center, yaw, size, resolution))
+    }
+}
@@ -0,0 +1,247 @@
+use std::f64::consts::PI;
+
+use serde::Deserialize;
+
+/// Pose `(x, y, yaw)` in the map frame.
+pub type Pose = [f64; 3];
+
+/// Path of three arcs or lines joining two poses, as in `arcline_path_utils.py`. `shape` spells the turn of each
+/// segment, `L`eft, `R`ight or `S`traight.
+#[derive(Clone, Debug, Deserialize)]
+pub struct ArclinePath {
+    pub start_pose: Pose,
+    pub end_pose: Pose,
+    pub shape: String,
+    pub radius: f64,
+    pub segment_length: [f64; 3],
+}
+
+/// Wraps an angle to `[-pi, pi)`.
+fn principal_value(angle: f64) -> f64 {
+    (angle + PI).rem_euclid(2.0 * PI) - PI
+}
+
+/// Pose reached after `step` meters along a constant-curvature segment given by its Lie algebra, from the origin.
+fn transformation_at_step(algebra: Pose, step: f64) -> Pose {
+    let theta = algebra[2] * step;
+    if algebra[2].abs() < 1e-6 {
+        return [algebra[0] * step, algebra[1] * step, theta];
+    }
+    let (sin, cos) = theta.sin_cos();
+    [
+        (algebra[1] * (cos - 1.0) + algebra[0] * sin) / algebra[2],
+        (algebra[0] * (1.0 - cos) + algebra[1] * sin) / algebra[2],
+        theta,
+    ]
+}
+
+fn apply_transformation(pose: Pose, transformation: Pose) -> Pose {
+    let (sin, cos) = pose[2].sin_cos();
+    [
+        cos * transformation[0] - sin * transformation[1] + pose[0],
+        sin * transformation[0] + cos * transformation[1] + pose[1],
+        principal_value(pose[2] + transformation[2]),
+    ]
+}
+
+impl ArclinePath {
+    pub fn length(&self) -> f64 {
+        self.segment_length.iter().sum()
+    }
+
+    /// Turn of each segment, 1 to the left, -1 to the right and 0 straight, as `compute_segment_sign`.
+    fn segment_signs(&self) -> [f64; 3] {
+        let shape = self.shape.as_str();
+        let first = if matches!(shape, "LRL" | "LSL" | "LSR") { 1.0 } else { -1.0 };
+        let middle = match shape {
+            "RLR" => 1.0,
+            "LRL" => -1.0,
+            _ => 0.0,
+        };
+        let last = if matches!(shape, "LRL" | "LSL" | "RSL") { 1.0 } else { -1.0 };
+        [first, middle, last]
+    }
+
+    fn lie_algebra(&self) -> [Pose; 3] {
+        self.segment_signs().map(|sign| [1.0, 0.0, sign / self.radius])
+    }
+
+    /// Pose `pos` meters along the path, as `pose_at_length`.
+    pub fn pose_at_length(&self, pos: f64) -> Pose {
+        let mut pos = pos.clamp(0.0, self.length());
+        let mut pose = self.start_pose;
+        for (algebra, length) in self.lie_algebra().into_iter().zip(self.segment_length) {
+            if pos <= length {
+                return apply_transformation(pose, transformation_at_step(algebra, pos));
+            }
+            pose = apply_transformation(pose, transformation_at_step(algebra, length));
+            pos -= length;
+        }
+        pose
+    }
+
+    /// Poses evenly spaced along the path at most `resolution` meters apart, both ends included, as `discretize`.
+    pub fn discretize(&self, resolution: f64) -> Vec<Pose> {
+        let length = self.length();
+        let n_points = ((length / resolution).ceil() + 1.5).max(2.0) as usize;
+        let resolution = length / (n_points - 1) as f64;
+        let cumulative = [self.segment_length[0], self.segment_length[0] + self.segment_length[1], length + resolution];
+        let algebra = self.lie_algebra();
+
+        let mut pose = self.start_pose;
+        let (mut segment, mut start) = (0, 0.0);
+        (0..n_points)
+            .map(|step| {
+                let along = step as f64 * resolution;
+                if along > cumulative[segment] {
+                    pose = self.pose_at_length(along);
+                    start = along;
+                    segment += 1;
+                }
+                apply_transformation(pose, transformation_at_step(algebra[segment], along - start))
+            })
+            .collect()
+    }
+}
+
+/// Poses along the paths of a lane, as `discretize_lane`.
+pub fn discretize_lane(paths: &[ArclinePath], resolution: f64) -> Vec<Pose> {
+    paths.iter().flat_map(|path| path.discretize(resolution)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHAPES: [&str; 6] = ["LSL", "LSR", "RSL", "RSR", "LRL", "RLR"];
+
+    // Poses of `arcline_path_utils.py` for the path of each shape: the number of poses of `discretize(path, 1.3)` with
+    // its poses 1, 4 and last, then `pose_at_length(path, pos)` in each segment and past the end.
+    const DISCRETIZED: [(usize, [Pose; 3]); 6] = [
+        (
+            9,
+            [
+                [10.961592218633106, -4.694916765063938, -0.5515625000000002],
+                [14.213919282572794, -6.1383342710364195, -0.3875000000000002],
+                [18.75717066031081, -7.392137337037897, -0.012500000000000178],
+            ],
+        ),
+        (
+            9,
+            [
+                [12.121052668774373, -4.389040520981939, -0.2680555555555557],
+                [15.64071707359934, -4.917628312504735, -0.12222222222222223],
+                [20.24005050643547, -5.981695800818855, -0.4555555555555557],
+            ],
+        ),
+        (
+            9,
+            [
+                [13.17176164448182, -4.188346914093905, -0.21875],
+                [16.544232511992835, -5.327834591081234, -0.3500000000000001],
+                [21.117321141323476, -6.521681229604148, -0.050000000000000266],
+            ],
+        ),
+        (
+            9,
+            [
+                [14.174291744928103, -3.827297477291035, 0.09204545454545432],
+                [17.73448872505165, -3.8461790413662875, -0.027272727272727337],
+                [22.434597400919152, -4.381108361799422, -0.2999999999999998],
+            ],
+        ),
+        (
+            10,
+            [
+                [15.086653216151445, -3.328912275501269, 0.6064814814814814],
+                [18.20519578578893, -1.1119342834578112, 0.4907407407407405],
+                [24.20608726611494, 1.0238114092256965, 0.45833333333333304],
+            ],
+        ),
+        (
+            10,
+            [
+                [15.933814998681019, -3.128569255361687, 0.7017094017094019],
+                [18.882993189473122, -0.6891746270127119, 0.8085470085470083],
+                [22.610897744027163, 4.479105568700637, 0.8384615384615381],
+            ],
+        ),
+    ];
+    const POSITIONS: [f64; 4] = [1.7, 5.2, 9.4, 50.0];
+    const AT_LENGTH: [[Pose; 4]; 6] = [
+        [
+            [11.406392796963614, -4.949315820253808, -0.4874999999999998],
+            [14.630554770377376, -6.3083780015937005, -0.3875000000000002],
+            [18.65718888884679, -7.390262459105539, -0.025000000000000355],
+            [18.757170660310813, -7.392137337037897, -0.012500000000000178],
+        ],
+        [
+            [12.618846833138562, -4.510639254417314, -0.21111111111111125],
+            [16.08736014450457, -4.972491480589967, -0.12222222222222223],
+            [20.15000625939244, -5.938199471155089, -0.44444444444444464],
+            [20.24005050643547, -5.981695800818854, -0.4555555555555557],
+        ],
+        [
+            [13.66898020042003, -4.312332689121352, -0.27],
+            [16.966950232774156, -5.482138604436188, -0.3500000000000001],
+            [21.017472769235816, -6.5161840250065275, -0.06000000000000005],
+            [21.11732114132348, -6.521681229604148, -0.04999999999999982],
+        ],
+        [
+            [14.685534797268279, -3.792094036203077, 0.045454545454545414],
+            [18.184321380052758, -3.8584502472831237, -0.027272727272727337],
+            [22.33893074145009, -4.351990989052274, -0.290909090909091],
+            [22.434597400919152, -4.381108361799422, -0.2999999999999998],
+        ],
+        [
+            [15.42927078757409, -3.082201080369841, 0.6416666666666666],
+            [18.283748800027414, -1.0703335335387454, 0.48333333333333295],
+            [22.251329928735537, 0.2637584194016105, 0.2833333333333332],
+            [24.206087266114945, 1.0238114092256976, 0.45833333333333304],
+        ],
+        [
+            [16.260651954511854, -2.8612987205027225, 0.6692307692307695],
+            [18.944155140480206, -0.6246733128154369, 0.8153846153846156],
+            [21.338774793849094, 2.8111362211817603, 1.0],
+            [22.610897744027163, 4.479105568700637, 0.8384615384615381],
+        ],
+    ];
+
+    /// Path of each shape, with a straight or curved middle segment.
+    fn path(i: usize) -> ArclinePath {
+        let shape = SHAPES[i].to_string();
+        let middle = if shape.contains('S') { 4.0 } else { 6.0 };
+        ArclinePath {
+            start_pose: [10.0 + i as f64, -4.0, 0.3 * i as f64 - 0.7],
+            end_pose: [0.0; 3],
+            shape,
+            radius: 8.0 + i as f64,
+            segment_length: [2.5, middle, 3.0],
+        }
+    }
+
+    fn assert_close(actual: Pose, expected: Pose) {
+        assert!(actual.iter().zip(expected).all(|(a, e)| (a - e).abs() < 1e-9), "{actual:?} != {expected:?}");
+    }
+
+    #[test]
+    fn discretize_matches_arcline_path_utils() {
+        for (i, (len, expected)) in DISCRETIZED.into_iter().enumerate() {
+            let poses = path(i).discretize(1.3);
+            assert_eq!(poses.len(), len, "{}", SHAPES[i]);
+            assert_close(poses[0], path(i).start_pose);
+            for (k, expected) in [1, 4, len - 1].into_iter().zip(expected) {
+                assert_close(poses[k], expected);
+            }
+        }
+    }
+
+    #[test]
+    fn pose_at_length_matches_arcline_path_utils() {
+        for (i, expected) in AT_LENGTH.into_iter().enumerate() {
+            for (pos, expected) in POSITIONS.into_iter().zip(expected) {
+                assert_close(path(i).pose_at_length(pos), expected);
+            }
+        }
+    }
+}
@@ -0,0 +1,222 @@
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, VecDeque};
+
+use ahash::HashMap;
+
+use super::arcline::ArclinePath;
+use super::layers::Connectivity;
+
+/// Directed graph of lanes and lane connectors, from the `connectivity` of the map expansion. Edges go from a lane
+/// to the lanes starting at its end, and each lane weighs the length of its arcline paths.
+#[derive(Debug, Default)]
+pub struct LaneGraph {
+    pub tokens: Vec<String>,
+    pub index: HashMap<String, usize>,
+    pub successors: Vec<Vec<usize>>,
+    pub predecessors: Vec<Vec<usize>>,
+    pub lengths: Vec<f64>, // 0 for lanes without arcline paths
+}
+
+/// Direction in which to walk the lane graph.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LaneDirection {
+    Outgoing,
+    Incoming,
+}
+
+// Route cost ordered for the priority queue of Dijkstra's algorithm.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Cost(f64);
+
+impl Eq for Cost {}
+
+impl PartialOrd for Cost {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Cost {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+impl LaneGraph {
+    /// Builds the graph from the connectivity of the lanes, in token order so that it is deterministic. Lanes only
+    /// referenced by others are nodes too.
+    pub fn new(
+        connectivity: &HashMap<String, Connectivity>, arcline_paths: &HashMap<String, Vec<ArclinePath>>,
+    ) -> Self {
+        let mut keys: Vec<&String> = connectivity.keys().collect();
+        keys.sort_unstable();
+        let mut graph = Self::default();
+        for &key in &keys {
+            graph.node(key);
+        }
+        for key in keys {
+            let from = graph.index[key.as_str()];
+            for to in &connectivity[key].outgoing {
+                let to = graph.node(to);
+                if !graph.successors[from].contains(&to) {
+                    graph.successors[from].push(to);
+                    graph.predecessors[to].push(from);
+                }
+            }
+            for to in &connectivity[key].incoming {
+                let to = graph.node(to);
+                if !graph.predecessors[from].contains(&to) {
+                    graph.predecessors[from].push(to);
+                    graph.successors[to].push(from);
+                }
+            }
+        }
+        graph.lengths = graph
+            .tokens
+            .iter()
+            .map(|token| arcline_paths.get(token).map_or(0.0, |paths| paths.iter().map(ArclinePath::length).sum()))
+            .collect();
+        graph
+    }
+
+    /// Index of the node of a lane, added if it is new.
+    fn node(&mut self, token: &str) -> usize {
+        if let Some(&i) = self.index.get(token) {
+            return i;
+        }
+        let i = self.tokens.len();
+        self.tokens.push(token.to_string());
+        self.index.insert(token.to_string(), i);
+        self.successors.push(Vec::new());
+        self.predecessors.push(Vec::new());
+        i
+    }
+
+    fn neighbours(&self, lane: usize, direction: LaneDirection) -> &[usize] {
+        match direction {
+            LaneDirection::Outgoing => &self.successors[lane],
+            LaneDirection::Incoming => &self.predecessors[lane],
+        }
+    }
+
+    /// Lanes reachable from a lane in at most `hops` steps, with their number of steps, in breadth-first order. The
+    /// lane itself comes first with 0 steps.
+    pub fn reachable(&self, lane: usize, hops: usize, direction: LaneDirection) -> Vec<(usize, usize)> {
+        let mut visited = vec![false; self.tokens.len()];
+        let mut order = vec![(lane, 0)];
+        visited[lane] = true;
+        let mut queue = VecDeque::from([(lane, 0)]);
+        while let Some((current, depth)) = queue.pop_front() {
+            if depth == hops {
+                continue;
+            }
+            for &next in self.neighbours(current, direction) {
+                if !visited[next] {
+                    visited[next] = true;
+                    order.push((next, depth + 1));
+                    queue.push_back((next, depth + 1));
+                }
+            }
+        }
+        order
+    }
+
+    /// Shortest route from the start of a lane to the end of another along successors, by the total length of its
+    /// lanes, with Dijkstra's algorithm. Returns the lanes of the route and its length, or `None` if unreachable.
+    pub fn shortest_route(&self, start: usize, goal: usize) -> Option<(Vec<usize>, f64)> {
+        let mut cost = vec![f64::INFINITY; self.tokens.len()];
+        let mut previous = vec![usize::MAX; self.tokens.len()];
+        let mut heap = BinaryHeap::new();
+        cost[start] = self.lengths[start];
+        heap.push((Reverse(Cost(cost[start])), start));
+        while let Some((Reverse(Cost(c)), lane)) = heap.pop() {
+            if lane == goal {
+                break;
+            }
+            if c > cost[lane] {
+                continue;
+            }
+            for &next in &self.successors[lane] {
+                let next_cost = c + self.lengths[next];
+                if next_cost < cost[next] {
+                    cost[next] = next_cost;
+                    previous[next] = lane;
+                    heap.push((Reverse(Cost(next_cost)), next));
+                }
+            }
+        }
+        if cost[goal].is_infinite() {
+            return None;
+        }
+        let mut route = vec![goal];
+        while let Some(&lane) = route.last().filter(|&&lane| lane != start) {
+            route.push(previous[lane]);
+        }
+        route.reverse();
+        Some((route, cost[goal]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Lanes `a` to `e` of lengths 1, 10, 3, 3 and 1, with `a -> b -> e` and `a -> c -> d -> e`, `d -> b`, and a lane
+    /// `f` without paths only known as entering `e`.
+    fn graph() -> LaneGraph {
+        let lane = |incoming: &[&str], outgoing: &[&str]| Connectivity {
+            incoming: incoming.iter().map(|s| s.to_string()).collect(),
+            outgoing: outgoing.iter().map(|s| s.to_string()).collect(),
+        };
+        let connectivity = [
+            ("a", lane(&[], &["b", "c"])),
+            ("b", lane(&["a", "d"], &["e"])),
+            ("c", lane(&["a"], &["d"])),
+            ("d", lane(&["c"], &["b", "e"])),
+            ("e", lane(&["b", "f"], &[])),
+        ];
+        let paths = [("a", 1.0), ("b", 10.0), ("c", 3.0), ("d", 3.0), ("e", 1.0)].map(|(token, length)| {
+            let path = ArclinePath {
+                start_pose: [0.0; 3],
+                end_pose: [0.0; 3],
+                shape: "LSL".to_string(),
+                radius: 1.0,
+                segment_length: [0.0, length, 0.0],
+            };
+            (token.to_string(), vec![path])
+        });
+        let connectivity = connectivity.into_iter().map(|(token, lane)| (token.to_string(), lane)).collect();
+        LaneGraph::new(&connectivity, &paths.into_iter().collect())
+    }
+
+    fn tokens<'g>(graph: &'g LaneGraph, lanes: &[(usize, usize)]) -> Vec<(&'g str, usize)> {
+        lanes.iter().map(|&(lane, hops)| (graph.tokens[lane].as_str(), hops)).collect()
+    }
+
+    #[test]
+    fn reachable_walks_breadth_first() {
+        let graph = graph();
+        let a = graph.index["a"];
+        assert_eq!(tokens(&graph, &graph.reachable(a, 0, LaneDirection::Outgoing)), [("a", 0)]);
+        assert_eq!(tokens(&graph, &graph.reachable(a, 1, LaneDirection::Outgoing)), [("a", 0), ("b", 1), ("c", 1)]);
+        assert_eq!(
+            tokens(&graph, &graph.reachable(a, 5, LaneDirection::Outgoing)),
+            [("a", 0), ("b", 1), ("c", 1), ("e", 2), ("d", 2)]
+        );
+        let e = graph.index["e"];
+        assert_eq!(
+            tokens(&graph, &graph.reachable(e, 1, LaneDirection::Incoming)),
+            [("e", 0), ("b", 1), ("d", 1), ("f", 1)]
+        );
+    }
+
+    #[test]
+    fn shortest_route_minimizes_the_length_of_lanes() {
+        let graph = graph();
+        let [a, c, d, e, f] = ["a", "c", "d", "e", "f"].map(|token| graph.index[token]);
+        assert_eq!(graph.shortest_route(a, e), Some((vec![a, c, d, e], 8.0)));
+        assert_eq!(graph.shortest_route(a, a), Some((vec![a], 1.0)));
+        assert_eq!(graph.shortest_route(f, e), Some((vec![f, e], 1.0)));
+        assert_eq!(graph.shortest_route(e, a), None);
+    }
+}
@@ -0,0 +1,471 @@
+use std::fmt::Display;
+use std::path::Path;
+use std::str::FromStr;
+
+use ahash::HashMap;
+use serde::Deserialize;
+use serde_json::{Map as JsonMap, Value};
+
+use super::arcline::ArclinePath;
+use super::lanegraph::LaneGraph;
+use super::rtree::RTree;
+use super::shapes::{Bounds, Polygon};
+
+/// Layers of the map expansion, in the order of `NuScenesMap.layer_names`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MapLayer {
+    Polygon,
+    Line,
+    Node,
+    DrivableArea,
+    RoadSegment,
+    RoadBlock,
+    Lane,
+    PedCrossing,
+    Walkway,
+    StopLine,
+    CarparkArea,
+    LaneConnector,
+    RoadDivider,
+    LaneDivider,
+    TrafficLight,
+}
+
+impl MapLayer {
+    pub const ALL: [Self; 15] = [
+        Self::Polygon,
+        Self::Line,
+        Self::Node,
+        Self::DrivableArea,
+        Self::RoadSegment,
+        Self::RoadBlock,
+        Self::Lane,
+        Self::PedCrossing,
+        Self::Walkway,
+        Self::StopLine,
+        Self::CarparkArea,
+        Self::LaneConnector,
+        Self::RoadDivider,
+        Self::LaneDivider,
+        Self::TrafficLight,
+    ];
+
+    /// Layers whose records are described by polygons, as `non_geometric_polygon_layers`.
+    pub const POLYGON_LAYERS: [Self; 8] = [
+        Self::DrivableArea,
+        Self::RoadSegment,
+        Self::RoadBlock,
+        Self::Lane,
+        Self::PedCrossing,
+        Self::Walkway,
+        Self::StopLine,
+        Self::CarparkArea,
+    ];
+
+    /// Layers whose records are described by lines, as `non_geometric_line_layers`.
+    pub const LINE_LAYERS: [Self; 3] = [Self::RoadDivider, Self::LaneDivider, Self::TrafficLight];
+
+    /// Layers searched by default, as `non_geometric_layers`.
+    pub const NON_GEOMETRIC: [Self; 11] = [
+        Self::DrivableArea,
+        Self::RoadSegment,
+        Self::RoadBlock,
+        Self::Lane,
+        Self::PedCrossing,
+        Self::Walkway,
+        Self::StopLine,
+        Self::CarparkArea,
+        Self::RoadDivider,
+        Self::LaneDivider,
+        Self::TrafficLight,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Polygon => "polygon",
+            Self::Line => "line",
+            Self::Node => "node",
+            Self::DrivableArea => "drivable_area",
+            Self::RoadSegment => "road_segment",
+            Self::RoadBlock => "road_block",
+            Self::Lane => "lane",
+            Self::PedCrossing => "ped_crossing",
+            Self::Walkway => "walkway",
+            Self::StopLine => "stop_line",
+            Self::CarparkArea => "carpark_area",
+            Self::LaneConnector => "lane_connector",
+            Self::RoadDivider => "road_divider",
+            Self::LaneDivider => "lane_divider",
+            Self::TrafficLight => "traffic_light",
+        }
+    }
+
+    /// Whether records of the layer are polygons that can be looked up, which adds lane connectors to the polygon layers.
+    pub fn is_polygonal(&self) -> bool {
+        Self::POLYGON_LAYERS.contains(self) || *self == Self::LaneConnector
+    }
+
+    pub fn is_linear(&self) -> bool {
+        Self::LINE_LAYERS.contains(self)
+    }
+}
+
+impl Display for MapLayer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for MapLayer {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL.into_iter().find(|layer| layer.as_str() == s).ok_or_else(|| format!("Layer {s} not found"))
+    }
+}
+
+/// Whether a record intersects or lies within a patch.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PatchMode {
+    Intersect,
+    Within,
+}
+
+impl FromStr for PatchMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "intersect" => Ok(Self::Intersect),
+            "within" => Ok(Self::Within),
+            _ => Err(format!("Mode {s} is not valid, choice=('intersect', 'within')")),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct NodeModel {
+    token: String,
+    x: f64,
+    y: f64,
+}
+
+#[derive(Debug, Deserialize)]
+struct HoleModel {
+    node_tokens: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PolygonModel {
+    token: String,
+    exterior_node_tokens: Vec<String>,
+    holes: Vec<HoleModel>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LineModel {
+    token: String,
+    node_tokens: Vec<String>,
+}
+
+/// Record of a non-geometric layer. Its geometry is referenced by `polygon_token`, `polygon_tokens` for drivable areas
+/// or `line_token`, and kept among the other fields.
+#[derive(Debug, Deserialize)]
+struct RecordModel {
+    token: String,
+    #[serde(flatten)]
+    fields: JsonMap<String, Value>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MapModel {
+    version: Option<String>,
+    canvas_edge: [f64; 2],
+    node: Vec<NodeModel>,
+    line: Vec<LineModel>,
+    polygon: Vec<PolygonModel>,
+    #[serde(default)]
+    drivable_area: Vec<RecordModel>,
+    #[serde(default)]
+    road_segment: Vec<RecordModel>,
+    #[serde(default)]
+    road_block: Vec<RecordModel>,
+    #[serde(default)]
+    lane: Vec<RecordModel>,
+    #[serde(default)]
+    ped_crossing: Vec<RecordModel>,
+    #[serde(default)]
+    walkway: Vec<RecordModel>,
+    #[serde(default)]
+    stop_line: Vec<RecordModel>,
+    #[serde(default)]
+    carpark_area: Vec<RecordModel>,
+    #[serde(default)]
+    lane_connector: Vec<RecordModel>,
+    #[serde(default)]
+    road_divider: Vec<RecordModel>,
+    #[serde(default)]
+    lane_divider: Vec<RecordModel>,
+    #[serde(default)]
+    traffic_light: Vec<RecordModel>,
+    #[serde(default)]
+    arcline_path_3: HashMap<String, Vec<ArclinePath>>,
+    #[serde(default)]
+    connectivity: HashMap<String, Connectivity>,
+}
+
+/// Lanes and lane connectors entering and leaving a lane, by token.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct Connectivity {
+    pub incoming: Vec<String>,
+    pub outgoing: Vec<String>,
+}
+
+/// Geometry of a non-geometric record, by index in the polygon or line layer.
+#[derive(Clone, Debug)]
+pub enum Shape {
+    Polygons(Vec<usize>),
+    Line(usize),
+}
+
+/// Record of a non-geometric layer, with its geometry resolved.
+#[derive(Debug)]
+pub struct MapRecord {
+    pub token: String,
+    pub shape: Shape,
+    pub bounds: Option<Bounds>,
+    pub fields: JsonMap<String, Value>,
+}
+
+/// Records of a layer with their token index and an R-tree over their bounds.
+#[derive(Debug, Default)]
+pub struct RecordLayer {
+    pub records: Vec<MapRecord>,
+    pub index: HashMap<String, usize>,
+    pub rtree: RTree,
+}
+
+impl RecordLayer {
+    pub fn get(&self, token: &str) -> Option<&MapRecord> {
+        self.index.get(token).map(|&i| &self.records[i])
+    }
+}
+
+/// Line of the line layer, with the indices of its nodes.
+#[derive(Debug)]
+pub struct MapLine {
+    pub token: String,
+    pub nodes: Vec<usize>,
+}
+
+/// Polygon of the polygon layer, with the indices of its exterior and hole nodes.
+#[derive(Debug)]
+pub struct MapPolygon {
+    pub token: String,
+    pub exterior: Vec<usize>,
+    pub holes: Vec<Vec<usize>>,
+    pub shape: Polygon,
+}
+
+/// Vector map of a location from the map expansion, as `NuScenesMap` in `map_api.py`.
+#[derive(Debug)]
+pub struct HdMap {
+    pub name: String,
+    pub version: String,
+    pub canvas_edge: [f64; 2],
+    pub node_tokens: Vec<String>,
+    pub nodes: Vec<[f64; 2]>,
+    pub node_index: HashMap<String, usize>,
+    pub lines: Vec<MapLine>,
+    pub line_index: HashMap<String, usize>,
+    pub polygons: Vec<MapPolygon>,
+    pub polygon_index: HashMap<String, usize>,
+    pub layers: HashMap<MapLayer, RecordLayer>,
+    pub arcline_paths: HashMap<String, Vec<ArclinePath>>,
+    pub connectivity: HashMap<String, Connectivity>,
+    pub lane_graph: LaneGraph,
+}
+
+fn token_index<T>(items: &[T], token: impl Fn(&T) -> &str) -> HashMap<String, usize> {
+    items.iter().enumerate().map(|(i, item)| (token(item).to_string(), i)).collect()
+}
+
+fn resolve(index: &HashMap<String, usize>, token: &str, layer: &str) -> Result<usize, String> {
+    index.get(token).copied().ok_or_else(|| format!("Unknown {layer} token {token}"))
+}
+
+impl HdMap {
+    /// Loads the map expansion of a location from `maps/expansion/<name>.json` under `dataroot`.
+    pub fn load(dataroot: &Path, name: &str) -> Result<Self, String> {
+        let path = dataroot.join("maps").join("expansion").join(format!("{name}.json"));
+        let bytes = std::fs::read(&path).map_err(|e| format!("Failed to read {}: {e}", path.display()))?;
+        let model: MapModel =
+            serde_json::from_slice(&bytes).map_err(|e| format!("Failed to parse {}: {e}", path.display()))?;
+        Self::from_model(name, model)
+    }
+
+    fn from_model(name: &str, model: MapModel) -> Result<Self, String> {
+        let version = model.version.unwrap_or_else(|| "1.0".to_string());
+        if version.as_str() < "1.3" {
+            return Err(format!(
+                "You are using an outdated map version ({version})! Please go to https://www.nuscenes.org/download \
+                 to download the latest map!"
+            ));
+        }
+
+        let node_index = token_index(&model.node, |n| &n.token);
+        let nodes: Vec<[f64; 2]> = model.node.iter().map(|n| [n.x, n.y]).collect();
+        let resolve_nodes =
+            |tokens: &[String]| tokens.iter().map(|t| resolve(&node_index, t, "node")).collect::<Result<Vec<_>, _>>();
+
+        let lines = model
+            .line
+            .into_iter()
+            .map(|line| Ok(MapLine { nodes: resolve_nodes(&line.node_tokens)?, token: line.token }))
+            .collect::<Result<Vec<_>, String>>()?;
+        let polygons = model
+            .polygon
+            .into_iter()
+            .map(|polygon| {
+                let exterior = resolve_nodes(&polygon.exterior_node_tokens)?;
+                let holes =
+                    polygon.holes.iter().map(|hole| resolve_nodes(&hole.node_tokens)).collect::<Result<Vec<_>, _>>()?;
+                let coords = |ring: &[usize]| ring.iter().map(|&i| nodes[i]).collect::<Vec<_>>();
+                // Empty holes are dropped, as in `extract_polygon`.
+                let shape = Polygon {
+                    exterior: coords(&exterior),
+                    holes: holes.iter().filter(|hole| !hole.is_empty()).map(|hole| coords(hole)).collect(),
+                };
+                Ok(MapPolygon { token: polygon.token, exterior, holes, shape })
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        let line_index = token_index(&lines, |l| &l.token);
+        let polygon_index = token_index(&polygons, |p| &p.token);
+
+        let field = |record: &RecordModel, name: &str| -> Result<String, String> {
+            match record.fields.get(name) {
+                Some(Value::String(token)) => Ok(token.clone()),
+                _ => Err(format!("Record {} has no {name}", record.token)),
+            }
+        };
+        let layer = |layer: MapLayer, records: Vec<RecordModel>| -> Result<(MapLayer, RecordLayer), String> {
+            let records = records
+                .into_iter()
+                .map(|record| {
+                    let shape = if layer.is_linear() {
+                        Shape::Line(resolve(&line_index, &field(&record, "line_token")?, "line")?)
+                    } else if layer == MapLayer::DrivableArea {
+                        let tokens = match record.fields.get("polygon_tokens") {
+                            Some(Value::Array(tokens)) => tokens.iter().filter_map(Value::as_str).collect(),
+                            _ => Vec::new(),
+                        };
+                        Shape::Polygons(
+                            tokens.iter().map(|t| resolve(&polygon_index, t, "polygon")).collect::<Result<_, _>>()?,
+                        )
+                    } else {
+                        Shape::Polygons(vec![resolve(&polygon_index, &field(&record, "polygon_token")?, "polygon")?])
+                    };
+                    let bounds = match &shape {
+                        Shape::Line(i) => Bounds::of(lines[*i].nodes.iter().map(|&n| nodes[n])),
+                        Shape::Polygons(polys) => {
+                            Bounds::of(polys.iter().flat_map(|&p| polygons[p].shape.exterior.iter().copied()))
+                        }
+                    };
+                    Ok(MapRecord { token: record.token, shape, bounds, fields: record.fields })
+                })
+                .collect::<Result<Vec<_>, String>>()?;
+            let index = token_index(&records, |r| &r.token);
+            let rtree = RTree::new(
+                records.iter().enumerate().filter_map(|(i, r)| r.bounds.map(|b| (b, i))).collect::<Vec<_>>(),
+            );
+            Ok((layer, RecordLayer { records, index, rtree }))
+        };
+
+        let layers = [
+            (MapLayer::DrivableArea, model.drivable_area),
+            (MapLayer::RoadSegment, model.road_segment),
+            (MapLayer::RoadBlock, model.road_block),
+            (MapLayer::Lane, model.lane),
+            (MapLayer::PedCrossing, model.ped_crossing),
+            (MapLayer::Walkway, model.walkway),
+            (MapLayer::StopLine, model.stop_line),
+            (MapLayer::CarparkArea, model.carpark_area),
+            (MapLayer::LaneConnector, model.lane_connector),
+            (MapLayer::RoadDivider, model.road_divider),
+            (MapLayer::LaneDivider, model.lane_divider),
+            (MapLayer::TrafficLight, model.traffic_light),
+        ]
+        .into_iter()
+        .map(|(name, records)| layer(name, records))
+        .collect::<Result<_, _>>()?;
+
+        Ok(Self {
+            name: name.to_string(),
+            version,
+            canvas_edge: model.canvas_edge,
+            node_tokens: model.node.into_iter().map(|n| n.token).collect(),
+            nodes,
+            node_index,
+            lines,
+            line_index,
+            polygons,
+            polygon_index,
+            layers,
+            lane_graph: LaneGraph::new(&model.connectivity, &model.arcline_path_3),
+            arcline_paths: model.arcline_path_3,
+            connectivity: model.connectivity,
+        })
+    }
+
+    /// Returns the records of a non-geometric layer.
+    pub fn layer(&self, layer: MapLayer) -> Result<&RecordLayer, String> {
+        self.layers.get(&layer).ok_or_else(|| format!("{layer} is not a non-geometric layer"))
+    }
+
+    /// Returns the index of a record in its layer, as `NuScenesMap.getind`.
+    pub fn record_index(&self, layer: MapLayer, token: &str) -> Option<usize> {
+        match layer {
+            MapLayer::Node => self.node_index.get(token).copied(),
+            MapLayer::Line => self.line_index.get(token).copied(),
+            MapLayer::Polygon => self.polygon_index.get(token).copied(),
+            _ => self.layers.get(&layer)?.index.get(token).copied(),
+        }
+    }
+
+    /// Number of records of a layer.
+    pub fn layer_len(&self, layer: MapLayer) -> usize {
+        match layer {
+            MapLayer::Node => self.nodes.len(),
+            MapLayer::Line => self.lines.len(),
+            MapLayer::Polygon => self.polygons.len(),
+            _ => self.layers.get(&layer).map_or(0, |l| l.records.len()),
+        }
+    }
+
+    /// Token of the record at an index of a layer.
+    pub fn record_token(&self, layer: MapLayer, index: usize) -> &str {
+        match layer {
+            MapLayer::Node => &self.node_tokens[index],
+            MapLayer::Line => &self.lines[index].token,
+            MapLayer::Polygon => &self.polygons[index].token,
+            _ => &self.layers[&layer].records[index].token,
+        }
+    }
+
+    /// Polygons of a polygonal record.
+    pub fn record_polygons<'a>(&'a self, record: &'a MapRecord) -> impl Iterator<Item = &'a Polygon> + 'a {
+        let polys: &[usize] = match &record.shape {
+            Shape::Polygons(polys) => polys,
+            Shape::Line(_) => &[],
+        };
+        polys.iter().map(|&p| &self.polygons[p].shape)
+    }
+
+    /// Node coordinates of a linear record.
+    pub fn record_line(&self, record: &MapRecord) -> Vec<[f64; 2]> {
+        match record.shape {
+            Shape::Line(line) => self.lines[line].nodes.iter().map(|&n| self.nodes[n]).collect(),
+            Shape::Polygons(_) => Vec::new(),
+        }
+    }
+}
@@ -0,0 +1,86 @@
+use super::arcline::discretize_lane;
+use super::layers::{HdMap, MapLayer, MapRecord, PatchMode, RecordLayer};
+use super::shapes::Bounds;
+
+// Resolution in meters of the lanes discretized by `closest_lane`, as in `get_closest_lane`.
+const CLOSEST_LANE_RESOLUTION: f64 = 0.5;
+
+impl HdMap {
+    /// Whether a record intersects or lies within a patch, as `is_record_in_patch`. Lines only count nodes strictly
+    /// inside the patch.
+    pub fn record_in_patch(&self, layer: MapLayer, record: &MapRecord, patch: &Bounds, mode: PatchMode) -> bool {
+        if layer.is_linear() {
+            let nodes = self.record_line(record);
+            let [x0, y0, x1, y1] = patch.0;
+            let inside = |p: &[f64; 2]| p[0] > x0 && p[0] < x1 && p[1] > y0 && p[1] < y1;
+            return match mode {
+                PatchMode::Intersect => nodes.iter().any(inside),
+                PatchMode::Within => !nodes.is_empty() && nodes.iter().all(inside),
+            };
+        }
+        let mut polygons = self.record_polygons(record).peekable();
+        match mode {
+            PatchMode::Intersect => polygons.any(|p| p.intersects_rect(patch)),
+            PatchMode::Within => polygons.peek().is_some() && polygons.all(|p| p.within_rect(patch)),
+        }
+    }
+
+    /// Checks that a layer can be searched by geometry and returns its records.
+    fn lookup_layer(&self, layer: MapLayer) -> Result<&RecordLayer, String> {
+        if !layer.is_polygonal() && !layer.is_linear() {
+            return Err(format!("{layer} is not a valid layer"));
+        }
+        self.layer(layer)
+    }
+
+    /// Tokens of the records of a layer intersecting or within a patch, in the order of the layer.
+    pub fn records_in_patch(&self, layer: MapLayer, patch: &Bounds, mode: PatchMode) -> Result<Vec<&str>, String> {
+        let records = self.lookup_layer(layer)?;
+        Ok(records
+            .rtree
+            .query(patch)
+            .into_iter()
+            .map(|i| &records.records[i])
+            .filter(|record| self.record_in_patch(layer, record, patch, mode))
+            .map(|record| record.token.as_str())
+            .collect())
+    }
+
+    /// Token of the first record of a polygon layer containing a point, as `record_on_point`.
+    pub fn record_on_point(&self, layer: MapLayer, point: [f64; 2]) -> Result<Option<&str>, String> {
+        if !MapLayer::POLYGON_LAYERS.contains(&layer) {
+            return Err(format!("{layer} is not a polygon layer"));
+        }
+        let records = self.layer(layer)?;
+        Ok(records
+            .rtree
+            .query(&Bounds([point[0], point[1], point[0], point[1]]))
+            .into_iter()
+            .map(|i| &records.records[i])
+            .find(|record| self.record_polygons(record).any(|p| p.contains(point)))
+            .map(|record| record.token.as_str()))
+    }
+
+    /// Token of the lane or lane connector closest to a point among those intersecting the square of half side
+    /// `radius` around it, by the distance to their poses discretized every half meter, as `get_closest_lane`.
+    pub fn closest_lane(&self, point: [f64; 2], radius: f64) -> Result<Option<&str>, String> {
+        let patch = Bounds([point[0] - radius, point[1] - radius, point[0] + radius, point[1] + radius]);
+        let mut closest = None;
+        let mut min_distance = f64::INFINITY;
+        for layer in [MapLayer::Lane, MapLayer::LaneConnector] {
+            for token in self.records_in_patch(layer, &patch, PatchMode::Intersect)? {
+                let Some(paths) = self.arcline_paths.get(token) else { continue };
+                let distance = discretize_lane(paths, CLOSEST_LANE_RESOLUTION)
+                    .iter()
+                    .map(|pose| (pose[0] - point[0]).hypot(pose[1] - point[1]))
+                    .fold(f64::INFINITY, f64::min);
+                // Ties go to the later lane, as in `get_closest_lane`.
+                if distance <= min_distance && distance.is_finite() {
+                    min_distance = distance;
+                    closest = Some(token);
+                }
+            }
+        }
+        Ok(closest)
+    }
+}
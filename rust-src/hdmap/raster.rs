@@ -0,0 +1,188 @@
+use rayon::prelude::*;
+
+use super::arcline::discretize_lane;
+use super::layers::{HdMap, MapLayer, PatchMode, Shape};
+use super::shapes::Bounds;
+
+// Width in pixels of the lines of line layers, as the thickness of `cv2.polylines` in `get_map_mask`.
+const LINE_THICKNESS: f64 = 2.0;
+// Width in pixels of the lane direction strokes and spacing in meters of their poses, as in `StaticLayerRasterizer`.
+const LANE_THICKNESS: f64 = 5.0;
+const LANE_RESOLUTION: f64 = 1.0;
+
+/// Affine map from the map frame to the continuous `(column, row)` coordinates of a raster, whose pixel `(i, j)`
+/// covers the point `(j, i)`. `heading` is the yaw of the forward axis of the raster in the map frame.
+#[derive(Clone, Copy, Debug)]
+pub struct RasterFrame {
+    pub rows: usize,
+    pub cols: usize,
+    pub heading: f64,
+    matrix: [[f64; 3]; 2],
+}
+
+impl RasterFrame {
+    /// Builds the frame from the coefficients of `(column, row)` in the `(forward, left)` coordinates of a point
+    /// relative to `center`, along the heading `yaw`.
+    fn new(center: [f64; 2], yaw: f64, [col, row]: [[f64; 3]; 2], (rows, cols): (usize, usize)) -> Self {
+        let (sin, cos) = yaw.sin_cos();
+        // forward = cos * dx + sin * dy and left = cos * dy - sin * dx, with d the offset from the center.
+        let axis = |[f, l, offset]: [f64; 3]| {
+            let (a, b) = (f * cos - l * sin, f * sin + l * cos);
+            [a, b, offset - a * center[0] - b * center[1]]
+        };
+        Self { rows, cols, heading: yaw, matrix: [axis(col), axis(row)] }
+    }
+
+    /// Frame of `get_map_mask` for a patch `(height, width)` in meters centered on a global position and rotated by
+    /// `angle` radians, on a canvas of `(rows, cols)` pixels. Columns follow the rotated x axis and rows the rotated y
+    /// axis, both increasing.
+    pub fn patch(center: [f64; 2], angle: f64, (height, width): (f64, f64), canvas: (usize, usize)) -> Self {
+        let (sx, sy) = (canvas.1 as f64 / width, canvas.0 as f64 / height);
+        Self::new(center, angle, [[sx, 0.0, sx * width / 2.0], [0.0, sy, sy * height / 2.0]], canvas)
+    }
+
+    /// Frame of a `(length, width)` patch in meters centered on a global position and aligned with a heading, sampled
+    /// every `resolution` meters as `MapMask.patch`. The heading points to the first row and its left to the first
+    /// column.
+    pub fn heading(center: [f64; 2], yaw: f64, (length, width): (f64, f64), resolution: f64) -> Self {
+        let canvas = ((length / resolution).round() as usize, (width / resolution).round() as usize);
+        let (col, row) = (width / resolution / 2.0 - 0.5, length / resolution / 2.0 - 0.5);
+        Self::new(center, yaw, [[0.0, -1.0 / resolution, col], [-1.0 / resolution, 0.0, row]], canvas)
+    }
+
+    fn pixel(&self, [x, y]: [f64; 2]) -> [f64; 2] {
+        let [c, r] = self.matrix;
+        [c[0] * x + c[1] * y + c[2], r[0] * x + r[1] * y + r[2]]
+    }
+
+    fn map_point(&self, [col, row]: [f64; 2]) -> [f64; 2] {
+        let [c, r] = self.matrix;
+        let det = c[0] * r[1] - c[1] * r[0];
+        let (u, v) = (col - c[2], row - r[2]);
+        [(r[1] * u - c[1] * v) / det, (c[0] * v - r[0] * u) / det]
+    }
+
+    /// Bounds in the map frame of the canvas grown by `margin` pixels.
+    fn bounds(&self, margin: f64) -> Bounds {
+        let (c0, r0) = (-margin, -margin);
+        let (c1, r1) = (self.cols as f64 + margin, self.rows as f64 + margin);
+        Bounds::of([[c0, r0], [c1, r0], [c1, r1], [c0, r1]].map(|p| self.map_point(p))).unwrap()
+    }
+
+    /// Calls `paint` with the index of each pixel within `thickness / 2` pixels of a segment.
+    fn stroke(&self, a: [f64; 2], b: [f64; 2], thickness: f64, mut paint: impl FnMut(usize)) {
+        let half = thickness / 2.0;
+        let clamp = |v: f64, len: usize| v.clamp(0.0, len as f64) as usize;
+        let (c0, c1) =
+            (clamp((a[0].min(b[0]) - half).ceil(), self.cols), clamp(a[0].max(b[0]) + half + 1.0, self.cols));
+        let (r0, r1) =
+            (clamp((a[1].min(b[1]) - half).ceil(), self.rows), clamp(a[1].max(b[1]) + half + 1.0, self.rows));
+        let (dx, dy) = (b[0] - a[0], b[1] - a[1]);
+        let squared = dx * dx + dy * dy;
+        for row in r0..r1 {
+            for col in c0..c1 {
+                let (px, py) = (col as f64 - a[0], row as f64 - a[1]);
+                let t = if squared > 0.0 { ((px * dx + py * dy) / squared).clamp(0.0, 1.0) } else { 0.0 };
+                if (px - t * dx).hypot(py - t * dy) <= half {
+                    paint(row * self.cols + col);
+                }
+            }
+        }
+    }
+
+    /// Sets the pixels inside a ring or on its outline to `value`, as `cv2.fillPoly`.
+    fn fill(&self, ring: &[[f64; 2]], value: u8, mask: &mut [u8]) {
+        let Some(Bounds([_, y0, _, y1])) = Bounds::of(ring.iter().copied()) else { return };
+        let (r0, r1) = (y0.ceil().max(0.0) as usize, (y1.floor() + 1.0).clamp(0.0, self.rows as f64) as usize);
+        let mut crossings = Vec::new();
+        for row in r0..r1 {
+            let y = row as f64;
+            crossings.clear();
+            for (i, &[xa, ya]) in ring.iter().enumerate() {
+                let [xb, yb] = ring[(i + 1) % ring.len()];
+                if (ya <= y) != (yb <= y) {
+                    crossings.push(xa + (y - ya) / (yb - ya) * (xb - xa));
+                }
+            }
+            crossings.sort_unstable_by(f64::total_cmp);
+            for span in crossings.chunks_exact(2) {
+                let c0 = span[0].ceil().max(0.0) as usize;
+                let c1 = (span[1].floor() + 1.0).clamp(0.0, self.cols as f64) as usize;
+                mask[row * self.cols..][c0.min(c1)..c1].fill(value);
+            }
+        }
+        for (i, &a) in ring.iter().enumerate() {
+            self.stroke(a, ring[(i + 1) % ring.len()], 1.0, |j| mask[j] = value);
+        }
+    }
+}
+
+impl HdMap {
+    /// Binary mask of a polygon or line layer in a raster frame, as a channel of `get_map_mask`. Polygons other than
+    /// drivable areas are skipped if invalid. Traffic lights have no mask and give `None`.
+    pub fn layer_mask(&self, layer: MapLayer, frame: &RasterFrame) -> Result<Option<Vec<u8>>, String> {
+        if !layer.is_polygonal() && !layer.is_linear() {
+            return Err(format!("{layer} is not a valid layer"));
+        }
+        if layer == MapLayer::TrafficLight {
+            return Ok(None);
+        }
+        let records = self.layer(layer)?;
+        let mut mask = vec![0; frame.rows * frame.cols];
+        for i in records.rtree.query(&frame.bounds(LINE_THICKNESS)) {
+            let record = &records.records[i];
+            match record.shape {
+                Shape::Line(_) => {
+                    let nodes: Vec<_> = self.record_line(record).into_iter().map(|p| frame.pixel(p)).collect();
+                    for pair in nodes.windows(2) {
+                        frame.stroke(pair[0], pair[1], LINE_THICKNESS, |j| mask[j] = 1);
+                    }
+                }
+                Shape::Polygons(_) => {
+                    for polygon in self.record_polygons(record) {
+                        if !polygon.is_valid() && layer != MapLayer::DrivableArea {
+                            continue;
+                        }
+                        // Holes are cleared after their exterior is filled, as in `mask_for_polygons`.
+                        for (k, ring) in polygon.rings().enumerate() {
+                            let ring: Vec<_> = ring.iter().map(|&p| frame.pixel(p)).collect();
+                            frame.fill(&ring, u8::from(k == 0), &mut mask);
+                        }
+                    }
+                }
+            }
+        }
+        Ok(Some(mask))
+    }
+
+    /// Masks of layers in a raster frame, computed in parallel. Traffic lights are left out, as in `get_map_mask`.
+    pub fn layer_masks(&self, layers: &[MapLayer], frame: &RasterFrame) -> Result<Vec<(MapLayer, Vec<u8>)>, String> {
+        let masks: Vec<_> = layers.par_iter().map(|&layer| self.layer_mask(layer, frame)).collect::<Result<_, _>>()?;
+        Ok(layers.iter().zip(masks).filter_map(|(&layer, mask)| Some((layer, mask?))).collect())
+    }
+
+    /// Cosine and sine channels of the yaw of the lanes and lane connectors relative to the heading of a raster frame,
+    /// as a `(2, rows, cols)` buffer which is zero off the lanes. The lanes are drawn between their poses every meter,
+    /// with the yaw of the first pose of each segment, as the lanes of `StaticLayerRasterizer`.
+    pub fn lane_directions(&self, frame: &RasterFrame) -> Vec<f32> {
+        let size = frame.rows * frame.cols;
+        let mut directions = vec![0.0; 2 * size];
+        let bounds = frame.bounds(LANE_THICKNESS);
+        for layer in [MapLayer::Lane, MapLayer::LaneConnector] {
+            let tokens = self.records_in_patch(layer, &bounds, PatchMode::Intersect).unwrap_or_default();
+            for token in tokens {
+                let Some(paths) = self.arcline_paths.get(token) else { continue };
+                let poses = discretize_lane(paths, LANE_RESOLUTION);
+                for pair in poses.windows(2) {
+                    let (sin, cos) = (pair[0][2] - frame.heading).sin_cos();
+                    let [a, b] = [pair[0], pair[1]].map(|pose| frame.pixel([pose[0], pose[1]]));
+                    frame.stroke(a, b, LANE_THICKNESS, |j| {
+                        directions[j] = cos as f32;
+                        directions[size + j] = sin as f32;
+                    });
+                }
+            }
+        }
+        directions
+    }
+}
@@ -0,0 +1,68 @@
+use super::shapes::Bounds;
+
+// Number of children of each node.
+const NODE_CAPACITY: usize = 16;
+
+/// Static R-tree over item bounds, bulk-loaded with sort-tile-recursive packing.
+///
+/// Levels are stored bottom-up: the entries of level 0 are the items, and each entry of a level above bounds a run
+/// of up to `NODE_CAPACITY` consecutive entries of the level below.
+#[derive(Clone, Debug, Default)]
+pub struct RTree {
+    items: Vec<usize>,
+    levels: Vec<Vec<Bounds>>,
+}
+
+impl RTree {
+    pub fn new(mut entries: Vec<(Bounds, usize)>) -> Self {
+        if entries.is_empty() {
+            return Self::default();
+        }
+        // Sort by x into vertical slices, then each slice by y.
+        let leaves = entries.len().div_ceil(NODE_CAPACITY);
+        let slices = (leaves as f64).sqrt().ceil() as usize;
+        let slice_len = leaves.div_ceil(slices) * NODE_CAPACITY;
+        entries.sort_by(|a, b| center(&a.0)[0].total_cmp(&center(&b.0)[0]));
+        for slice in entries.chunks_mut(slice_len) {
+            slice.sort_by(|a, b| center(&a.0)[1].total_cmp(&center(&b.0)[1]));
+        }
+
+        let items = entries.iter().map(|(_, item)| *item).collect();
+        let mut levels = vec![entries.into_iter().map(|(bounds, _)| bounds).collect::<Vec<_>>()];
+        while levels.last().unwrap().len() > 1 {
+            let parents = levels
+                .last()
+                .unwrap()
+                .chunks(NODE_CAPACITY)
+                .map(|children| children.iter().skip(1).fold(children[0], |acc, b| acc.union(b)))
+                .collect();
+            levels.push(parents);
+        }
+        Self { items, levels }
+    }
+
+    /// Items whose bounds intersect a rectangle, in ascending order.
+    pub fn query(&self, rect: &Bounds) -> Vec<usize> {
+        let mut found = Vec::new();
+        let Some(top) = self.levels.len().checked_sub(1) else { return found };
+        let mut stack: Vec<(usize, usize)> = (0..self.levels[top].len()).map(|i| (top, i)).collect();
+        while let Some((level, i)) = stack.pop() {
+            if !self.levels[level][i].intersects(rect) {
+                continue;
+            }
+            if level == 0 {
+                found.push(self.items[i]);
+            } else {
+                let children = i * NODE_CAPACITY..((i + 1) * NODE_CAPACITY).min(self.levels[level - 1].len());
+                stack.extend(children.map(|child| (level - 1, child)));
+            }
+        }
+        found.sort_unstable();
+        found
+    }
+}
+
+fn center(bounds: &Bounds) -> [f64; 2] {
+    let [x0, y0, x1, y1] = bounds.0;
+    [(x0 + x1) / 2.0, (y0 + y1) / 2.0]
+}
@@ -0,0 +1,114 @@
+/// Axis-aligned bounds `[x_min, y_min, x_max, y_max]`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Bounds(pub [f64; 4]);
+
+impl Bounds {
+    /// Bounds of points, or `None` if there are none.
+    pub fn of(points: impl IntoIterator<Item = [f64; 2]>) -> Option<Self> {
+        points.into_iter().fold(None, |bounds, [x, y]| {
+            let [x0, y0, x1, y1] = bounds.map_or([x, y, x, y], |Bounds(b)| b);
+            Some(Self([x0.min(x), y0.min(y), x1.max(x), y1.max(y)]))
+        })
+    }
+
+    pub fn union(&self, other: &Self) -> Self {
+        let (a, b) = (self.0, other.0);
+        Self([a[0].min(b[0]), a[1].min(b[1]), a[2].max(b[2]), a[3].max(b[3])])
+    }
+
+    pub fn intersects(&self, other: &Self) -> bool {
+        let (a, b) = (self.0, other.0);
+        a[0] <= b[2] && b[0] <= a[2] && a[1] <= b[3] && b[1] <= a[3]
+    }
+
+    /// Whether a point is inside, boundary included.
+    pub fn contains(&self, [x, y]: [f64; 2]) -> bool {
+        let b = self.0;
+        x >= b[0] && x <= b[2] && y >= b[1] && y <= b[3]
+    }
+
+    fn corners(&self) -> [[f64; 2]; 4] {
+        let [x0, y0, x1, y1] = self.0;
+        [[x0, y0], [x1, y0], [x1, y1], [x0, y1]]
+    }
+}
+
+/// Segments of a ring, closing it.
+fn ring_edges(ring: &[[f64; 2]]) -> impl Iterator<Item = ([f64; 2], [f64; 2])> + '_ {
+    (0..ring.len()).map(move |i| (ring[i], ring[(i + 1) % ring.len()]))
+}
+
+fn cross(o: [f64; 2], a: [f64; 2], b: [f64; 2]) -> f64 {
+    (a[0] - o[0]) * (b[1] - o[1]) - (a[1] - o[1]) * (b[0] - o[0])
+}
+
+/// Whether two closed segments share a point.
+fn segments_intersect(p1: [f64; 2], p2: [f64; 2], q1: [f64; 2], q2: [f64; 2]) -> bool {
+    let on_segment = |a: [f64; 2], b: [f64; 2], p: [f64; 2]| {
+        p[0] >= a[0].min(b[0]) && p[0] <= a[0].max(b[0]) && p[1] >= a[1].min(b[1]) && p[1] <= a[1].max(b[1])
+    };
+    let (d1, d2) = (cross(q1, q2, p1), cross(q1, q2, p2));
+    let (d3, d4) = (cross(p1, p2, q1), cross(p1, p2, q2));
+    if ((d1 > 0.0 && d2 < 0.0) || (d1 < 0.0 && d2 > 0.0)) && ((d3 > 0.0 && d4 < 0.0) || (d3 < 0.0 && d4 > 0.0)) {
+        return true;
+    }
+    (d1 == 0.0 && on_segment(q1, q2, p1))
+        || (d2 == 0.0 && on_segment(q1, q2, p2))
+        || (d3 == 0.0 && on_segment(p1, p2, q1))
+        || (d4 == 0.0 && on_segment(p1, p2, q2))
+}
+
+/// Whether a point is inside a ring, by the even-odd rule.
+fn ring_contains(ring: &[[f64; 2]], [x, y]: [f64; 2]) -> bool {
+    let mut inside = false;
+    for (a, b) in ring_edges(ring) {
+        if (a[1] > y) != (b[1] > y) && x < a[0] + (y - a[1]) / (b[1] - a[1]) * (b[0] - a[0]) {
+            inside = !inside;
+        }
+    }
+    inside
+}
+
+/// Polygon with holes, as built by `NuScenesMap.extract_polygon`.
+#[derive(Clone, Debug, Default)]
+pub struct Polygon {
+    pub exterior: Vec<[f64; 2]>,
+    pub holes: Vec<Vec<[f64; 2]>>,
+}
+
+impl Polygon {
+    pub fn rings(&self) -> impl Iterator<Item = &[[f64; 2]]> {
+        std::iter::once(self.exterior.as_slice()).chain(self.holes.iter().map(Vec::as_slice))
+    }
+
+    /// Whether the polygon has an area, which needs at least three exterior nodes.
+    pub fn is_valid(&self) -> bool {
+        self.exterior.len() >= 3
+    }
+
+    /// Whether a point is inside the polygon and outside of its holes.
+    pub fn contains(&self, point: [f64; 2]) -> bool {
+        self.is_valid()
+            && ring_contains(&self.exterior, point)
+            && !self.holes.iter().any(|hole| ring_contains(hole, point))
+    }
+
+    /// Whether the polygon shares a point with a rectangle, as `Polygon.intersects`.
+    pub fn intersects_rect(&self, rect: &Bounds) -> bool {
+        if !self.is_valid() || !Bounds::of(self.exterior.iter().copied()).is_some_and(|b| b.intersects(rect)) {
+            return false;
+        }
+        if self.rings().flatten().any(|p| rect.contains(*p)) || rect.corners().iter().any(|c| self.contains(*c)) {
+            return true;
+        }
+        let corners = rect.corners();
+        self.rings()
+            .flat_map(ring_edges)
+            .any(|(a, b)| (0..4).any(|i| segments_intersect(a, b, corners[i], corners[(i + 1) % 4])))
+    }
+
+    /// Whether the polygon lies within a rectangle, as `Polygon.within`.
+    pub fn within_rect(&self, rect: &Bounds) -> bool {
+        self.is_valid() && self.exterior.iter().all(|p| rect.contains(*p))
+    }
+}
@@ -0,0 +1,194 @@
+use std::sync::OnceLock;
+
+use ahash::{HashMap, HashMapExt};
+use pyo3::exceptions::{PyKeyError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+use crate::domain::ToPyDict;
+use crate::nusc::{Tables, decode_token};
+use crate::table::{AsRefToken, Table};
+
+type TokenIndex = HashMap<[u8; 16], Box<[[u8; 16]]>>;
+
+/// Reverse indexes between related tables, each built on first use.
+#[derive(Default)]
+pub struct ReverseIndex {
+    log_to_scenes: OnceLock<TokenIndex>,
+    location_to_scenes: OnceLock<HashMap<String, Box<[[u8; 16]]>>>,
+    scene_to_samples: OnceLock<TokenIndex>,
+    sample_to_instances: OnceLock<TokenIndex>,
+    category_to_instances: OnceLock<TokenIndex>,
+    calib_to_sample_data: OnceLock<TokenIndex>,
+    sensor_to_calibs: OnceLock<TokenIndex>,
+    attribute_to_anns: OnceLock<TokenIndex>,
+    sample_data_to_lidarseg: OnceLock<HashMap<[u8; 16], [u8; 16]>>,
+    sample_data_to_panoptic: OnceLock<HashMap<[u8; 16], [u8; 16]>>,
+}
+
+/// Groups `(key, value)` pairs into a one-to-many index, keeping the input order of the values.
+fn group(pairs: impl Iterator<Item = ([u8; 16], [u8; 16])>) -> TokenIndex {
+    let mut acc: HashMap<[u8; 16], Vec<[u8; 16]>> = HashMap::new();
+    for (key, val) in pairs {
+        acc.entry(key).or_default().push(val);
+    }
+    acc.into_iter().map(|(key, vals)| (key, vals.into_boxed_slice())).collect()
+}
+
+impl Tables {
+    pub(crate) fn log_scenes_index(&self) -> &TokenIndex {
+        self.index.log_to_scenes.get_or_init(|| group(self.scene.iter().map(|scene| (scene.log_token, scene.token))))
+    }
+
+    pub(crate) fn location_scenes_index(&self) -> &HashMap<String, Box<[[u8; 16]]>> {
+        self.index.location_to_scenes.get_or_init(|| {
+            let mut acc: HashMap<String, Vec<[u8; 16]>> = HashMap::new();
+            for scene in &self.scene {
+                let log = self.log.get(&scene.log_token).unwrap();
+                acc.entry(log.location.to_string()).or_default().push(scene.token);
+            }
+            acc.into_iter().map(|(key, vals)| (key, vals.into_boxed_slice())).collect()
+        })
+    }
+
+    /// Samples of each scene, in temporal order.
+    pub(crate) fn scene_samples_index(&self) -> &TokenIndex {
+        self.index.scene_to_samples.get_or_init(|| {
+            self.scene
+                .iter()
+                .map(|scene| {
+                    let mut samples = Vec::with_capacity(scene.nbr_samples as usize);
+                    let mut cursor = Some(scene.first_sample_token);
+                    while let Some(sample) = cursor.and_then(|tok| self.sample.get(&tok)) {
+                        samples.push(sample.token);
+                        cursor = sample.next;
+                    }
+                    (scene.token, samples.into_boxed_slice())
+                })
+                .collect()
+        })
+    }
+
+    pub(crate) fn sample_instances_index(&self) -> &TokenIndex {
+        self.index.sample_to_instances.get_or_init(|| {
+            self.sample
+                .iter()
+                .map(|sample| {
+                    let instances = sample
+                        .anns
+                        .iter()
+                        .map(|tok| self.sample_ann.get(tok).unwrap().instance_token)
+                        .collect::<Vec<_>>()
+                        .into_boxed_slice();
+                    (sample.token, instances)
+                })
+                .collect()
+        })
+    }
+
+    pub(crate) fn category_instances_index(&self) -> &TokenIndex {
+        self.index
+            .category_to_instances
+            .get_or_init(|| group(self.instance.iter().map(|ins| (ins.category_token, ins.token))))
+    }
+
+    pub(crate) fn calib_sample_data_index(&self) -> &TokenIndex {
+        self.index
+            .calib_to_sample_data
+            .get_or_init(|| group(self.sample_data.iter().map(|sd| (sd.calibrated_sensor_token, sd.token))))
+    }
+
+    pub(crate) fn sensor_calibs_index(&self) -> &TokenIndex {
+        self.index.sensor_to_calibs.get_or_init(|| group(self.calib.iter().map(|cal| (cal.sensor_token, cal.token))))
+    }
+
+    pub(crate) fn attribute_anns_index(&self) -> &TokenIndex {
+        self.index.attribute_to_anns.get_or_init(|| {
+            group(self.sample_ann.iter().flat_map(|ann| ann.attribute_tokens.iter().map(|attr| (*attr, ann.token))))
+        })
+    }
+
+    pub(crate) fn sample_data_lidarseg_index(&self) -> Option<&HashMap<[u8; 16], [u8; 16]>> {
+        let lidarseg = self.lidarseg.as_ref()?;
+        Some(
+            self.index
+                .sample_data_to_lidarseg
+                .get_or_init(|| lidarseg.iter().map(|rec| (rec.sample_data_token, rec.token)).collect()),
+        )
+    }
+
+    pub(crate) fn sample_data_panoptic_index(&self) -> Option<&HashMap<[u8; 16], [u8; 16]>> {
+        let panoptic = self.panoptic.as_ref()?;
+        Some(
+            self.index
+                .sample_data_to_panoptic
+                .get_or_init(|| panoptic.iter().map(|rec| (rec.sample_data_token, rec.token)).collect()),
+        )
+    }
+}
+
+/// Looks up `token` in `index`, after checking that it exists in the table it belongs to.
+pub(crate) fn lookup<T: AsRefToken>(table: &Table<T>, index: &TokenIndex, token: &str) -> PyResult<Vec<String>> {
+    let bytes = decode_token(token)?;
+    table.get(&bytes).ok_or_else(|| PyKeyError::new_err(token.to_string()))?;
+    Ok(index.get(&bytes).map(|toks| toks.iter().map(hex::encode).collect()).unwrap_or_default())
+}
+
+/// Returns the extension record for a sample data, or `None` if the sample data has none, after checking that the
+/// sample data exists.
+pub(crate) fn lookup_extension<'py, S: AsRefToken, T: ToPyDict + AsRefToken>(
+    py: Python<'py>, sample_data: &Table<S>, table: Option<&Table<T>>, index: Option<&HashMap<[u8; 16], [u8; 16]>>,
+    name: &str, sample_data_token: &str,
+) -> PyResult<Option<Bound<'py, PyDict>>> {
+    let (table, index) = table
+        .zip(index)
+        .ok_or_else(|| PyValueError::new_err(format!("{name} not loaded due to missing '{name}.json'")))?;
+    let bytes = decode_token(sample_data_token)?;
+    sample_data.get(&bytes).ok_or_else(|| PyKeyError::new_err(sample_data_token.to_string()))?;
+    index.get(&bytes).map(|tok| table.get(tok).unwrap().to_py_dict(py)).transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::{SensorChannel, SensorModality};
+    use crate::domain::Sensor;
+
+    /// Calibrated sensors 1-4 of sensors 10 and 20, interleaved, and sensor 30 without any calibration.
+    fn sensors() -> (Table<Sensor>, TokenIndex) {
+        let sensors = [
+            (10, SensorChannel::CamFront, SensorModality::Camera),
+            (20, SensorChannel::LidarTop, SensorModality::Lidar),
+            (30, SensorChannel::CamBack, SensorModality::Camera),
+        ]
+        .map(|(tok, channel, modality)| Sensor { token: [tok; 16], channel, modality });
+        let index = group([(20, 1), (10, 2), (20, 3), (10, 4)].into_iter().map(|(key, val)| ([key; 16], [val; 16])));
+        (Table::new(Box::new(sensors)), index)
+    }
+
+    #[test]
+    fn group_keeps_the_order_of_the_values() {
+        let (_, index) = sensors();
+        assert_eq!(index.len(), 2);
+        assert_eq!(*index[&[10; 16]], [[2; 16], [4; 16]]);
+        assert_eq!(*index[&[20; 16]], [[1; 16], [3; 16]]);
+    }
+
+    #[test]
+    fn lookup_checks_the_token_before_the_index() {
+        let (table, index) = sensors();
+        assert_eq!(
+            lookup(&table, &index, &hex::encode([20; 16])).unwrap(),
+            [hex::encode([1; 16]), hex::encode([3; 16])]
+        );
+        assert!(lookup(&table, &index, &hex::encode([30; 16])).unwrap().is_empty());
+
+        Python::initialize();
+        Python::attach(|py| {
+            let missing = lookup(&table, &index, &hex::encode([40; 16])).unwrap_err();
+            assert!(missing.is_instance_of::<PyKeyError>(py));
+            let invalid = lookup(&table, &index, "not-a-token").unwrap_err();
+            assert!(invalid.is_instance_of::<PyValueError>(py));
+        });
+    }
+}
@@ -1,17 +1,48 @@
+mod bev;
+mod boxes;
+mod camera;
+mod classmap;
+mod codec;
 mod common;
+mod depth;
 mod domain;
+mod geometry;
+mod hdmap;
+mod index;
+mod mapmask;
 mod model;
 mod nusc;
+mod pointcloud;
 mod proxy;
+mod segstats;
 mod table;
+mod timeline;
+mod trajectory;
+mod velocity;
 
 /// A module for loading and querying nuScenes tables implemented in Rust
 #[pyo3::pymodule]
 mod _lib {
     use pyo3::prelude::*;
 
+    #[pymodule_export]
+    use super::boxes::Boxes;
+    #[pymodule_export]
+    use super::camera::{BoxVisibility, py_view_points};
+    #[pymodule_export]
+    use super::classmap::ClassMapping;
+    #[pymodule_export]
+    use super::common::{PyAttributeKind, PyCategoryKind, PyMapLocation};
+    #[pymodule_export]
+    use super::geometry::{quaternion_slerp, quaternion_yaw, transform_matrix};
+    #[pymodule_export]
+    use super::hdmap::VectorMap;
+    #[pymodule_export]
+    use super::mapmask::MapMask;
     #[pymodule_export]
     use super::nusc::Tables;
+    #[pymodule_export]
+    use super::pointcloud::{LidarPoints, voxelize};
 
     #[pymodule_init]
     fn init(_m: &Bound<'_, PyModule>) -> PyResult<()> {
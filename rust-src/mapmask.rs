@@ -0,0 +1,401 @@
+use std::fmt;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+
+use ahash::{HashMap, HashMapExt};
+use numpy::ndarray::{Array2, ArrayD, IxDyn};
+use numpy::{IntoPyArray, PyArray1, PyArray2, PyArrayDyn, PyReadonlyArray1};
+use pyo3::exceptions::{PyFileNotFoundError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyTuple;
+use rayon::prelude::*;
+
+use crate::codec::{Image, decode_png};
+use crate::domain::SampleData;
+use crate::geometry::Quaternion;
+use crate::nusc::Tables;
+
+/// Resolution of the map PNG files, in meters per pixel.
+pub const NATIVE_RESOLUTION: f64 = 0.1;
+
+const FOREGROUND: u8 = 255;
+const BACKGROUND: u8 = 0;
+
+// Squared distances of the distance transform start from this instead of infinity, which breaks its arithmetic.
+const FAR: f32 = 1e20;
+
+/// Semantic prior of a map, read and decoded on first use, as `MapMask` in `map_mask.py`. Queries on color images
+/// use their first channel.
+pub struct MaskSource {
+    pub path: PathBuf,
+    pub resolution: f64,
+    raster: OnceLock<Result<Image, String>>,
+    distance: OnceLock<Vec<f32>>,
+    // Read-only arrays of the masks by the bits of their dilation, which are too large to copy on every call.
+    arrays: Mutex<HashMap<u64, Py<PyArrayDyn<u8>>>>,
+}
+
+impl fmt::Debug for MaskSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MaskSource").field("path", &self.path).field("resolution", &self.resolution).finish()
+    }
+}
+
+/// Resizes an image to `scale` times its size with nearest neighbours, as `Image.resize` with `Image.NEAREST`.
+fn resize_nearest(image: Image, scale: f64) -> Image {
+    let (width, height) = ((image.width as f64 * scale) as usize, (image.height as f64 * scale) as usize);
+    if (width, height) == (image.width, image.height) {
+        return image;
+    }
+    let (sx, sy) = (image.width as f64 / width as f64, image.height as f64 / height as f64);
+    let cols: Vec<usize> = (0..width).map(|x| (((x as f64 + 0.5) * sx) as usize).min(image.width - 1)).collect();
+    let channels = image.channels;
+    let mut data = vec![0; width * height * channels];
+    data.par_chunks_mut((width * channels).max(1)).enumerate().for_each(|(y, row)| {
+        let src = (((y as f64 + 0.5) * sy) as usize).min(image.height - 1) * image.width;
+        for (out, &x) in row.chunks_exact_mut(channels).zip(&cols) {
+            out.copy_from_slice(&image.data[(src + x) * channels..(src + x + 1) * channels]);
+        }
+    });
+    Image { width, height, channels, data }
+}
+
+/// Squared Euclidean distance transform of a 1D function, after Felzenszwalb and Huttenlocher.
+fn distance_1d(f: &[f32], d: &mut [f32], v: &mut [usize], z: &mut [f32]) {
+    let n = f.len();
+    let mut k = 0;
+    v[0] = 0;
+    z[0] = f32::NEG_INFINITY;
+    z[1] = f32::INFINITY;
+    for q in 1..n {
+        let s = loop {
+            let p = v[k];
+            let s = ((f[q] + (q * q) as f32) - (f[p] + (p * p) as f32)) / (2 * (q - p)) as f32;
+            if s > z[k] || k == 0 {
+                break s;
+            }
+            k -= 1;
+        };
+        if s > z[k] {
+            k += 1;
+        }
+        v[k] = q;
+        z[k] = s;
+        z[k + 1] = f32::INFINITY;
+    }
+    k = 0;
+    for (q, out) in d.iter_mut().enumerate() {
+        while z[k + 1] < q as f32 {
+            k += 1;
+        }
+        let p = v[k];
+        *out = (q as f32 - p as f32).powi(2) + f[p];
+    }
+}
+
+/// Whether the pixel at a row-major index is foreground, in the first channel.
+fn is_foreground(image: &Image, index: usize) -> bool {
+    image.data[index * image.channels] == FOREGROUND
+}
+
+/// Distance in pixels of every pixel to the nearest foreground pixel, infinite if there is none.
+fn distance_transform(image: &Image) -> Vec<f32> {
+    let (width, height) = (image.width, image.height);
+    if width == 0 || height == 0 {
+        return Vec::new();
+    }
+    // Columns first, stored column-major.
+    let mut columns = vec![0.0f32; width * height];
+    columns.par_chunks_mut(height).enumerate().for_each(|(x, out)| {
+        let f: Vec<f32> = (0..height).map(|y| if is_foreground(image, y * width + x) { 0.0 } else { FAR }).collect();
+        let (mut v, mut z) = (vec![0; height], vec![0.0; height + 1]);
+        distance_1d(&f, out, &mut v, &mut z);
+    });
+    let mut distance = vec![0.0f32; width * height];
+    distance.par_chunks_mut(width).enumerate().for_each(|(y, out)| {
+        let f: Vec<f32> = (0..width).map(|x| columns[x * height + y]).collect();
+        let (mut v, mut z) = (vec![0; width], vec![0.0; width + 1]);
+        distance_1d(&f, out, &mut v, &mut z);
+        for d in out.iter_mut() {
+            *d = if *d >= FAR { f32::INFINITY } else { d.sqrt() };
+        }
+    });
+    distance
+}
+
+impl MaskSource {
+    pub fn new(path: PathBuf, resolution: f64) -> Self {
+        Self {
+            path,
+            resolution,
+            raster: OnceLock::new(),
+            distance: OnceLock::new(),
+            arrays: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn load(path: &Path, resolution: f64) -> Result<Image, String> {
+        let mut bytes = Vec::new();
+        std::fs::File::open(path)
+            .and_then(|mut file| file.read_to_end(&mut bytes))
+            .map_err(|e| format!("Failed to read {}: {e}", path.display()))?;
+        let image = decode_png(&bytes).map_err(|e| format!("Failed to decode {}: {e}", path.display()))?;
+        Ok(resize_nearest(image, NATIVE_RESOLUTION / resolution))
+    }
+
+    /// Returns the mask at the resolution of this source, decoding it on first use.
+    pub fn raster(&self) -> PyResult<&Image> {
+        if !self.path.exists() {
+            return Err(PyFileNotFoundError::new_err(format!("map mask {} does not exist", self.path.display())));
+        }
+        self.raster
+            .get_or_init(|| Self::load(&self.path, self.resolution))
+            .as_ref()
+            .map_err(|e| PyValueError::new_err(e.clone()))
+    }
+
+    /// Returns the distance in pixels of every pixel to the mask, computing it on first use.
+    fn distance(&self) -> PyResult<&[f32]> {
+        let raster = self.raster()?;
+        Ok(self.distance.get_or_init(|| distance_transform(raster)))
+    }
+
+    /// Maps global coordinates to pixel coordinates, as `MapMask.to_pixel_coords`.
+    pub fn to_pixel(&self, raster: &Image, [x, y]: [f64; 2]) -> [i64; 2] {
+        [(x / self.resolution).round() as i64, (raster.height as f64 - y / self.resolution).round() as i64]
+    }
+
+    /// Row-major index of the pixel at global coordinates, if it is inside the mask.
+    fn pixel_index(&self, raster: &Image, point: [f64; 2]) -> Option<usize> {
+        let [px, py] = self.to_pixel(raster, point);
+        let inside = px >= 0 && py >= 0 && (px as usize) < raster.width && (py as usize) < raster.height;
+        inside.then(|| py as usize * raster.width + px as usize)
+    }
+
+    /// Returns the mask dilated by `dilation` meters, as `MapMask.mask`, with its number of channels. Only the
+    /// undilated mask keeps all channels.
+    pub fn mask(&self, dilation: f64) -> PyResult<(Vec<u8>, usize)> {
+        let raster = self.raster()?;
+        if dilation == 0.0 {
+            return Ok((raster.data.clone(), raster.channels));
+        }
+        let limit = (dilation / self.resolution) as f32;
+        Ok((self.distance()?.par_iter().map(|&d| if d <= limit { FOREGROUND } else { BACKGROUND }).collect(), 1))
+    }
+
+    /// Whether global points are on the mask dilated by `dilation` meters, as `MapMask.is_on_mask`.
+    pub fn is_on_mask(&self, points: &[[f64; 2]], dilation: f64) -> PyResult<Vec<bool>> {
+        let raster = self.raster()?;
+        if dilation == 0.0 {
+            return Ok(points
+                .par_iter()
+                .map(|p| self.pixel_index(raster, *p).is_some_and(|i| is_foreground(raster, i)))
+                .collect());
+        }
+        let limit = (dilation / self.resolution) as f32;
+        let distance = self.distance()?;
+        Ok(points.par_iter().map(|p| self.pixel_index(raster, *p).is_some_and(|i| distance[i] <= limit)).collect())
+    }
+
+    /// Distance in meters from global points to the mask, measured between pixel centers. NaN outside of the map.
+    pub fn distances(&self, points: &[[f64; 2]]) -> PyResult<Vec<f64>> {
+        let raster = self.raster()?;
+        let distance = self.distance()?;
+        Ok(points
+            .par_iter()
+            .map(|p| self.pixel_index(raster, *p).map_or(f64::NAN, |i| distance[i] as f64 * self.resolution))
+            .collect())
+    }
+
+    /// Crops a `(length, width)` patch in meters centered on a global position and aligned with a heading, sampled
+    /// every `resolution` meters. The heading points up, to the first row, and its left to the first column. Pixels
+    /// outside of the map are background.
+    pub fn patch(
+        &self, center: [f64; 2], yaw: f64, (length, width): (f64, f64), resolution: f64, dilation: f64,
+    ) -> PyResult<(Vec<u8>, usize, usize)> {
+        let valid = [resolution, length, width].iter().all(|v| v.is_finite() && *v > 0.0);
+        let (rows, cols) = ((length / resolution).round() as usize, (width / resolution).round() as usize);
+        if !valid || rows.checked_mul(cols).is_none() {
+            return Err(PyValueError::new_err(format!(
+                "Invalid patch size {:?} or resolution {resolution}",
+                (length, width)
+            )));
+        }
+        let raster = self.raster()?;
+        let dilated = (dilation != 0.0).then(|| self.distance()).transpose()?;
+        let limit = (dilation / self.resolution) as f32;
+        let (sin, cos) = yaw.sin_cos();
+
+        let mut data = vec![BACKGROUND; rows * cols];
+        data.par_chunks_mut(cols.max(1)).enumerate().for_each(|(i, row)| {
+            let forward = length / 2.0 - (i as f64 + 0.5) * resolution;
+            for (j, out) in row.iter_mut().enumerate() {
+                let left = width / 2.0 - (j as f64 + 0.5) * resolution;
+                let point = [center[0] + cos * forward - sin * left, center[1] + sin * forward + cos * left];
+                let Some(index) = self.pixel_index(raster, point) else { continue };
+                let on_mask = match dilated {
+                    Some(distance) => distance[index] <= limit,
+                    None => is_foreground(raster, index),
+                };
+                *out = if on_mask { FOREGROUND } else { BACKGROUND };
+            }
+        });
+        Ok((data, rows, cols))
+    }
+}
+
+/// Reads coordinates given as a scalar, a sequence or a 1D array.
+fn coords_from_py(value: &Bound<'_, PyAny>) -> PyResult<Vec<f64>> {
+    if let Ok(array) = value.extract::<PyReadonlyArray1<'_, f64>>() {
+        return Ok(array.as_array().to_vec());
+    }
+    if let Ok(scalar) = value.extract::<f64>() {
+        return Ok(vec![scalar]);
+    }
+    value.extract()
+}
+
+fn points_from_py(x: &Bound<'_, PyAny>, y: &Bound<'_, PyAny>) -> PyResult<Vec<[f64; 2]>> {
+    let (x, y) = (coords_from_py(x)?, coords_from_py(y)?);
+    if x.len() != y.len() {
+        return Err(PyValueError::new_err(format!("Got {} x coordinates and {} y coordinates", x.len(), y.len())));
+    }
+    Ok(x.into_iter().zip(y).map(|(x, y)| [x, y]).collect())
+}
+
+/// Semantic prior of a map, the drivable surface and sidewalks, as `MapMask` in `map_mask.py`.
+///
+/// The PNG file is only read and decoded on first use, and the distance transform for dilations and distances on
+/// first need. Masks of the map table share these, and the arrays returned by `mask`.
+#[pyclass(module = "nuscenes._lib", frozen)]
+#[derive(Clone, Debug)]
+pub struct MapMask {
+    pub source: Arc<MaskSource>,
+}
+
+#[pymethods]
+impl MapMask {
+    #[new]
+    #[pyo3(signature = (img_file, resolution=0.1))]
+    fn new(img_file: PathBuf, resolution: f64) -> PyResult<Self> {
+        if !resolution.is_finite() {
+            return Err(PyValueError::new_err(format!("Invalid resolution {resolution}")));
+        }
+        if resolution < NATIVE_RESOLUTION {
+            return Err(PyValueError::new_err("Only supports down to 0.1 meter resolution."));
+        }
+        Ok(Self { source: Arc::new(MaskSource::new(img_file, resolution)) })
+    }
+
+    #[getter]
+    fn img_file(&self) -> PathBuf {
+        self.source.path.clone()
+    }
+
+    #[getter]
+    fn resolution(&self) -> f64 {
+        self.source.resolution
+    }
+
+    #[getter]
+    fn foreground(&self) -> u8 {
+        FOREGROUND
+    }
+
+    #[getter]
+    fn background(&self) -> u8 {
+        BACKGROUND
+    }
+
+    /// Returns the map mask, optionally dilated by `dilation` meters. The array is read-only and shared by the calls
+    /// with the same dilation. It is `(H, W)`, or `(H, W, C)` for the undilated mask of a color image.
+    #[pyo3(signature = (dilation=0.0))]
+    fn mask<'py>(&self, py: Python<'py>, dilation: f64) -> PyResult<Bound<'py, PyArrayDyn<u8>>> {
+        if let Some(array) = self.source.arrays.lock().unwrap().get(&dilation.to_bits()) {
+            return Ok(array.bind(py).clone());
+        }
+        let raster = py.detach(|| self.source.raster())?;
+        let (data, channels) = py.detach(|| self.source.mask(dilation))?;
+        let shape =
+            if channels == 1 { vec![raster.height, raster.width] } else { vec![raster.height, raster.width, channels] };
+        let array = ArrayD::from_shape_vec(IxDyn(&shape), data).unwrap().into_pyarray(py);
+        array.call_method1("setflags", (false,))?;
+        // Another thread may have computed the same mask meanwhile, in which case its array is kept.
+        let mut arrays = self.source.arrays.lock().unwrap();
+        Ok(arrays.entry(dilation.to_bits()).or_insert_with(|| array.unbind()).bind(py).clone())
+    }
+
+    /// The 4x4 transform from global coordinates to pixel coordinates.
+    #[getter]
+    fn transform_matrix<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyArray2<f64>>> {
+        let height = py.detach(|| self.source.raster())?.height as f64;
+        let r = self.source.resolution;
+        let matrix =
+            [[1.0 / r, 0.0, 0.0, 0.0], [0.0, -1.0 / r, 0.0, height], [0.0, 0.0, 1.0, 0.0], [0.0, 0.0, 0.0, 1.0]];
+        Ok(Array2::from_shape_vec((4, 4), matrix.concat()).unwrap().into_pyarray(py))
+    }
+
+    /// Whether global coordinates are on the mask, optionally dilated by `dilation` meters.
+    #[pyo3(signature = (x, y, dilation=0.0))]
+    fn is_on_mask<'py>(
+        &self, py: Python<'py>, x: &Bound<'py, PyAny>, y: &Bound<'py, PyAny>, dilation: f64,
+    ) -> PyResult<Bound<'py, PyArray1<bool>>> {
+        let points = points_from_py(x, y)?;
+        Ok(py.detach(|| self.source.is_on_mask(&points, dilation))?.into_pyarray(py))
+    }
+
+    /// Maps global coordinates to the pixel coordinates of the mask.
+    fn to_pixel_coords<'py>(
+        &self, py: Python<'py>, x: &Bound<'py, PyAny>, y: &Bound<'py, PyAny>,
+    ) -> PyResult<Bound<'py, PyTuple>> {
+        let points = points_from_py(x, y)?;
+        let raster = py.detach(|| self.source.raster())?;
+        let (px, py_): (Vec<i32>, Vec<i32>) = points
+            .iter()
+            .map(|p| {
+                let [px, py] = self.source.to_pixel(raster, *p);
+                (px as i32, py as i32)
+            })
+            .unzip();
+        (px.into_pyarray(py), py_.into_pyarray(py)).into_pyobject(py)
+    }
+
+    /// Distance in meters from global coordinates to the mask, 0 on it and NaN outside of the map.
+    fn distance<'py>(
+        &self, py: Python<'py>, x: &Bound<'py, PyAny>, y: &Bound<'py, PyAny>,
+    ) -> PyResult<Bound<'py, PyArray1<f64>>> {
+        let points = points_from_py(x, y)?;
+        Ok(py.detach(|| self.source.distances(&points))?.into_pyarray(py))
+    }
+
+    /// Crops a `(length, width)` patch in meters centered on global `(x, y)` and aligned with the heading `yaw`, which
+    /// points to the first row while its left points to the first column. Sampled every `resolution` meters, which
+    /// defaults to that of the mask, and optionally dilated by `dilation` meters.
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3(signature = (x, y, yaw, size, resolution=None, dilation=0.0))]
+    fn patch<'py>(
+        &self, py: Python<'py>, x: f64, y: f64, yaw: f64, size: (f64, f64), resolution: Option<f64>, dilation: f64,
+    ) -> PyResult<Bound<'py, PyArray2<u8>>> {
+        let resolution = resolution.unwrap_or(self.source.resolution);
+        let (data, rows, cols) = py.detach(|| self.source.patch([x, y], yaw, size, resolution, dilation))?;
+        Ok(Array2::from_shape_vec((rows, cols), data).unwrap().into_pyarray(py))
+    }
+
+    fn __repr__(&self) -> String {
+        format!("MapMask(img_file={:?}, resolution={})", self.source.path.display().to_string(), self.source.resolution)
+    }
+}
+
+impl Tables {
+    /// Returns the mask of the map of the log of a sample data, with the global position and yaw of its ego pose.
+    pub(crate) fn ego_map_mask(&self, sd: &SampleData) -> (Arc<MaskSource>, [f64; 2], f64) {
+        let sample = self.sample.get(&sd.sample_token).unwrap();
+        let scene = self.scene.get(&sample.scene_token).unwrap();
+        let log = self.log.get(&scene.log_token).unwrap();
+        let map = self.map.get(&log.map_token).unwrap();
+        let pose = self.ego_pose.get(&sd.ego_pose_token).unwrap();
+        let yaw = Quaternion::from_f32(pose.rotation).yaw();
+        (map.mask.clone(), [pose.translation[0] as f64, pose.translation[1] as f64], yaw)
+    }
+}
@@ -34,9 +34,6 @@ pub struct LogModel<'a> {
     pub date_captured: Cow<'a, str>,
     #[serde(borrow)]
     pub location: Cow<'a, str>,
-
-    #[serde(skip)]
-    pub map_token: [u8; 16], // Reverse index from Map
 }
 
 #[serde_as]
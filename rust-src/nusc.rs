@@ -1,23 +1,41 @@
 use std::fs::File;
 use std::path::Path;
-use std::sync::OnceLock;
+use std::sync::{Arc, OnceLock};
 use std::time::Instant;
 
-use ahash::{HashMap, HashMapExt};
-use enum_map::EnumMap;
+use ahash::{HashMap, HashMapExt, HashSet};
+use enum_map::{Enum, EnumMap};
 use log::debug;
 use num_format::ToFormattedString;
+use numpy::ndarray::{Array2, Array3};
+use numpy::{IntoPyArray, PyArray1, PyArray2, PyReadonlyArray2};
 use pyo3::exceptions::{PyFileNotFoundError, PyKeyError, PyValueError};
-use pyo3::types::PyDict;
+use pyo3::types::{PyDict, PyTuple};
 use pyo3::{IntoPyObjectExt, prelude::*};
-use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use rayon::prelude::*;
 use serde::Deserialize;
 
-use crate::common::SensorChannel;
+use crate::bev::BevGrid;
+use crate::boxes::{BoxFilter, Boxes};
+use crate::camera::{BoxVisibility, ProjectedBoxes, points_from_pyarray};
+use crate::classmap::ClassMapping;
+use crate::common::{MapLocation, SensorChannel, SensorModality};
+use crate::depth::{DepthProjection, PaintedPoints};
 use crate::domain::*;
+use crate::geometry::{Frame, Quaternion, matrix_to_pyarray};
+use crate::hdmap::{HdMap, VectorMap};
+use crate::index::{ReverseIndex, lookup, lookup_extension};
+use crate::mapmask::{MaskSource, NATIVE_RESOLUTION};
 use crate::model::*;
+use crate::pointcloud::{
+    LIDAR_SWEEP_FIELDS, LidarPoints, RadarFilters, RadarPoints, Sweeps, VoxelGrid, decode_panoptic,
+};
 use crate::proxy::*;
+use crate::segstats::{LabelSource, mean_std, unique_counts};
 use crate::table::{AsRefToken, Table};
+use crate::timeline::Timeline;
+use crate::trajectory::{DERIVATIVE_WINDOW, EgoTrajectory, Trajectories};
+use crate::velocity::{MAX_TIME_DIFF, annotate_velocities};
 
 macro_rules! load_table {
     ($scope:ident, $root:expr, $filename:expr, $target:ident) => {
@@ -41,39 +59,60 @@ where
     let mmap = Box::leak(Box::new(mmap));
     let json =
         std::str::from_utf8(mmap).map_err(|e| PyValueError::new_err(format!("Invalid UTF-8 token found: {e}")))?;
-    serde_json::from_str(&json).map_err(|e| PyValueError::new_err(format!("Failed to parse {name}: {}", e)))
+    serde_json::from_str(json).map_err(|e| PyValueError::new_err(format!("Failed to parse {name}: {}", e)))
 }
 
 #[pyclass(module = "nuscenes._lib")]
 /// Class for loading tables and querying data from the nuScenes dataset.
 pub struct Tables {
-    version: String,
-    dataroot: String,
+    pub(crate) version: String,
+    pub(crate) dataroot: String,
+    pub(crate) annotation_fields: AnnotationFields,
+    pub(crate) class_mapping: Option<ClassMapping>,
     // Vehicle
-    log: Table<Log<'static>>,
-    map: Table<Map<'static>>,
-    sensor: Table<Sensor>,
-    calib: Table<CalibratedSensor>,
+    pub(crate) log: Table<Log<'static>>,
+    pub(crate) map: Table<Map<'static>>,
+    pub(crate) sensor: Table<Sensor>,
+    pub(crate) calib: Table<CalibratedSensor>,
     // Extraction
-    scene: Table<Scene<'static>>,
-    sample: Table<Sample>,
-    sample_data: Table<SampleData<'static>>,
-    ego_pose: Table<EgoPose>,
+    pub(crate) scene: Table<Scene<'static>>,
+    pub(crate) sample: Table<Sample>,
+    pub(crate) sample_data: Table<SampleData<'static>>,
+    pub(crate) ego_pose: Table<EgoPose>,
     // Annotation
-    instance: Table<Instance>,
-    sample_ann: Table<SampleAnnotation<'static>>,
+    pub(crate) instance: Table<Instance>,
+    pub(crate) sample_ann: Table<SampleAnnotation<'static>>,
     // Taxonomy
-    category: Table<Category<'static>>,
-    attribute: Table<Attribute<'static>>,
+    pub(crate) category: Table<Category<'static>>,
+    pub(crate) attribute: Table<Attribute<'static>>,
     // Extensions
-    lidarseg: Option<Table<LidarSeg<'static>>>,
-    panoptic: Option<Table<Panoptic<'static>>>,
+    pub(crate) lidarseg: Option<Table<LidarSeg<'static>>>,
+    pub(crate) panoptic: Option<Table<Panoptic<'static>>>,
+    // Lazily-built indexes
+    pub(crate) index: ReverseIndex,
+    pub(crate) timeline: OnceLock<Timeline>,
+    pub(crate) trajectories: OnceLock<Trajectories>,
+    pub(crate) vector_maps: HashMap<MapLocation, OnceLock<Arc<HdMap>>>,
 }
 
 #[pymethods]
 impl Tables {
     #[new]
-    fn new(version: &str, dataroot: &str) -> PyResult<Self> {
+    #[pyo3(signature = (version, dataroot, annotation_fields=None, class_mapping=None))]
+    fn new(
+        version: &str, dataroot: &str, annotation_fields: Option<Vec<String>>, class_mapping: Option<&Bound<'_, PyAny>>,
+    ) -> PyResult<Self> {
+        let class_mapping = class_mapping
+            .map(|m| match m.cast::<ClassMapping>() {
+                Ok(m) => Ok(m.get().clone()),
+                Err(_) => ClassMapping::resolve(&m.extract::<String>()?),
+            })
+            .transpose()?;
+        let annotation_fields = match annotation_fields {
+            Some(names) => AnnotationFields::parse(&names).map_err(PyValueError::new_err)?,
+            None => AnnotationFields::default(),
+        };
+
         let table_root = Path::new(dataroot).join(version);
         if !table_root.exists() {
             return Err(PyFileNotFoundError::new_err(format!("Dataset not found at {}", table_root.display())));
@@ -120,7 +159,7 @@ impl Tables {
 
         // into_iter() is used instead of into_par_iter() for tables that are relatively small.
         let log = log.into_inner().unwrap()?; // 68
-        let map = Table::new(map.into_inner().unwrap()?.into_iter().map(Map::from).collect()); // 4
+        let map = map.into_inner().unwrap()?; // 4
         let sensor = Table::new(sensor.into_inner().unwrap()?.into_iter().map(Sensor::from).collect()); // 12
         let calib = Table::new(calib.into_inner().unwrap()?.into_par_iter().map(CalibratedSensor::from).collect()); // 10200
 
@@ -129,7 +168,7 @@ impl Tables {
         let sample_data = sample_data.into_inner().unwrap()?; // 2,631,083
         let ego_pose = Table::new(ego_pose.into_inner().unwrap()?.into_par_iter().map(EgoPose::from).collect()); // 2,631,083
 
-        let instance = Table::new(instance.into_inner().unwrap()?.into_par_iter().map(Instance::from).collect()); // 64,386
+        let instance = instance.into_inner().unwrap()?; // 64,386
         let sample_ann = sample_ann.into_inner().unwrap()?; // 1,166,187
 
         let category = Table::new(category.into_inner().unwrap()?.into_iter().map(Category::from).collect()); // 23
@@ -173,6 +212,22 @@ impl Tables {
             Table::new(log)
         };
 
+        let map = {
+            let map = map
+                .into_iter()
+                .map(|map| {
+                    let location = map
+                        .log_tokens
+                        .first()
+                        .and_then(|tok| log.get(tok))
+                        .map_or_else(|| MapLocation::Other(String::new()), |log| log.map_location.clone());
+                    let mask = MaskSource::new(Path::new(dataroot).join(map.filename.as_ref()), NATIVE_RESOLUTION);
+                    Map::from_model(location, mask, map)
+                })
+                .collect();
+            Table::new(map)
+        };
+
         let sample_data = {
             let sample_data = sample_data
                 .into_par_iter()
@@ -185,21 +240,48 @@ impl Tables {
             Table::new(sample_data)
         };
 
-        let sample_ann = {
+        let mut sample_ann = {
+            let ins_to_cat: HashMap<[u8; 16], [u8; 16]> =
+                instance.iter().map(|ins| (ins.token, ins.category_token)).collect();
             let sample_ann = sample_ann
                 .into_par_iter()
                 .map(|ann| {
-                    let ins = instance.get(&ann.instance_token).unwrap();
-                    let cat = category.get(&ins.category_token).unwrap();
-                    SampleAnnotation::from_model(cat.name.clone(), ann)
+                    let cat = category.get(&ins_to_cat[&ann.instance_token]).unwrap();
+                    let category_name = cat.name.clone();
+                    let attribute_names = annotation_fields.attribute_names.then(|| {
+                        ann.attribute_tokens.iter().map(|tok| attribute.get(tok).unwrap().name.clone()).collect()
+                    });
+                    let class = class_mapping
+                        .as_ref()
+                        .and_then(|m| m.class_id(&cat.name).map(|id| (id, m.class_name(id).clone())));
+                    SampleAnnotation::from_model(cat.kind.clone(), category_name, attribute_names, class, ann)
                 })
                 .collect();
             Table::new(sample_ann)
         };
 
+        let instance = {
+            // Follow the `next` links from the first annotation so that the track is in temporal order.
+            let instance = instance
+                .into_par_iter()
+                .map(|ins| {
+                    let mut anns = Vec::with_capacity(ins.nbr_annotations as usize);
+                    let mut cursor = Some(ins.first_annotation_token);
+                    while let Some(ann) = cursor.and_then(|tok| sample_ann.get(&tok)) {
+                        anns.push(ann.token);
+                        cursor = ann.next;
+                    }
+                    Instance::from_model(anns.into_boxed_slice(), ins)
+                })
+                .collect();
+            Table::new(instance)
+        };
+
         let sample = {
-            let sample_to_sd: HashMap<[u8; 16], EnumMap<SensorChannel, [u8; 16]>> =
-                sample_data.iter().fold(HashMap::with_capacity(sample.len()), |mut acc, sd| {
+            let sample_to_sd: HashMap<[u8; 16], EnumMap<SensorChannel, [u8; 16]>> = sample_data
+                .iter()
+                .filter(|sd| sd.is_key_frame)
+                .fold(HashMap::with_capacity(sample.len()), |mut acc, sd| {
                     let map = acc.entry(sd.sample_token).or_default();
                     map[sd.channel] = sd.token;
                     acc
@@ -220,12 +302,18 @@ impl Tables {
             Table::new(sample)
         };
 
+        if annotation_fields.velocity {
+            annotate_velocities(&mut sample_ann, &sample, &sample_data, &ego_pose);
+        }
+
         let elapsed = start_time.elapsed();
         debug!(target: "nuscenes", "Done reverse indexing in {:.3} seconds\n======", elapsed.as_secs_f32());
 
         Ok(Self {
             version: version.to_string(),
             dataroot: dataroot.to_string(),
+            annotation_fields,
+            class_mapping,
             log,
             map,
             sensor,
@@ -240,6 +328,10 @@ impl Tables {
             attribute,
             lidarseg,
             panoptic,
+            index: ReverseIndex::default(),
+            timeline: OnceLock::new(),
+            trajectories: OnceLock::new(),
+            vector_maps: MapLocation::MEMBERS.iter().map(|location| (location.clone(), OnceLock::new())).collect(),
         })
     }
 
@@ -314,9 +406,7 @@ impl Tables {
     }
 
     fn get<'py>(slf: PyRef<'py, Self>, table: &str, token: &str) -> PyResult<Bound<'py, PyDict>> {
-        let mut bytes = [0u8; 16];
-        hex::decode_to_slice(token, &mut bytes)
-            .map_err(|_| PyValueError::new_err(format!("Invalid token format: {token}")))?;
+        let bytes = decode_token(token)?;
 
         match table {
             "log" => slf.lookup_in_table(slf.py(), &slf.log, &bytes),
@@ -346,29 +436,1047 @@ impl Tables {
         }
     }
 
+    /// Returns the annotations of an instance in temporal order, each with the timestamp of its sample.
+    fn instance_track<'py>(slf: PyRef<'py, Self>, instance_token: &str) -> PyResult<Vec<Bound<'py, PyDict>>> {
+        let ins = slf
+            .instance
+            .get(&decode_token(instance_token)?)
+            .ok_or_else(|| PyKeyError::new_err(instance_token.to_string()))?;
+        ins.anns
+            .iter()
+            .map(|tok| {
+                let ann = slf.sample_ann.get(tok).unwrap();
+                let dict = ann.to_py_dict(slf.py())?;
+                dict.set_item("timestamp", slf.sample.get(&ann.sample_token).unwrap().timestamp)?;
+                Ok(dict)
+            })
+            .collect()
+    }
+
+    /// Returns `(translations, rotations, timestamps)` arrays of shape `(T, 3)`, `(T, 4)` and `(T,)` per instance.
+    /// All instances are returned when `instance_tokens` is not given.
+    #[pyo3(signature = (instance_tokens=None))]
+    fn instance_tracks<'py>(
+        slf: PyRef<'py, Self>, instance_tokens: Option<Vec<String>>,
+    ) -> PyResult<Bound<'py, PyDict>> {
+        let instances: Vec<&Instance> = match &instance_tokens {
+            Some(tokens) => tokens
+                .iter()
+                .map(|tok| slf.instance.get(&decode_token(tok)?).ok_or_else(|| PyKeyError::new_err(tok.clone())))
+                .collect::<PyResult<_>>()?,
+            None => slf.instance.iter().collect(),
+        };
+
+        let tables = &*slf;
+        let tracks: Vec<_> = instances
+            .par_iter()
+            .map(|ins| {
+                let mut translations = Vec::with_capacity(ins.anns.len() * 3);
+                let mut rotations = Vec::with_capacity(ins.anns.len() * 4);
+                let mut timestamps = Vec::with_capacity(ins.anns.len());
+                for tok in &ins.anns {
+                    let ann = tables.sample_ann.get(tok).unwrap();
+                    translations.extend_from_slice(&ann.translation);
+                    rotations.extend_from_slice(&ann.rotation);
+                    timestamps.push(tables.sample.get(&ann.sample_token).unwrap().timestamp);
+                }
+                (ins.token, translations, rotations, timestamps)
+            })
+            .collect();
+
+        let dict = PyDict::new(slf.py());
+        for (token, translations, rotations, timestamps) in tracks {
+            let len = timestamps.len();
+            let translations = Array2::from_shape_vec((len, 3), translations).unwrap().into_pyarray(slf.py());
+            let rotations = Array2::from_shape_vec((len, 4), rotations).unwrap().into_pyarray(slf.py());
+            let timestamps = timestamps.into_pyarray(slf.py());
+            dict.set_item(hex::encode(token), (translations, rotations, timestamps))?;
+        }
+        Ok(dict)
+    }
+
+    /// Returns the tokens of all scenes recorded in a log.
+    fn scenes_in_log(&self, log_token: &str) -> PyResult<Vec<String>> {
+        lookup(&self.log, self.log_scenes_index(), log_token)
+    }
+
+    /// Returns the tokens of all scenes recorded at a location, e.g. `singapore-onenorth`.
+    fn scenes_in_location(&self, location: &str) -> Vec<String> {
+        self.location_scenes_index()
+            .get(location)
+            .map(|toks| toks.iter().map(hex::encode).collect())
+            .unwrap_or_default()
+    }
+
+    /// Returns the tokens of all samples in a scene, in temporal order.
+    fn samples_in_scene(&self, scene_token: &str) -> PyResult<Vec<String>> {
+        lookup(&self.scene, self.scene_samples_index(), scene_token)
+    }
+
+    /// Returns the tokens of the instances annotated in a sample.
+    fn instances_in_sample(&self, sample_token: &str) -> PyResult<Vec<String>> {
+        lookup(&self.sample, self.sample_instances_index(), sample_token)
+    }
+
+    /// Returns the tokens of all instances of a category.
+    fn instances_of_category(&self, category_token: &str) -> PyResult<Vec<String>> {
+        lookup(&self.category, self.category_instances_index(), category_token)
+    }
+
+    /// Returns the tokens of all sample data captured with a calibrated sensor.
+    fn sample_data_of_calibrated_sensor(&self, calibrated_sensor_token: &str) -> PyResult<Vec<String>> {
+        lookup(&self.calib, self.calib_sample_data_index(), calibrated_sensor_token)
+    }
+
+    /// Returns the tokens of all calibrations of a sensor.
+    fn calibrated_sensors_of_sensor(&self, sensor_token: &str) -> PyResult<Vec<String>> {
+        lookup(&self.sensor, self.sensor_calibs_index(), sensor_token)
+    }
+
+    /// Returns the tokens of all sample annotations having an attribute.
+    fn annotations_with_attribute(&self, attribute_token: &str) -> PyResult<Vec<String>> {
+        lookup(&self.attribute, self.attribute_anns_index(), attribute_token)
+    }
+
+    /// Returns the lidarseg record of a sample data, or `None` if it has none.
+    fn lidarseg_of_sample_data<'py>(
+        slf: PyRef<'py, Self>, sample_data_token: &str,
+    ) -> PyResult<Option<Bound<'py, PyDict>>> {
+        lookup_extension(
+            slf.py(),
+            &slf.sample_data,
+            slf.lidarseg.as_ref(),
+            slf.sample_data_lidarseg_index(),
+            "lidarseg",
+            sample_data_token,
+        )
+    }
+
+    /// Returns the panoptic record of a sample data, or `None` if it has none.
+    fn panoptic_of_sample_data<'py>(
+        slf: PyRef<'py, Self>, sample_data_token: &str,
+    ) -> PyResult<Option<Bound<'py, PyDict>>> {
+        lookup_extension(
+            slf.py(),
+            &slf.sample_data,
+            slf.panoptic.as_ref(),
+            slf.sample_data_panoptic_index(),
+            "panoptic",
+            sample_data_token,
+        )
+    }
+
+    /// The class mapping used for the `class_id` and `class_name` of sample annotations, if any.
+    #[getter]
+    fn class_mapping(&self) -> Option<ClassMapping> {
+        self.class_mapping.clone()
+    }
+
+    /// Returns a lookup table from lidarseg category indices to class ids, e.g. `table[labels]` remaps the labels of
+    /// a point cloud. Uses the class mapping of the tables if `mapping` is not given. Unmapped indices are set to
+    /// `fill`, which defaults to the largest `uint16`.
+    #[pyo3(signature = (mapping=None, fill=None))]
+    fn label_table<'py>(
+        &self, py: Python<'py>, mapping: Option<&Bound<'py, ClassMapping>>, fill: Option<u16>,
+    ) -> PyResult<Bound<'py, PyArray1<u16>>> {
+        let mapping = self.mapping_or_default(mapping)?;
+        let categories = self.category.iter().filter_map(|cat| cat.index.map(|index| (index, cat.name.as_ref())));
+        Ok(mapping.label_table(categories, fill.unwrap_or(u16::MAX)).into_pyarray(py))
+    }
+
+    /// Returns the tokens of all sample annotations in a category or any of its subcategories, e.g. `vehicle.bus`.
+    fn annotations_in_category(&self, category: &str) -> Vec<String> {
+        self.sample_ann
+            .data
+            .par_iter()
+            .filter(|ann| ann.category.is_a(category))
+            .map(|ann| hex::encode(ann.token))
+            .collect()
+    }
+
+    /// Returns the token of the `channel` sample data closest in time to `timestamp`, optionally within a scene.
+    #[pyo3(signature = (channel, timestamp, scene=None))]
+    fn nearest(&self, channel: &str, timestamp: u64, scene: Option<&str>) -> PyResult<Option<String>> {
+        let channel = parse_channel(channel)?;
+        let scene = self.scene_filter(scene)?;
+        let series = self.timeline().channel(channel, scene.as_ref());
+        Ok(series.and_then(|s| s.nearest(timestamp)).map(|(_, tok)| hex::encode(tok)))
+    }
+
+    /// Batch variant of `nearest` for many timestamps.
+    #[pyo3(signature = (channel, timestamps, scene=None))]
+    fn nearest_batch(&self, channel: &str, timestamps: Vec<u64>, scene: Option<&str>) -> PyResult<Vec<Option<String>>> {
+        let channel = parse_channel(channel)?;
+        let scene = self.scene_filter(scene)?;
+        let Some(series) = self.timeline().channel(channel, scene.as_ref()) else {
+            return Ok(vec![None; timestamps.len()]);
+        };
+        Ok(timestamps.par_iter().map(|&t| series.nearest(t).map(|(_, tok)| hex::encode(tok))).collect())
+    }
+
+    /// Returns the token of the sample closest in time to `timestamp`, optionally within a scene.
+    #[pyo3(signature = (timestamp, scene=None))]
+    fn nearest_sample(&self, timestamp: u64, scene: Option<&str>) -> PyResult<Option<String>> {
+        let scene = self.scene_filter(scene)?;
+        let series = self.timeline().samples(scene.as_ref());
+        Ok(series.and_then(|s| s.nearest(timestamp)).map(|(_, tok)| hex::encode(tok)))
+    }
+
+    /// Returns the sample data tokens per channel with `start <= timestamp <= end`, in temporal order.
+    /// All channels are queried when `channels` is not given.
+    #[pyo3(signature = (start, end, channels=None, scene=None))]
+    fn window(
+        &self, start: u64, end: u64, channels: Option<Vec<String>>, scene: Option<&str>,
+    ) -> PyResult<HashMap<String, Vec<String>>> {
+        let channels = parse_channels(channels)?;
+        let scene = self.scene_filter(scene)?;
+        Ok(self.window_by_channel(start, end, &channels, scene.as_ref()))
+    }
+
+    /// Batch variant of `window` for many `(start, end)` pairs.
+    #[pyo3(signature = (starts, ends, channels=None, scene=None))]
+    fn window_batch(
+        &self, starts: Vec<u64>, ends: Vec<u64>, channels: Option<Vec<String>>, scene: Option<&str>,
+    ) -> PyResult<Vec<HashMap<String, Vec<String>>>> {
+        if starts.len() != ends.len() {
+            return Err(PyValueError::new_err("starts and ends must have the same length"));
+        }
+        let channels = parse_channels(channels)?;
+        let scene = self.scene_filter(scene)?;
+        Ok(starts
+            .par_iter()
+            .zip(ends.par_iter())
+            .map(|(&start, &end)| self.window_by_channel(start, end, &channels, scene.as_ref()))
+            .collect())
+    }
+
+    /// Returns the 4x4 transform from the sensor frame to the ego frame of a sample data.
+    fn sensor_to_ego<'py>(&self, py: Python<'py>, sample_data_token: &str) -> PyResult<Bound<'py, PyArray2<f64>>> {
+        self.frame_transform(py, sample_data_token, "sensor", "ego")
+    }
+
+    /// Returns the 4x4 transform from the ego frame to the global frame at the time of a sample data.
+    fn ego_to_global<'py>(&self, py: Python<'py>, sample_data_token: &str) -> PyResult<Bound<'py, PyArray2<f64>>> {
+        self.frame_transform(py, sample_data_token, "ego", "global")
+    }
+
+    /// Returns the 4x4 transform from the sensor frame of a sample data to the global frame.
+    fn sensor_to_global<'py>(&self, py: Python<'py>, sample_data_token: &str) -> PyResult<Bound<'py, PyArray2<f64>>> {
+        self.frame_transform(py, sample_data_token, "sensor", "global")
+    }
+
+    /// Returns the 4x4 transform from the global frame to the sensor frame of a sample data.
+    fn global_to_sensor<'py>(&self, py: Python<'py>, sample_data_token: &str) -> PyResult<Bound<'py, PyArray2<f64>>> {
+        self.frame_transform(py, sample_data_token, "global", "sensor")
+    }
+
+    /// Returns the 4x4 transform between two of the `sensor`, `ego` and `global` frames of a sample data.
+    fn frame_transform<'py>(
+        &self, py: Python<'py>, sample_data_token: &str, source: &str, target: &str,
+    ) -> PyResult<Bound<'py, PyArray2<f64>>> {
+        let sd = find(&self.sample_data, sample_data_token)?;
+        let source: Frame = source.parse().map_err(PyValueError::new_err)?;
+        let target: Frame = target.parse().map_err(PyValueError::new_err)?;
+        Ok(matrix_to_pyarray(py, &self.sensor_pose(sd).between(source, target).matrix()))
+    }
+
+    /// Returns the boxes of sample annotations in the global frame, named after their categories.
+    fn annotation_boxes(&self, sample_annotation_tokens: Vec<String>) -> PyResult<Boxes> {
+        let boxes = sample_annotation_tokens
+            .iter()
+            .map(|token| Ok(self.annotation_box(find(&self.sample_ann, token)?)))
+            .collect::<PyResult<_>>()?;
+        Ok(Boxes { boxes })
+    }
+
+    /// Returns the boxes of all annotations of a sample in the global frame.
+    fn sample_boxes(&self, sample_token: &str) -> PyResult<Boxes> {
+        let sample = find(&self.sample, sample_token)?;
+        let boxes = sample.anns.iter().map(|tok| self.annotation_box(self.sample_ann.get(tok).unwrap())).collect();
+        Ok(Boxes { boxes })
+    }
+
+    /// Returns the boxes at the time of a sample data in the `global`, `ego` or `sensor` frame, interpolated for
+    /// sweeps. Boxes can be restricted to visibility tokens or names, e.g. `["3", "4"]`, and to categories including
+    /// their subcategories, e.g. `["vehicle"]`. Velocities are NaN unless computed for annotations, and always NaN for
+    /// interpolated boxes.
+    #[pyo3(signature = (sample_data_token, frame="global", visibilities=None, categories=None))]
+    fn get_boxes(
+        &self, sample_data_token: &str, frame: &str, visibilities: Option<Vec<String>>, categories: Option<Vec<String>>,
+    ) -> PyResult<Boxes> {
+        let sd = find(&self.sample_data, sample_data_token)?;
+        let frame: Frame = frame.parse().map_err(PyValueError::new_err)?;
+        let filter = BoxFilter::parse(visibilities, categories)?;
+        Ok(Boxes { boxes: self.sample_data_boxes(sd, frame, &filter) })
+    }
+
+    /// Batched version of `get_boxes`, computed in parallel.
+    #[pyo3(signature = (sample_data_tokens, frame="global", visibilities=None, categories=None))]
+    fn get_boxes_batch(
+        slf: PyRef<'_, Self>, sample_data_tokens: Vec<String>, frame: &str, visibilities: Option<Vec<String>>,
+        categories: Option<Vec<String>>,
+    ) -> PyResult<Vec<Boxes>> {
+        let sds = sample_data_tokens.iter().map(|tok| find(&slf.sample_data, tok)).collect::<PyResult<Vec<_>>>()?;
+        let frame: Frame = frame.parse().map_err(PyValueError::new_err)?;
+        let filter = BoxFilter::parse(visibilities, categories)?;
+        let tables = &*slf;
+        Ok(sds.par_iter().map(|sd| Boxes { boxes: tables.sample_data_boxes(sd, frame, &filter) }).collect())
+    }
+
+    /// Returns the velocity of an annotation in the `global` or `ego` frame, as `NuScenes.box_velocity`.
+    #[pyo3(signature = (sample_annotation_token, max_time_diff=MAX_TIME_DIFF, frame="global"))]
+    fn box_velocity<'py>(
+        &self, py: Python<'py>, sample_annotation_token: &str, max_time_diff: f64, frame: &str,
+    ) -> PyResult<Bound<'py, PyArray1<f32>>> {
+        let ann = find(&self.sample_ann, sample_annotation_token)?;
+        let frame = parse_velocity_frame(frame)?;
+        Ok(self.annotation_velocity(ann, frame, max_time_diff).to_vec().into_pyarray(py))
+    }
+
+    /// Returns the velocities of all sample annotations as an `(N, 3)` array, in the order of `sample_annotation`.
+    #[pyo3(signature = (frame="global", max_time_diff=MAX_TIME_DIFF))]
+    fn annotation_velocities<'py>(
+        slf: PyRef<'py, Self>, frame: &str, max_time_diff: f64,
+    ) -> PyResult<Bound<'py, PyArray2<f32>>> {
+        let frame = parse_velocity_frame(frame)?;
+        let tables = &*slf;
+        let velocities: Vec<_> = tables
+            .sample_ann
+            .data
+            .par_iter()
+            .flat_map_iter(|ann| tables.annotation_velocity(ann, frame, max_time_diff))
+            .collect();
+        Ok(Array2::from_shape_vec((tables.sample_ann.len(), 3), velocities).unwrap().into_pyarray(slf.py()))
+    }
+
+    /// Returns the ego poses of a scene or log token sorted by timestamp, as `(timestamps, translations, rotations)`.
+    fn ego_trajectory<'py>(&self, py: Python<'py>, token: &str) -> PyResult<Bound<'py, PyTuple>> {
+        let trajectory = self.ego_trajectory_of(token)?;
+        let translations: Vec<_> = trajectory.poses.iter().flat_map(|p| p.translation).collect();
+        let rotations: Vec<_> = trajectory.poses.iter().flat_map(|p| p.rotation.to_array()).collect();
+        let len = trajectory.poses.len();
+        (
+            trajectory.timestamps.to_vec().into_pyarray(py),
+            Array2::from_shape_vec((len, 3), translations).unwrap().into_pyarray(py),
+            Array2::from_shape_vec((len, 4), rotations).unwrap().into_pyarray(py),
+        )
+            .into_pyobject(py)
+    }
+
+    /// Interpolates the ego pose of a scene or log token at each timestamp, as `(translations, rotations)`. Timestamps
+    /// outside of the trajectory get NaN.
+    fn ego_poses_at<'py>(slf: PyRef<'py, Self>, token: &str, timestamps: Vec<u64>) -> PyResult<Bound<'py, PyTuple>> {
+        let py = slf.py();
+        let trajectory = slf.ego_trajectory_of(token)?;
+        let poses: Vec<_> = timestamps.par_iter().map(|t| trajectory.pose_at(*t)).collect();
+        let translations: Vec<_> = poses.iter().flat_map(|p| p.map_or([f64::NAN; 3], |p| p.translation)).collect();
+        let rotations: Vec<_> = poses.iter().flat_map(|p| p.map_or([f64::NAN; 4], |p| p.rotation.to_array())).collect();
+        (
+            Array2::from_shape_vec((poses.len(), 3), translations).unwrap().into_pyarray(py),
+            Array2::from_shape_vec((poses.len(), 4), rotations).unwrap().into_pyarray(py),
+        )
+            .into_pyobject(py)
+    }
+
+    /// Returns the `timestamps`, `speed`, `acceleration` and `yaw_rate` of the ego vehicle for a scene or log token,
+    /// at the given timestamps or at every ego pose. Derivatives are central differences over `window` seconds on
+    /// either side.
+    #[pyo3(signature = (token, timestamps=None, window=DERIVATIVE_WINDOW))]
+    fn ego_dynamics<'py>(
+        &self, py: Python<'py>, token: &str, timestamps: Option<Vec<u64>>, window: f64,
+    ) -> PyResult<Bound<'py, PyDict>> {
+        if window <= 0.0 {
+            return Err(PyValueError::new_err("window must be positive"));
+        }
+        let trajectory = self.ego_trajectory_of(token)?;
+        let timestamps = timestamps.unwrap_or_else(|| trajectory.timestamps.to_vec());
+        let dynamics = trajectory.dynamics(&timestamps, window);
+
+        let dict = PyDict::new(py);
+        dict.set_item("timestamps", timestamps.into_pyarray(py))?;
+        dict.set_item("speed", dynamics.speed.into_pyarray(py))?;
+        dict.set_item("acceleration", dynamics.acceleration.into_pyarray(py))?;
+        dict.set_item("yaw_rate", dynamics.yaw_rate.into_pyarray(py))?;
+        Ok(dict)
+    }
+
+    /// Projects `(N, 3)` points given in the `global`, `ego` or `sensor` frame into a camera sample data, returning
+    /// the `(N, 2)` pixel coordinates, the depths and whether each point is in the image and at least `min_depth`
+    /// meters in front of the camera.
+    #[pyo3(signature = (sample_data_token, points, frame="global", min_depth=1.0))]
+    fn project_points<'py>(
+        &self, py: Python<'py>, sample_data_token: &str, points: PyReadonlyArray2<'py, f64>, frame: &str,
+        min_depth: f64,
+    ) -> PyResult<Bound<'py, PyTuple>> {
+        let sd = find(&self.sample_data, sample_data_token)?;
+        let camera = self.camera(sd)?;
+        let frame: Frame = frame.parse().map_err(PyValueError::new_err)?;
+        let transform = self.sensor_pose(sd).between(frame, Frame::Sensor);
+        let points: Vec<_> = points_from_pyarray(&points)?.par_iter().map(|p| transform.apply(*p)).collect();
+
+        let projected = camera.project(&points);
+        let pixels: Vec<_> = projected.iter().flat_map(|(uv, _)| *uv).collect();
+        let depths: Vec<_> = projected.iter().map(|(_, depth)| *depth).collect();
+        let mask: Vec<_> = projected.iter().map(|(uv, depth)| *depth > min_depth && camera.in_image(*uv)).collect();
+        (
+            Array2::from_shape_vec((projected.len(), 2), pixels).unwrap().into_pyarray(py),
+            depths.into_pyarray(py),
+            mask.into_pyarray(py),
+        )
+            .into_pyobject(py)
+    }
+
+    /// Returns the boxes visible in a camera sample data according to `vis_level`, as a dict with the `boxes` in the
+    /// camera frame, the `(M, 8, 2)` pixel `corners` and the `(M, 4)` image `bboxes`, NaN if outside the image.
+    /// Boxes are interpolated for sweeps and filtered as in `get_boxes`.
+    #[pyo3(signature = (sample_data_token, vis_level=None, visibilities=None, categories=None))]
+    fn project_boxes<'py>(
+        &self, py: Python<'py>, sample_data_token: &str, vis_level: Option<&Bound<'py, PyAny>>,
+        visibilities: Option<Vec<String>>, categories: Option<Vec<String>>,
+    ) -> PyResult<Bound<'py, PyDict>> {
+        let sd = find(&self.sample_data, sample_data_token)?;
+        let vis = vis_level.map(BoxVisibility::extract).transpose()?.unwrap_or_default();
+        let filter = BoxFilter::parse(visibilities, categories)?;
+        let projected = self.projected_boxes(sd, vis, &filter)?;
+        projected_boxes_to_dict(py, projected)
+    }
+
+    /// Projects the boxes of a sample into each of its cameras in parallel, as `project_boxes`, keyed by channel.
+    #[pyo3(signature = (sample_token, vis_level=None, visibilities=None, categories=None))]
+    fn project_sample_boxes<'py>(
+        slf: PyRef<'py, Self>, sample_token: &str, vis_level: Option<&Bound<'py, PyAny>>,
+        visibilities: Option<Vec<String>>, categories: Option<Vec<String>>,
+    ) -> PyResult<Bound<'py, PyDict>> {
+        let py = slf.py();
+        let sample = find(&slf.sample, sample_token)?;
+        let vis = vis_level.map(BoxVisibility::extract).transpose()?.unwrap_or_default();
+        let filter = BoxFilter::parse(visibilities, categories)?;
+        let tables = &*slf;
+        let cameras: Vec<_> = sample
+            .data
+            .values()
+            .filter_map(|tok| tables.sample_data.get(tok))
+            .filter(|sd| sd.modality == SensorModality::Camera)
+            .collect();
+        let projected: Vec<_> = cameras
+            .par_iter()
+            .map(|sd| Ok((sd.channel, tables.projected_boxes(sd, vis, &filter)?)))
+            .collect::<PyResult<_>>()?;
+
+        let dict = PyDict::new(py);
+        for (channel, projected) in projected {
+            dict.set_item(channel.to_string(), projected_boxes_to_dict(py, projected)?)?;
+        }
+        Ok(dict)
+    }
+
+    /// Projects the points of a lidar sample data into a camera sample data, as `map_pointcloud_to_image`. Returns a
+    /// dict with the `(H, W)` sparse `depth` image, 0 where no point falls and the nearest depth where several do, and
+    /// per point the `(N, 2)` `pixels`, the `depths` and the `mask` of points in the image and farther than `min_dist`.
+    #[pyo3(signature = (lidar_token, camera_token, min_dist=1.0))]
+    fn depth_map<'py>(
+        &self, py: Python<'py>, lidar_token: &str, camera_token: &str, min_dist: f64,
+    ) -> PyResult<Bound<'py, PyDict>> {
+        let lidar = find(&self.sample_data, lidar_token)?;
+        let cam = find(&self.sample_data, camera_token)?;
+        depth_projection_to_dict(py, self.depth_projection(lidar, cam, min_dist)?)
+    }
+
+    /// Batched version of `depth_map` for the LIDAR_TOP points of a sample, projected into its cameras among
+    /// `channels` in parallel. Returns a dict from channel to the dict of `depth_map`.
+    #[pyo3(signature = (sample_token, channels=None, min_dist=1.0))]
+    fn sample_depth_maps<'py>(
+        slf: PyRef<'py, Self>, sample_token: &str, channels: Option<Vec<String>>, min_dist: f64,
+    ) -> PyResult<Bound<'py, PyDict>> {
+        let py = slf.py();
+        let sample = find(&slf.sample, sample_token)?;
+        let channels = parse_channels(channels)?;
+        let tables = &*slf;
+        let (projections, _) = py.detach(|| tables.sample_depth_projections(sample, &channels, min_dist))?;
+        let dict = PyDict::new(py);
+        for (channel, projection) in projections {
+            dict.set_item(channel.to_string(), depth_projection_to_dict(py, projection)?)?;
+        }
+        Ok(dict)
+    }
+
+    /// Paints the LIDAR_TOP points of a sample with the pixels of its cameras among `channels`, taking the first
+    /// camera in which each point is visible. Returns a dict with the camera `channels` and per point the `camera`
+    /// index in them, the `(N, 2)` `pixels` and the row-major `pixel_index`, -1 or NaN if not visible in any camera.
+    #[pyo3(signature = (sample_token, channels=None, min_dist=1.0))]
+    fn paint_points<'py>(
+        slf: PyRef<'py, Self>, sample_token: &str, channels: Option<Vec<String>>, min_dist: f64,
+    ) -> PyResult<Bound<'py, PyDict>> {
+        let py = slf.py();
+        let sample = find(&slf.sample, sample_token)?;
+        let channels = parse_channels(channels)?;
+        let tables = &*slf;
+        let painted = py.detach(|| {
+            let (projections, num_points) = tables.sample_depth_projections(sample, &channels, min_dist)?;
+            PyResult::Ok(PaintedPoints::new(&projections, num_points))
+        })?;
+        let num_points = painted.camera.len();
+        let dict = PyDict::new(py);
+        dict.set_item("channels", painted.channels.iter().map(|c| c.to_string()).collect::<Vec<_>>())?;
+        dict.set_item("camera", painted.camera.into_pyarray(py))?;
+        dict.set_item(
+            "pixels",
+            Array2::from_shape_vec((num_points, 2), painted.pixels.concat()).unwrap().into_pyarray(py),
+        )?;
+        dict.set_item("pixel_index", painted.pixel_index.into_pyarray(py))?;
+        Ok(dict)
+    }
+
+    /// Rasterizes the annotation boxes of a sample on an ego-centric BEV grid covering `x_range` and `y_range` in
+    /// meters, rotated by `rotation` radians, with cells of `resolution` meters. Columns go along x and rows along y.
+    /// Boxes are drawn as filled polygons in the order of the sample, filtered as in `get_boxes` and by the class
+    /// mapping, which defaults to that of the tables. Returns a dict with the `(H, W)` `classes`, `fill` outside of
+    /// boxes, `instances` indexing `annotation_tokens` and `instance_tokens`, -1 outside of boxes, and `headings` and
+    /// `(H, W, 2)` `velocities` in the grid frame, NaN outside of boxes.
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3(signature = (
+        sample_token, x_range=(-50.0, 50.0), y_range=(-50.0, 50.0), resolution=0.5, rotation=0.0, mapping=None,
+        fill=None, visibilities=None, categories=None
+    ))]
+    fn rasterize_boxes<'py>(
+        &self, py: Python<'py>, sample_token: &str, x_range: (f64, f64), y_range: (f64, f64), resolution: f64,
+        rotation: f64, mapping: Option<&Bound<'py, ClassMapping>>, fill: Option<u16>,
+        visibilities: Option<Vec<String>>, categories: Option<Vec<String>>,
+    ) -> PyResult<Bound<'py, PyDict>> {
+        let sample = find(&self.sample, sample_token)?;
+        let grid = BevGrid::new(x_range.into(), y_range.into(), resolution, rotation)?;
+        let mapping = self.mapping_or_default(mapping)?;
+        let filter = BoxFilter::parse(visibilities, categories)?;
+        let raster = self.box_raster(sample, &grid, mapping, &filter, fill.unwrap_or(u16::MAX))?;
+
+        let shape = (grid.height, grid.width);
+        let dict = PyDict::new(py);
+        dict.set_item("classes", Array2::from_shape_vec(shape, raster.classes).unwrap().into_pyarray(py))?;
+        dict.set_item("instances", Array2::from_shape_vec(shape, raster.instances).unwrap().into_pyarray(py))?;
+        dict.set_item("headings", Array2::from_shape_vec(shape, raster.headings).unwrap().into_pyarray(py))?;
+        let velocities = Array3::from_shape_vec((grid.height, grid.width, 2), raster.velocities.concat()).unwrap();
+        dict.set_item("velocities", velocities.into_pyarray(py))?;
+        dict.set_item("annotation_tokens", raster.annotations.iter().map(hex::encode).collect::<Vec<_>>())?;
+        dict.set_item("instance_tokens", raster.instance_tokens.iter().map(hex::encode).collect::<Vec<_>>())?;
+        Ok(dict)
+    }
+
+    /// Bins the LIDAR_TOP points of a sample on the BEV grid of `rasterize_boxes`, keeping those with a height in the
+    /// ego frame within `z_range`. Returns a dict with the `(H, W)` point `counts`, and the `max_height` and mean
+    /// `intensity` of the points of each cell, NaN for empty cells.
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3(signature = (
+        sample_token, x_range=(-50.0, 50.0), y_range=(-50.0, 50.0), resolution=0.5, rotation=0.0, z_range=None
+    ))]
+    fn rasterize_lidar<'py>(
+        &self, py: Python<'py>, sample_token: &str, x_range: (f64, f64), y_range: (f64, f64), resolution: f64,
+        rotation: f64, z_range: Option<(f64, f64)>,
+    ) -> PyResult<Bound<'py, PyDict>> {
+        let sample = find(&self.sample, sample_token)?;
+        let grid = BevGrid::new(x_range.into(), y_range.into(), resolution, rotation)?;
+        let z_range = z_range.map_or([f64::NEG_INFINITY, f64::INFINITY], Into::into);
+        let raster = py.detach(|| self.lidar_raster(sample, &grid, z_range))?;
+
+        let shape = (grid.height, grid.width);
+        let dict = PyDict::new(py);
+        dict.set_item("counts", Array2::from_shape_vec(shape, raster.counts).unwrap().into_pyarray(py))?;
+        dict.set_item("max_height", Array2::from_shape_vec(shape, raster.max_height).unwrap().into_pyarray(py))?;
+        dict.set_item("intensity", Array2::from_shape_vec(shape, raster.intensity).unwrap().into_pyarray(py))?;
+        Ok(dict)
+    }
+
+    /// Returns the vector map of the map expansion at the location of a log, loaded on first use and shared by all
+    /// logs at that location.
+    fn vector_map(&self, py: Python<'_>, log_token: &str) -> PyResult<VectorMap> {
+        let log = find(&self.log, log_token)?;
+        Ok(VectorMap { map: self.location_vector_map(py, &log.map_location)? })
+    }
+
+    /// Crops the map mask of the log of a sample data around its ego pose, as an `(H, W)` uint8 array covering `size`
+    /// meters ahead and to the left, sampled every `resolution` meters, 0.1 by default. The heading of the ego vehicle
+    /// points to the first row and its left to the first column. Optionally dilated by `dilation` meters.
+    #[pyo3(signature = (sample_data_token, size=(100.0, 100.0), resolution=None, dilation=0.0))]
+    fn ego_map_patch<'py>(
+        &self, py: Python<'py>, sample_data_token: &str, size: (f64, f64), resolution: Option<f64>, dilation: f64,
+    ) -> PyResult<Bound<'py, PyArray2<u8>>> {
+        let sd = find(&self.sample_data, sample_data_token)?;
+        let (mask, center, yaw) = self.ego_map_mask(sd);
+        let resolution = resolution.unwrap_or(NATIVE_RESOLUTION);
+        let (data, rows, cols) = py.detach(|| mask.patch(center, yaw, size, resolution, dilation))?;
+        Ok(Array2::from_shape_vec((rows, cols), data).unwrap().into_pyarray(py))
+    }
+
+    /// Rasterizes the vector map of the log of a sample data around its ego pose, as `VectorMap.rasterize`, in a patch
+    /// covering `size` meters ahead and to the left, sampled every `resolution` meters. The heading of the ego vehicle
+    /// points to the first row and its left to the first column, as in `ego_map_patch`.
+    #[pyo3(signature = (sample_data_token, size=(100.0, 100.0), resolution=NATIVE_RESOLUTION, layer_names=None))]
+    fn ego_map_raster<'py>(
+        &self, py: Python<'py>, sample_data_token: &str, size: (f64, f64), resolution: f64,
+        layer_names: Option<Vec<String>>,
+    ) -> PyResult<Bound<'py, PyDict>> {
+        let sd = find(&self.sample_data, sample_data_token)?;
+        let pose = self.ego_pose.get(&sd.ego_pose_token).unwrap();
+        let center = [pose.translation[0] as f64, pose.translation[1] as f64];
+        let yaw = Quaternion::from_f32(pose.rotation).yaw();
+        self.heading_map_raster(py, &sd.sample_token, center, yaw, size, resolution, layer_names)
+    }
+
+    /// Rasterizes the vector map around a sample annotation as `ego_map_raster`, aligned with the heading of its box.
+    #[pyo3(signature = (sample_annotation_token, size=(100.0, 100.0), resolution=NATIVE_RESOLUTION, layer_names=None))]
+    fn annotation_map_raster<'py>(
+        &self, py: Python<'py>, sample_annotation_token: &str, size: (f64, f64), resolution: f64,
+        layer_names: Option<Vec<String>>,
+    ) -> PyResult<Bound<'py, PyDict>> {
+        let ann = find(&self.sample_ann, sample_annotation_token)?;
+        let center = [ann.translation[0] as f64, ann.translation[1] as f64];
+        let yaw = Quaternion::from_f32(ann.rotation).yaw();
+        self.heading_map_raster(py, &ann.sample_token, center, yaw, size, resolution, layer_names)
+    }
+
+    /// Loads the `.pcd.bin` file of a lidar sample data as an `(N, 5)` float32 array of x, y, z, intensity and ring
+    /// index. The array views a private memory mapping of the file, without copies.
+    fn load_lidar<'py>(&self, py: Python<'py>, sample_data_token: &str) -> PyResult<Bound<'py, PyArray2<f32>>> {
+        let sd = find(&self.sample_data, sample_data_token)?;
+        self.lidar_points(sd, false)?.into_pyarray(py)
+    }
+
+    /// Batched version of `load_lidar`, reading the files in parallel.
+    fn load_lidar_batch<'py>(
+        slf: PyRef<'py, Self>, sample_data_tokens: Vec<String>,
+    ) -> PyResult<Vec<Bound<'py, PyArray2<f32>>>> {
+        let py = slf.py();
+        let sds = sample_data_tokens.iter().map(|tok| find(&slf.sample_data, tok)).collect::<PyResult<Vec<_>>>()?;
+        let tables = &*slf;
+        let points: Vec<LidarPoints> =
+            py.detach(|| sds.par_iter().map(|sd| tables.lidar_points(sd, true)).collect::<PyResult<_>>())?;
+        points.into_iter().map(|p| p.into_pyarray(py)).collect()
+    }
+
+    /// Loads the `.pcd` file of a radar sample data, keeping the returns whose states are in the given filters, which
+    /// default to those of `RadarPointCloud`. Positions and compensated velocities are in the `sensor`, `ego` or
+    /// `global` frame. Returns a structured array with the fields of the file, or an `(N, 18)` float32 array as
+    /// `RadarPointCloud.points.T` if not `structured`.
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3(signature = (
+        sample_data_token, invalid_states=None, dynprop_states=None, ambig_states=None, frame="sensor", structured=true
+    ))]
+    fn load_radar<'py>(
+        &self, py: Python<'py>, sample_data_token: &str, invalid_states: Option<Vec<i64>>,
+        dynprop_states: Option<Vec<i64>>, ambig_states: Option<Vec<i64>>, frame: &str, structured: bool,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let sd = find(&self.sample_data, sample_data_token)?;
+        let filters = RadarFilters::new(invalid_states, dynprop_states, ambig_states);
+        let frame: Frame = frame.parse().map_err(PyValueError::new_err)?;
+        radar_to_py(py, self.radar_points(sd, &filters, frame)?, structured)
+    }
+
+    /// Batched version of `load_radar`, reading the files in parallel.
+    #[pyo3(signature = (
+        sample_data_tokens, invalid_states=None, dynprop_states=None, ambig_states=None, frame="sensor", structured=true
+    ))]
+    fn load_radar_batch<'py>(
+        slf: PyRef<'py, Self>, sample_data_tokens: Vec<String>, invalid_states: Option<Vec<i64>>,
+        dynprop_states: Option<Vec<i64>>, ambig_states: Option<Vec<i64>>, frame: &str, structured: bool,
+    ) -> PyResult<Vec<Bound<'py, PyAny>>> {
+        let py = slf.py();
+        let sds = sample_data_tokens.iter().map(|tok| find(&slf.sample_data, tok)).collect::<PyResult<Vec<_>>>()?;
+        let filters = RadarFilters::new(invalid_states, dynprop_states, ambig_states);
+        let frame: Frame = frame.parse().map_err(PyValueError::new_err)?;
+        let tables = &*slf;
+        let points: Vec<RadarPoints> =
+            py.detach(|| sds.par_iter().map(|sd| tables.radar_points(sd, &filters, frame)).collect::<PyResult<_>>())?;
+        points.into_iter().map(|p| radar_to_py(py, p, structured)).collect()
+    }
+
+    /// Aggregates `nsweeps` lidar or radar sweeps of `channel` up to the keyframe of a sample into the frame of the
+    /// `ref_channel` keyframe, as `PointCloud.from_file_multisweep`. Returns an `(N, C + 1)` float32 array of the
+    /// point fields, x, y, z and intensity for lidar and the 18 fields for radar, followed by the time lag in seconds.
+    /// Radar returns are filtered as in `load_radar`.
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3(signature = (
+        sample_token, channel, ref_channel="LIDAR_TOP", nsweeps=5, min_distance=1.0, invalid_states=None,
+        dynprop_states=None, ambig_states=None
+    ))]
+    fn load_multisweep<'py>(
+        &self, py: Python<'py>, sample_token: &str, channel: &str, ref_channel: &str, nsweeps: usize,
+        min_distance: f32, invalid_states: Option<Vec<i64>>, dynprop_states: Option<Vec<i64>>,
+        ambig_states: Option<Vec<i64>>,
+    ) -> PyResult<Bound<'py, PyArray2<f32>>> {
+        let sample = find(&self.sample, sample_token)?;
+        let (channel, ref_channel) = (parse_channel(channel)?, parse_channel(ref_channel)?);
+        let filters = RadarFilters::new(invalid_states, dynprop_states, ambig_states);
+        let sweeps = self.multisweep(sample, channel, ref_channel, nsweeps, min_distance, &filters)?;
+        Ok(sweeps_to_pyarray(py, sweeps))
+    }
+
+    /// Voxelizes the LIDAR_TOP points of a sample aggregated over `nsweeps` sweeps as `load_multisweep`, with columns
+    /// x, y, z, intensity and time lag. See `voxelize` for the grid and the returned arrays.
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3(signature = (
+        sample_token, point_cloud_range, voxel_size, max_points=32, max_voxels=20000, nsweeps=1, min_distance=1.0
+    ))]
+    fn voxelize_lidar<'py>(
+        &self, py: Python<'py>, sample_token: &str, point_cloud_range: [f32; 6], voxel_size: [f32; 3],
+        max_points: usize, max_voxels: usize, nsweeps: usize, min_distance: f32,
+    ) -> PyResult<Bound<'py, PyTuple>> {
+        let sample = find(&self.sample, sample_token)?;
+        // Lidar sweeps have the fields of `LIDAR_SWEEP_FIELDS` and the time lag.
+        let grid = VoxelGrid::new(point_cloud_range, voxel_size, max_points, max_voxels, LIDAR_SWEEP_FIELDS + 1)?;
+        let voxels = py.detach(|| {
+            let filters = RadarFilters::default();
+            let sweeps = self.multisweep(
+                sample,
+                SensorChannel::LidarTop,
+                SensorChannel::LidarTop,
+                nsweeps,
+                min_distance,
+                &filters,
+            )?;
+            PyResult::Ok(grid.voxelize(&sweeps.data))
+        })?;
+        voxels.into_pytuple(py, max_points)
+    }
+
+    /// Batched version of `load_multisweep` over many samples, computed in parallel.
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3(signature = (
+        sample_tokens, channel, ref_channel="LIDAR_TOP", nsweeps=5, min_distance=1.0, invalid_states=None,
+        dynprop_states=None, ambig_states=None
+    ))]
+    fn load_multisweep_batch<'py>(
+        slf: PyRef<'py, Self>, sample_tokens: Vec<String>, channel: &str, ref_channel: &str, nsweeps: usize,
+        min_distance: f32, invalid_states: Option<Vec<i64>>, dynprop_states: Option<Vec<i64>>,
+        ambig_states: Option<Vec<i64>>,
+    ) -> PyResult<Vec<Bound<'py, PyArray2<f32>>>> {
+        let py = slf.py();
+        let samples = sample_tokens.iter().map(|tok| find(&slf.sample, tok)).collect::<PyResult<Vec<_>>>()?;
+        let (channel, ref_channel) = (parse_channel(channel)?, parse_channel(ref_channel)?);
+        let filters = RadarFilters::new(invalid_states, dynprop_states, ambig_states);
+        let tables = &*slf;
+        let sweeps: Vec<_> = py.detach(|| {
+            samples
+                .par_iter()
+                .map(|sample| tables.multisweep(sample, channel, ref_channel, nsweeps, min_distance, &filters))
+                .collect::<PyResult<_>>()
+        })?;
+        Ok(sweeps.into_iter().map(|s| sweeps_to_pyarray(py, s)).collect())
+    }
+
+    /// Loads the lidarseg labels of a lidar sample data as a uint8 array of category indices, one per point. With
+    /// `verify`, checks that the count matches the points of its `.pcd.bin` file.
+    #[pyo3(signature = (sample_data_token, verify=true))]
+    fn load_lidarseg<'py>(
+        &self, py: Python<'py>, sample_data_token: &str, verify: bool,
+    ) -> PyResult<Bound<'py, PyArray1<u8>>> {
+        let sd = find(&self.sample_data, sample_data_token)?;
+        Ok(self.lidarseg_labels(sd, verify)?.into_pyarray(py))
+    }
+
+    /// Loads the panoptic labels of a lidar sample data as a uint16 array of `category_index * 1000 + instance_id`,
+    /// one per point. With `decode`, returns a uint8 array of category indices and a uint16 array of instance ids
+    /// instead, as `panoptic_to_lidarseg`. With `verify`, checks that the count matches the points of its `.pcd.bin`
+    /// file.
+    #[pyo3(signature = (sample_data_token, decode=false, verify=true))]
+    fn load_panoptic<'py>(
+        &self, py: Python<'py>, sample_data_token: &str, decode: bool, verify: bool,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let sd = find(&self.sample_data, sample_data_token)?;
+        let labels = self.panoptic_labels(sd, verify)?;
+        if !decode {
+            return labels.into_pyarray(py).into_bound_py_any(py);
+        }
+        let (semantic, instance) = decode_panoptic(&labels);
+        (semantic.into_pyarray(py), instance.into_pyarray(py)).into_bound_py_any(py)
+    }
+
+    /// Labels the points of a lidar sample data with the annotation boxes containing them, as `points_in_box` with
+    /// boxes scaled by `wlh_factor`, for releases without lidarseg. A point inside several boxes goes to the one with
+    /// the nearest center. Returns a dict with the `box_index` of each point, -1 outside of all boxes, and per box the
+    /// `annotation_tokens`, `instance_tokens` and `category_names`, the `num_lidar_pts` regenerated from the points
+    /// and the `stored_num_lidar_pts` of the annotations to validate them against.
+    #[pyo3(signature = (sample_data_token, wlh_factor=1.0))]
+    fn box_point_labels<'py>(
+        slf: PyRef<'py, Self>, sample_data_token: &str, wlh_factor: f64,
+    ) -> PyResult<Bound<'py, PyDict>> {
+        let py = slf.py();
+        let sd = find(&slf.sample_data, sample_data_token)?;
+        let tables = &*slf;
+        let labels = py.detach(|| tables.label_points_by_boxes(sd, wlh_factor))?;
+
+        let anns: Vec<_> = labels.annotations.iter().map(|tok| slf.sample_ann.get(tok).unwrap()).collect();
+        let dict = PyDict::new(py);
+        dict.set_item("box_index", labels.box_index.into_pyarray(py))?;
+        dict.set_item("annotation_tokens", anns.iter().map(|ann| hex::encode(ann.token)).collect::<Vec<_>>())?;
+        dict.set_item("instance_tokens", anns.iter().map(|ann| hex::encode(ann.instance_token)).collect::<Vec<_>>())?;
+        dict.set_item("category_names", anns.iter().map(|ann| ann.category.as_str()).collect::<Vec<_>>())?;
+        dict.set_item("num_lidar_pts", labels.num_lidar_pts.into_pyarray(py))?;
+        dict.set_item(
+            "stored_num_lidar_pts",
+            anns.iter().map(|ann| ann.num_lidar_pts).collect::<Vec<_>>().into_pyarray(py),
+        )?;
+        Ok(dict)
+    }
+
+    /// Counts the points of each lidarseg category over all point clouds labelled by `gt_from`, which is `lidarseg` or
+    /// `panoptic`, reading the label files in parallel. Optionally restricted to the scenes of a split of
+    /// `create_splits_scenes` and to a list of scene names or tokens. Returns a dict with the category `names` by index,
+    /// the total `counts`, and the `scene_counts` and `sample_counts` histograms of `scene_tokens` and `sample_tokens`.
+    #[pyo3(signature = (gt_from="lidarseg", split=None, scenes=None))]
+    fn lidarseg_stats<'py>(
+        slf: PyRef<'py, Self>, gt_from: &str, split: Option<&str>, scenes: Option<Vec<String>>,
+    ) -> PyResult<Bound<'py, PyDict>> {
+        let py = slf.py();
+        let source: LabelSource = gt_from.parse().map_err(PyValueError::new_err)?;
+        let scenes = slf.scene_set(py, split, scenes)?;
+        let tables = &*slf;
+        let histograms = py.detach(|| tables.semantic_histograms(source, scenes.as_ref()))?;
+
+        let num_classes = slf.lidarseg_class_count();
+        let to_array = |rows: Vec<Vec<u64>>| {
+            Array2::from_shape_vec((rows.len(), num_classes), rows.concat()).unwrap().into_pyarray(py)
+        };
+        let (scene_tokens, scene_counts) = histograms.per_scene(num_classes);
+        let dict = PyDict::new(py);
+        dict.set_item("names", slf.lidarseg_class_names())?;
+        dict.set_item("counts", histograms.total(num_classes).into_pyarray(py))?;
+        dict.set_item("scene_tokens", scene_tokens.iter().map(hex::encode).collect::<Vec<_>>())?;
+        dict.set_item("scene_counts", to_array(scene_counts))?;
+        dict.set_item("sample_tokens", histograms.samples.iter().map(hex::encode).collect::<Vec<_>>())?;
+        dict.set_item("sample_counts", to_array(histograms.counts))?;
+        Ok(dict)
+    }
+
+    /// Gathers the instance statistics of nuScenes-panoptic as `get_panoptic_instances_stats` with `get_hist`, reading
+    /// the label files in parallel. Optionally restricted to the scenes of a split of `create_splits_scenes` and to a
+    /// list of scene names or tokens.
+    #[pyo3(signature = (split=None, scenes=None))]
+    fn panoptic_stats<'py>(
+        slf: PyRef<'py, Self>, split: Option<&str>, scenes: Option<Vec<String>>,
+    ) -> PyResult<Bound<'py, PyDict>> {
+        let py = slf.py();
+        let scenes = slf.scene_set(py, split, scenes)?;
+        let tables = &*slf;
+        let stats = py.detach(|| tables.panoptic_instance_stats(scenes.as_ref()))?;
+
+        let names = slf.lidarseg_class_names();
+        let per_category = PyDict::new(py);
+        for (category, instances) in stats.categories.into_iter().enumerate().skip(1) {
+            let Some(name) = names.get(category) else { continue };
+            let dict = PyDict::new(py);
+            dict.set_item("num_instances", instances.frames_per_instance.len())?;
+            dict.set_item("num_frames_per_instance", mean_std(&instances.frames_per_instance))?;
+            dict.set_item("num_points_per_instance", mean_std(&instances.points_per_instance))?;
+            dict.set_item("num_frames_per_instance_count", instances.frames_per_instance.into_pyarray(py))?;
+            dict.set_item("num_points_per_instance_count", instances.points_per_instance.into_pyarray(py))?;
+            per_category.set_item(name, dict)?;
+        }
+        let num_instances: usize = per_category
+            .values()
+            .iter()
+            .map(|d| d.get_item("num_instances")?.extract::<usize>())
+            .sum::<PyResult<_>>()?;
+
+        let hist = unique_counts(&stats.instances_per_frame);
+        let per_frame = PyDict::new(py);
+        per_frame.set_item("per_frame_num_instances", mean_std(&stats.instances_per_frame))?;
+        per_frame.set_item(
+            "per_frame_num_instances_hist",
+            Array2::from_shape_vec((hist.len(), 2), hist.concat()).unwrap().into_pyarray(py),
+        )?;
+
+        let dict = PyDict::new(py);
+        dict.set_item("num_instances", num_instances)?;
+        dict.set_item("num_sample_annotations", stats.instances_per_frame.iter().map(|&n| n as i64).sum::<i64>())?;
+        dict.set_item("per_frame_panoptic_stats", per_frame)?;
+        dict.set_item("per_category_panoptic_stats", per_category)?;
+        Ok(dict)
+    }
+
     fn __getstate__(slf: PyRef<'_, Self>) -> PyResult<Bound<'_, PyAny>> {
         let state = PyDict::new(slf.py());
         state.set_item("version", slf.version.clone())?;
         state.set_item("dataroot", slf.dataroot.clone())?;
+        state.set_item("annotation_fields", slf.annotation_fields.names())?;
+        state.set_item("class_mapping", slf.class_mapping.clone())?;
         state.into_bound_py_any(slf.py())
     }
 
     fn __setstate__(mut slf: PyRefMut<'_, Self>, state: Bound<'_, PyDict>) -> PyResult<()> {
         let version: String = state.get_item("version")?.unwrap().extract()?;
         let dataroot: String = state.get_item("dataroot")?.unwrap().extract()?;
-        let new_instance = Self::new(&version, &dataroot)?;
+        let annotation_fields: Option<Vec<String>> =
+            state.get_item("annotation_fields")?.map(|f| f.extract()).transpose()?;
+        let class_mapping = state.get_item("class_mapping")?.filter(|m| !m.is_none());
+        let new_instance = Self::new(&version, &dataroot, annotation_fields, class_mapping.as_ref())?;
         *slf = new_instance;
         Ok(())
     }
 
-    fn __reduce__(slf: PyRef<'_, Self>) -> PyResult<(Bound<'_, PyAny>, (String, String))> {
+    fn __reduce__(slf: PyRef<'_, Self>) -> PyResult<(Bound<'_, PyAny>, Bound<'_, PyTuple>)> {
         let cls = slf.py().get_type::<Tables>();
         let cls = cls.into_bound_py_any(slf.py())?;
-        Ok((cls, (slf.version.clone(), slf.dataroot.clone())))
+        let args =
+            (slf.version.clone(), slf.dataroot.clone(), slf.annotation_fields.names(), slf.class_mapping.clone())
+                .into_pyobject(slf.py())?;
+        Ok((cls, args))
+    }
+}
+
+/// Decodes a 32-character hex token as passed from Python.
+pub(crate) fn decode_token(token: &str) -> PyResult<[u8; 16]> {
+    let mut bytes = [0u8; 16];
+    hex::decode_to_slice(token, &mut bytes)
+        .map_err(|_| PyValueError::new_err(format!("Invalid token format: {token}")))?;
+    Ok(bytes)
+}
+
+/// Looks up a record by its hex token, raising a `KeyError` if it does not exist.
+pub(crate) fn find<'t, T: AsRefToken>(table: &'t Table<T>, token: &str) -> PyResult<&'t T> {
+    table.get(&decode_token(token)?).ok_or_else(|| PyKeyError::new_err(token.to_string()))
+}
+
+fn projected_boxes_to_dict(py: Python<'_>, projected: ProjectedBoxes) -> PyResult<Bound<'_, PyDict>> {
+    let len = projected.boxes.len();
+    let corners: Vec<_> = projected.corners.iter().flatten().flatten().copied().collect();
+    let bboxes: Vec<_> = projected.bboxes.iter().flatten().copied().collect();
+    let dict = PyDict::new(py);
+    dict.set_item("corners", Array3::from_shape_vec((len, 8, 2), corners).unwrap().into_pyarray(py))?;
+    dict.set_item("bboxes", Array2::from_shape_vec((len, 4), bboxes).unwrap().into_pyarray(py))?;
+    dict.set_item("boxes", Boxes { boxes: projected.boxes })?;
+    Ok(dict)
+}
+
+fn depth_projection_to_dict(py: Python<'_>, projection: DepthProjection) -> PyResult<Bound<'_, PyDict>> {
+    let (width, height, num_points) = (projection.width, projection.height, projection.depths.len());
+    let dict = PyDict::new(py);
+    dict.set_item("depth", Array2::from_shape_vec((height, width), projection.depth_map()).unwrap().into_pyarray(py))?;
+    dict.set_item(
+        "pixels",
+        Array2::from_shape_vec((num_points, 2), projection.pixels.concat()).unwrap().into_pyarray(py),
+    )?;
+    dict.set_item("depths", projection.depths.into_pyarray(py))?;
+    dict.set_item("mask", projection.mask.into_pyarray(py))?;
+    Ok(dict)
+}
+
+fn sweeps_to_pyarray(py: Python<'_>, sweeps: Sweeps) -> Bound<'_, PyArray2<f32>> {
+    let shape = (sweeps.len(), sweeps.columns);
+    Array2::from_shape_vec(shape, sweeps.data).unwrap().into_pyarray(py)
+}
+
+fn radar_to_py(py: Python<'_>, points: RadarPoints, structured: bool) -> PyResult<Bound<'_, PyAny>> {
+    if structured {
+        return points.into_structured(py);
+    }
+    let shape = (points.len(), points.header.fields.len());
+    Ok(Array2::from_shape_vec(shape, points.to_matrix()).unwrap().into_pyarray(py).into_any())
+}
+
+/// Parses the frame of annotation velocities, which are only defined in the global and ego frames.
+fn parse_velocity_frame(frame: &str) -> PyResult<Frame> {
+    match frame.parse().map_err(PyValueError::new_err)? {
+        Frame::Sensor => Err(PyValueError::new_err("Velocities are only available in the 'global' and 'ego' frames")),
+        frame => Ok(frame),
+    }
+}
+
+/// Parses a sensor channel name such as `LIDAR_TOP`.
+pub(crate) fn parse_channel(channel: &str) -> PyResult<SensorChannel> {
+    channel.parse().map_err(PyValueError::new_err)
+}
+
+/// Parses a list of sensor channel names, defaulting to all channels.
+pub(crate) fn parse_channels(channels: Option<Vec<String>>) -> PyResult<Vec<SensorChannel>> {
+    match channels {
+        Some(channels) => channels.iter().map(|c| parse_channel(c)).collect(),
+        None => Ok((0..SensorChannel::LENGTH).map(SensorChannel::from_usize).collect()),
     }
 }
 
 impl Tables {
+    /// Returns the given class mapping, or that of the tables.
+    fn mapping_or_default<'a>(&'a self, mapping: Option<&'a Bound<'_, ClassMapping>>) -> PyResult<&'a ClassMapping> {
+        match mapping {
+            Some(m) => Ok(m.get()),
+            None => self
+                .class_mapping
+                .as_ref()
+                .ok_or_else(|| PyValueError::new_err("No class mapping given and none set on the tables")),
+        }
+    }
+
+    /// Decodes an optional scene token, checking that the scene exists.
+    pub(crate) fn scene_filter(&self, scene: Option<&str>) -> PyResult<Option<[u8; 16]>> {
+        scene
+            .map(|tok| {
+                let bytes = decode_token(tok)?;
+                self.scene.get(&bytes).map(|_| bytes).ok_or_else(|| PyKeyError::new_err(tok.to_string()))
+            })
+            .transpose()
+    }
+
+    /// Resolves the scenes of a split of `create_splits_scenes`, intersected with a list of scene names or tokens.
+    /// Returns `None` to keep all scenes.
+    fn scene_set(
+        &self, py: Python<'_>, split: Option<&str>, scenes: Option<Vec<String>>,
+    ) -> PyResult<Option<HashSet<[u8; 16]>>> {
+        let by_name: HashMap<&str, [u8; 16]> = self.scene.iter().map(|s| (s.name.as_ref(), s.token)).collect();
+        let split = split
+            .map(|split| {
+                let splits = py.import("nuscenes.utils.splits")?.call_method0("create_splits_scenes")?;
+                let names: Vec<String> = splits
+                    .get_item(split)
+                    .map_err(|_| PyValueError::new_err(format!("Unknown split {split}")))?
+                    .extract()?;
+                PyResult::Ok(
+                    names.iter().filter_map(|name| by_name.get(name.as_str()).copied()).collect::<HashSet<_>>(),
+                )
+            })
+            .transpose()?;
+        let scenes = scenes
+            .map(|scenes| {
+                scenes
+                    .iter()
+                    .map(|scene| match by_name.get(scene.as_str()) {
+                        Some(token) => Ok(*token),
+                        None => self.scene_filter(Some(scene)).map(Option::unwrap),
+                    })
+                    .collect::<PyResult<HashSet<_>>>()
+            })
+            .transpose()?;
+        Ok(match (split, scenes) {
+            (Some(split), Some(scenes)) => Some(split.intersection(&scenes).copied().collect()),
+            (split, scenes) => split.or(scenes),
+        })
+    }
+
+    fn window_by_channel(
+        &self, start: u64, end: u64, channels: &[SensorChannel], scene: Option<&[u8; 16]>,
+    ) -> HashMap<String, Vec<String>> {
+        let timeline = self.timeline();
+        channels
+            .iter()
+            .map(|&channel| {
+                let tokens = timeline
+                    .channel(channel, scene)
+                    .map(|s| s.window(start, end).iter().map(|(_, tok)| hex::encode(tok)).collect())
+                    .unwrap_or_default();
+                (channel.to_string(), tokens)
+            })
+            .collect()
+    }
+
+    fn ego_trajectory_of(&self, token: &str) -> PyResult<&EgoTrajectory> {
+        self.trajectories()
+            .get(&decode_token(token)?)
+            .ok_or_else(|| PyKeyError::new_err(format!("{token} is not a scene or log token")))
+    }
+
+    fn projected_boxes(&self, sd: &SampleData, vis: BoxVisibility, filter: &BoxFilter) -> PyResult<ProjectedBoxes> {
+        let camera = self.camera(sd)?;
+        Ok(ProjectedBoxes::new(&camera, self.sample_data_boxes(sd, Frame::Sensor, filter), vis))
+    }
+
     fn lookup_in_table<'py, T: ToPyDict + AsRefToken>(
         &self, py: Python<'py>, table: &Table<T>, token: &[u8; 16],
     ) -> PyResult<Bound<'py, PyDict>> {
@@ -0,0 +1,36 @@
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use pyo3::exceptions::{PyFileNotFoundError, PyValueError};
+use pyo3::prelude::*;
+
+use crate::domain::SampleData;
+use crate::nusc::Tables;
+
+mod labels;
+mod lidar;
+mod multisweep;
+mod pcd;
+mod radar;
+mod voxel;
+
+pub use labels::*;
+pub use lidar::*;
+pub use multisweep::*;
+pub use radar::*;
+pub use voxel::*;
+
+/// Opens a sample data file, raising a `FileNotFoundError` if it is missing.
+pub fn open_file(path: &Path) -> PyResult<File> {
+    if !path.exists() {
+        return Err(PyFileNotFoundError::new_err(format!("Point cloud not found at {}", path.display())));
+    }
+    File::open(path).map_err(|e| PyValueError::new_err(format!("Failed to open {}: {e}", path.display())))
+}
+
+impl Tables {
+    /// Path of the file of a sample data.
+    pub(crate) fn sample_data_path(&self, sd: &SampleData) -> PathBuf {
+        Path::new(&self.dataroot).join(sd.filename.as_ref())
+    }
+}
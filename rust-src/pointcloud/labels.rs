@@ -0,0 +1,140 @@
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use rayon::prelude::*;
+
+use super::{LIDAR_FIELDS, open_file};
+use crate::boxes::BoxFilter;
+use crate::codec::read_npz;
+use crate::domain::SampleData;
+use crate::geometry::Frame;
+use crate::nusc::Tables;
+
+/// Panoptic labels are `category_index * PANOPTIC_DIVISOR + instance_id`, with instance 0 for stuff and noise.
+pub const PANOPTIC_DIVISOR: u16 = 1000;
+
+/// Splits panoptic labels into lidarseg category indices and instance ids.
+pub fn decode_panoptic(labels: &[u16]) -> (Vec<u8>, Vec<u16>) {
+    labels.iter().map(|&label| ((label / PANOPTIC_DIVISOR) as u8, label % PANOPTIC_DIVISOR)).unzip()
+}
+
+// Points per parallel task when testing points against boxes.
+const POINT_CHUNK: usize = 4096;
+
+/// Labels of lidar points from the annotation boxes containing them.
+#[derive(Clone, Debug, Default)]
+pub struct BoxLabels {
+    pub annotations: Vec<[u8; 16]>, // Annotation of each box, in the order of the sample
+    pub box_index: Vec<i32>,        // Box of each point, -1 if outside of all boxes
+    pub num_lidar_pts: Vec<u32>,    // Points inside each box, including those assigned to another box
+}
+
+fn read_file(path: &Path) -> PyResult<Vec<u8>> {
+    let mut bytes = Vec::new();
+    open_file(path)?
+        .read_to_end(&mut bytes)
+        .map_err(|e| PyValueError::new_err(format!("Failed to read {}: {e}", path.display())))?;
+    Ok(bytes)
+}
+
+fn no_labels(sd: &SampleData, extension: &str) -> PyErr {
+    PyValueError::new_err(format!("{} has no {extension} labels", hex::encode(sd.token)))
+}
+
+impl Tables {
+    fn lidarseg_path(&self, sd: &SampleData) -> PyResult<PathBuf> {
+        let (Some(table), Some(index)) = (self.lidarseg.as_ref(), self.sample_data_lidarseg_index()) else {
+            return Err(PyValueError::new_err("lidarseg not loaded due to missing 'lidarseg.json'"));
+        };
+        let rec = index.get(&sd.token).and_then(|tok| table.get(tok)).ok_or_else(|| no_labels(sd, "lidarseg"))?;
+        Ok(Path::new(&self.dataroot).join(rec.filename.as_ref()))
+    }
+
+    fn panoptic_path(&self, sd: &SampleData) -> PyResult<PathBuf> {
+        let (Some(table), Some(index)) = (self.panoptic.as_ref(), self.sample_data_panoptic_index()) else {
+            return Err(PyValueError::new_err("panoptic is not loaded due to missing 'panoptic.json'"));
+        };
+        let rec = index.get(&sd.token).and_then(|tok| table.get(tok)).ok_or_else(|| no_labels(sd, "panoptic"))?;
+        Ok(Path::new(&self.dataroot).join(rec.filename.as_ref()))
+    }
+
+    /// Checks that there is one label per point of the `.pcd.bin` file of a lidar sample data.
+    fn verify_label_count(&self, sd: &SampleData, path: &Path, count: usize) -> PyResult<()> {
+        let points = self.lidar_points(sd, false)?.len();
+        if count != points {
+            return Err(PyValueError::new_err(format!(
+                "{} has {count} labels, but its point cloud has {points} points of {LIDAR_FIELDS} fields",
+                path.display()
+            )));
+        }
+        Ok(())
+    }
+
+    /// Reads the lidarseg category index of every point of a lidar sample data, from its `.bin` file of `uint8`.
+    pub(crate) fn lidarseg_labels(&self, sd: &SampleData, verify: bool) -> PyResult<Vec<u8>> {
+        let path = self.lidarseg_path(sd)?;
+        let labels = read_file(&path)?;
+        if verify {
+            self.verify_label_count(sd, &path, labels.len())?;
+        }
+        Ok(labels)
+    }
+
+    /// Reads the panoptic label of every point of a lidar sample data, from the `data` array of its `.npz` file.
+    pub(crate) fn panoptic_labels(&self, sd: &SampleData, verify: bool) -> PyResult<Vec<u16>> {
+        let path = self.panoptic_path(sd)?;
+        let labels = read_npz(&read_file(&path)?, "data")
+            .and_then(|array| array.to_u16())
+            .map_err(|e| PyValueError::new_err(format!("Failed to read {}: {e}", path.display())))?;
+        if verify {
+            self.verify_label_count(sd, &path, labels.len())?;
+        }
+        Ok(labels)
+    }
+
+    /// Assigns each point of a lidar sample data to the annotation box containing it, with boxes scaled by
+    /// `wlh_factor`. A point inside several boxes goes to the one with the nearest center, or the first of the sample on
+    /// ties. Sweeps get the interpolated boxes of `sample_data_boxes`.
+    pub(crate) fn label_points_by_boxes(&self, sd: &SampleData, wlh_factor: f64) -> PyResult<BoxLabels> {
+        let points = self.lidar_points(sd, false)?;
+        let sample = self.sample.get(&sd.sample_token).unwrap();
+        // Without a filter, there is one box per annotation of the sample, in the same order.
+        let interiors: Vec<_> = self
+            .sample_data_boxes(sd, Frame::Sensor, &BoxFilter::default())
+            .iter()
+            .map(|b| b.interior(wlh_factor))
+            .collect();
+
+        let chunks: Vec<(Vec<i32>, Vec<u32>)> = points
+            .points()
+            .par_chunks(POINT_CHUNK)
+            .map(|chunk| {
+                let mut box_index = Vec::with_capacity(chunk.len());
+                let mut num_lidar_pts = vec![0; interiors.len()];
+                for p in chunk {
+                    let p = [p[0] as f64, p[1] as f64, p[2] as f64];
+                    let mut best: Option<(usize, f64)> = None;
+                    for (i, interior) in interiors.iter().enumerate().filter(|(_, interior)| interior.contains(p)) {
+                        num_lidar_pts[i] += 1;
+                        let dist = interior.distance_squared(p);
+                        if best.is_none_or(|(_, best)| dist < best) {
+                            best = Some((i, dist));
+                        }
+                    }
+                    box_index.push(best.map_or(-1, |(i, _)| i as i32));
+                }
+                (box_index, num_lidar_pts)
+            })
+            .collect();
+
+        let mut labels = BoxLabels { annotations: sample.anns.to_vec(), ..Default::default() };
+        labels.num_lidar_pts = vec![0; interiors.len()];
+        for (box_index, num_lidar_pts) in chunks {
+            labels.box_index.extend(box_index);
+            labels.num_lidar_pts.iter_mut().zip(num_lidar_pts).for_each(|(total, n)| *total += n);
+        }
+        Ok(labels)
+    }
+}
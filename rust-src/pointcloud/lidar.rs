@@ -0,0 +1,107 @@
+use std::cell::UnsafeCell;
+use std::path::Path;
+
+use memmap2::{MmapMut, MmapOptions};
+use numpy::PyArray2;
+use numpy::ndarray::ArrayViewMut2;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+use super::open_file;
+use crate::common::{SensorChannel, SensorModality};
+use crate::domain::{Sample, SampleData};
+use crate::nusc::Tables;
+
+/// Number of float32 fields per point in `.pcd.bin` files: x, y, z, intensity and ring index.
+pub const LIDAR_FIELDS: usize = 5;
+
+const POINT_SIZE: usize = LIDAR_FIELDS * size_of::<f32>();
+
+/// A memory-mapped `.pcd.bin` lidar point cloud.
+///
+/// The mapping is private, so NumPy arrays viewing it are writable without modifying the file. It is also the base
+/// object of these arrays, which keep it alive. As NumPy writes to the mapping behind the shared references of Python,
+/// it is kept in an `UnsafeCell`.
+#[pyclass(module = "nuscenes._lib", frozen)]
+pub struct LidarPoints {
+    mmap: UnsafeCell<MmapMut>,
+    len: usize,
+}
+
+// SAFETY: Rust only reads the mapping through `points` before it is handed to NumPy by `into_pyarray`, which consumes
+// it. From then on the mapping is only accessed through the array.
+unsafe impl Sync for LidarPoints {}
+
+impl LidarPoints {
+    /// Maps a `.pcd.bin` file, checking that its size is a whole number of points. With `populate`, the file is read
+    /// ahead, which moves the I/O into the calling thread.
+    pub fn open(path: &Path, populate: bool) -> PyResult<Self> {
+        let file = open_file(path)?;
+        let len = file
+            .metadata()
+            .map_err(|e| PyValueError::new_err(format!("Failed to read the size of {}: {e}", path.display())))?
+            .len() as usize;
+        if !len.is_multiple_of(POINT_SIZE) {
+            return Err(PyValueError::new_err(format!(
+                "Size of {} is {len} bytes, which is not a multiple of {LIDAR_FIELDS} float32 per point",
+                path.display()
+            )));
+        }
+        let mut options = MmapOptions::new();
+        if populate {
+            options.populate();
+        }
+        // Mapping copy-on-write keeps writes to the arrays out of the file.
+        let mmap = unsafe { options.map_copy(&file) }
+            .map_err(|e| PyValueError::new_err(format!("Failed to map {}: {e}", path.display())))?;
+        Ok(Self { mmap: UnsafeCell::new(mmap), len: len / POINT_SIZE })
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn points(&self) -> &[[f32; LIDAR_FIELDS]] {
+        // SAFETY: Mappings are page-aligned and the size was checked to be a whole number of points. Only arrays from
+        // `into_pyarray` write to the mapping, and it consumes `self`.
+        unsafe {
+            let mmap = &*self.mmap.get();
+            std::slice::from_raw_parts(mmap.as_ptr().cast(), self.len())
+        }
+    }
+
+    /// Returns a writable `(N, 5)` array viewing the mapping, which it keeps alive.
+    pub fn into_pyarray(mut self, py: Python<'_>) -> PyResult<Bound<'_, PyArray2<f32>>> {
+        let len = self.len;
+        // The pointer comes from the exclusive borrow of the mapping, so that NumPy may write through it. Moving the
+        // `MmapMut` below does not move the mapped memory.
+        let ptr = self.mmap.get_mut().as_mut_ptr().cast::<f32>();
+        let owner = Bound::new(py, self)?;
+        // SAFETY: The pointer is page-aligned and valid for `len` points. The mapping is never remapped and lives as
+        // long as `owner`, the base object of the array.
+        Ok(unsafe {
+            let view = ArrayViewMut2::from_shape_ptr((len, LIDAR_FIELDS), ptr);
+            PyArray2::borrow_from_array(&view, owner.into_any())
+        })
+    }
+}
+
+impl Tables {
+    /// Maps the `.pcd.bin` file of a lidar sample data.
+    pub(crate) fn lidar_points(&self, sd: &SampleData, populate: bool) -> PyResult<LidarPoints> {
+        if sd.modality != SensorModality::Lidar || !sd.filename.ends_with(".pcd.bin") {
+            return Err(PyValueError::new_err(format!(
+                "{} is not a lidar .pcd.bin sample data",
+                hex::encode(sd.token)
+            )));
+        }
+        LidarPoints::open(&self.sample_data_path(sd), populate)
+    }
+
+    /// Returns the LIDAR_TOP keyframe of a sample, or an error if it has none.
+    pub(crate) fn sample_lidar_top(&self, sample: &Sample) -> PyResult<&SampleData<'_>> {
+        self.sample_data.get(&sample.data[SensorChannel::LidarTop]).ok_or_else(|| {
+            PyValueError::new_err(format!("Sample {} has no LIDAR_TOP keyframe", hex::encode(sample.token)))
+        })
+    }
+}
@@ -0,0 +1,91 @@
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use rayon::prelude::*;
+
+use super::RadarFilters;
+use crate::common::{SensorChannel, SensorModality};
+use crate::domain::{Sample, SampleData};
+use crate::geometry::Frame;
+use crate::nusc::Tables;
+
+/// Number of lidar fields kept in aggregated sweeps, dropping the ring index as `LidarPointCloud`.
+pub const LIDAR_SWEEP_FIELDS: usize = 4;
+
+/// Points of several sweeps in a reference frame, with the time lag to the reference as last column.
+#[derive(Clone, Debug, Default)]
+pub struct Sweeps {
+    pub data: Vec<f32>,
+    pub columns: usize,
+}
+
+impl Sweeps {
+    pub fn len(&self) -> usize {
+        self.data.len().checked_div(self.columns).unwrap_or_default()
+    }
+}
+
+impl Tables {
+    /// Reads the points of a lidar or radar sample data as rows of its fields, in the sensor frame.
+    fn sweep_rows(&self, sd: &SampleData, filters: &RadarFilters) -> PyResult<(Vec<f32>, usize)> {
+        match sd.modality {
+            SensorModality::Lidar => {
+                let points = self.lidar_points(sd, true)?;
+                let rows = points.points().iter().flat_map(|p| p[..LIDAR_SWEEP_FIELDS].iter().copied()).collect();
+                Ok((rows, LIDAR_SWEEP_FIELDS))
+            }
+            SensorModality::Radar => {
+                let points = self.radar_points(sd, filters, Frame::Sensor)?;
+                Ok((points.to_matrix(), points.header.fields.len()))
+            }
+            SensorModality::Camera => {
+                Err(PyValueError::new_err(format!("{} is not a lidar or radar sample data", hex::encode(sd.token))))
+            }
+        }
+    }
+
+    /// Aggregates `nsweeps` sweeps of `channel`, walking back from the keyframe of a sample, into the frame of the
+    /// `ref_channel` keyframe, as `PointCloud.from_file_multisweep`. Points within `min_distance` of the sensor along
+    /// both x and y are removed before the transform.
+    pub(crate) fn multisweep(
+        &self, sample: &Sample, channel: SensorChannel, ref_channel: SensorChannel, nsweeps: usize, min_distance: f32,
+        filters: &RadarFilters,
+    ) -> PyResult<Sweeps> {
+        let keyframe = |channel: SensorChannel| {
+            self.sample_data.get(&sample.data[channel]).ok_or_else(|| {
+                PyValueError::new_err(format!("Sample {} has no {channel} data", hex::encode(sample.token)))
+            })
+        };
+        let ref_sd = keyframe(ref_channel)?;
+        let ref_from_global = self.sensor_pose(ref_sd).sensor_to_global().inverse();
+
+        let mut sweeps = Vec::with_capacity(nsweeps);
+        let mut cursor = Some(keyframe(channel)?);
+        while let Some(sd) = cursor.filter(|_| sweeps.len() < nsweeps) {
+            sweeps.push(sd);
+            cursor = sd.prev.and_then(|tok| self.sample_data.get(&tok));
+        }
+
+        let parts = sweeps
+            .par_iter()
+            .map(|sd| {
+                let (rows, fields) = self.sweep_rows(sd, filters)?;
+                let transform = ref_from_global * self.sensor_pose(sd).sensor_to_global();
+                let time_lag = (ref_sd.timestamp as f64 - sd.timestamp as f64) * 1e-6;
+                let mut out = Vec::with_capacity(rows.len() + rows.len() / fields.max(1));
+                for row in rows.chunks_exact(fields) {
+                    if row[0].abs() < min_distance && row[1].abs() < min_distance {
+                        continue;
+                    }
+                    let p = transform.apply([row[0] as f64, row[1] as f64, row[2] as f64]);
+                    out.extend(p.map(|c| c as f32));
+                    out.extend_from_slice(&row[3..]);
+                    out.push(time_lag as f32);
+                }
+                Ok((out, fields + 1))
+            })
+            .collect::<PyResult<Vec<_>>>()?;
+
+        let columns = parts.first().map_or(0, |(_, columns)| *columns);
+        Ok(Sweeps { data: parts.into_iter().flat_map(|(data, _)| data).collect(), columns })
+    }
+}
@@ -0,0 +1,167 @@
+use std::str::FromStr;
+
+/// Storage type of a PCD field.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FieldType {
+    Float,
+    Signed,
+    Unsigned,
+}
+
+/// A field of a PCD file, with its byte offset in a point.
+#[derive(Clone, Debug)]
+pub struct PcdField {
+    pub name: String,
+    pub size: usize,
+    pub kind: FieldType,
+    pub offset: usize,
+}
+
+impl PcdField {
+    /// NumPy type string of the field, e.g. `<f4`.
+    pub fn dtype(&self) -> String {
+        let kind = match self.kind {
+            FieldType::Float => 'f',
+            FieldType::Signed => 'i',
+            FieldType::Unsigned => 'u',
+        };
+        format!("<{kind}{}", self.size)
+    }
+
+    /// Reads the field from the bytes of a point as a float.
+    pub fn read(&self, point: &[u8]) -> f64 {
+        let bytes = &point[self.offset..self.offset + self.size];
+        let mut buf = [0u8; 8];
+        buf[..self.size].copy_from_slice(bytes);
+        match (self.kind, self.size) {
+            (FieldType::Float, 4) => f32::from_le_bytes(buf[..4].try_into().unwrap()) as f64,
+            (FieldType::Float, _) => f64::from_le_bytes(buf),
+            (FieldType::Signed, 1) => bytes[0] as i8 as f64,
+            (FieldType::Signed, 2) => i16::from_le_bytes(buf[..2].try_into().unwrap()) as f64,
+            (FieldType::Signed, 4) => i32::from_le_bytes(buf[..4].try_into().unwrap()) as f64,
+            (FieldType::Signed, _) => i64::from_le_bytes(buf) as f64,
+            (FieldType::Unsigned, _) => u64::from_le_bytes(buf) as f64,
+        }
+    }
+
+    /// Overwrites a float32 field in the bytes of a point.
+    pub fn write_f32(&self, point: &mut [u8], value: f32) {
+        debug_assert!(self.kind == FieldType::Float && self.size == 4);
+        point[self.offset..self.offset + 4].copy_from_slice(&value.to_le_bytes());
+    }
+}
+
+/// Header of a binary PCD v0.7 file, with the offset of the point data.
+#[derive(Clone, Debug)]
+pub struct PcdHeader {
+    pub fields: Vec<PcdField>,
+    pub points: usize,
+    pub point_size: usize,
+    pub data_offset: usize,
+}
+
+impl PcdHeader {
+    pub fn field(&self, name: &str) -> Option<&PcdField> {
+        self.fields.iter().find(|f| f.name == name)
+    }
+}
+
+fn parse_values<T: FromStr>(key: &str, values: &[&str]) -> Result<Vec<T>, String> {
+    values.iter().map(|v| v.parse().map_err(|_| format!("Invalid {key} value '{v}'"))).collect()
+}
+
+/// Parses the header of a PCD file, which must have binary data and one value per field.
+pub fn parse_header(bytes: &[u8]) -> Result<PcdHeader, String> {
+    let (mut names, mut sizes, mut types, mut counts) = (Vec::new(), Vec::new(), Vec::new(), Vec::new());
+    let (mut width, mut height, mut points) = (None, 1, None);
+    let mut offset = 0;
+    loop {
+        let line_len = bytes[offset..].iter().position(|b| *b == b'\n').ok_or("Missing DATA line in PCD header")?;
+        let line = std::str::from_utf8(&bytes[offset..offset + line_len]).map_err(|e| e.to_string())?.trim();
+        offset += line_len + 1;
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut tokens = line.split_whitespace();
+        let key = tokens.next().unwrap();
+        let values: Vec<_> = tokens.collect();
+        match key {
+            "VERSION" => {}
+            "FIELDS" => names = values.iter().map(|v| v.to_string()).collect(),
+            "SIZE" => sizes = parse_values(key, &values)?,
+            "TYPE" => types = values.iter().map(|v| v.to_string()).collect(),
+            "COUNT" => counts = parse_values::<usize>(key, &values)?,
+            "WIDTH" => width = values.first().and_then(|v| v.parse::<usize>().ok()),
+            "HEIGHT" => height = values.first().and_then(|v| v.parse().ok()).ok_or("Invalid HEIGHT")?,
+            "VIEWPOINT" => {}
+            "POINTS" => points = values.first().and_then(|v| v.parse::<usize>().ok()),
+            "DATA" => match values.first() {
+                Some(&"binary") => break,
+                other => return Err(format!("Unsupported PCD data {other:?}, expected binary")),
+            },
+            other => return Err(format!("Unknown PCD header key '{other}'")),
+        }
+    }
+
+    if sizes.len() != names.len() || types.len() != names.len() {
+        return Err("PCD header has mismatching FIELDS, SIZE and TYPE".to_string());
+    }
+    if counts.iter().any(|c| *c != 1) {
+        return Err("PCD fields with COUNT other than 1 are not supported".to_string());
+    }
+    let width = width.ok_or("Missing WIDTH in PCD header")?;
+    if height != 1 {
+        return Err("PCD files with HEIGHT other than 1 are not supported".to_string());
+    }
+
+    let mut fields = Vec::with_capacity(names.len());
+    let mut point_size = 0usize;
+    for ((name, size), kind) in names.into_iter().zip(sizes).zip(types) {
+        let kind = match (kind.as_str(), size) {
+            ("F", 4 | 8) => FieldType::Float,
+            ("I", 1 | 2 | 4 | 8) => FieldType::Signed,
+            ("U", 1 | 2 | 4 | 8) => FieldType::Unsigned,
+            _ => return Err(format!("Unsupported type {kind} of size {size} for PCD field '{name}'")),
+        };
+        fields.push(PcdField { name, size, kind, offset: point_size });
+        point_size = point_size.checked_add(size).ok_or("PCD point size overflows")?;
+    }
+
+    let points = points.unwrap_or(width);
+    let expected = points
+        .checked_mul(point_size)
+        .ok_or_else(|| format!("PCD data of {points} points of {point_size} bytes overflows"))?;
+    if bytes.len() - offset < expected {
+        return Err(format!("PCD data has {} bytes, expected {expected} for {points} points", bytes.len() - offset));
+    }
+    Ok(PcdHeader { fields, points, point_size, data_offset: offset })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(points: usize) -> Vec<u8> {
+        let header = format!(
+            "VERSION 0.7\nFIELDS x y\nSIZE 4 8\nTYPE F U\nCOUNT 1 1\nWIDTH {points}\nPOINTS {points}\nDATA binary\n"
+        );
+        header.into_bytes()
+    }
+
+    #[test]
+    fn parse_header_lays_out_the_fields() {
+        let mut bytes = header(2);
+        let data_offset = bytes.len();
+        bytes.extend_from_slice(&[0; 24]);
+        let header = parse_header(&bytes).unwrap();
+        assert_eq!((header.points, header.point_size, header.data_offset), (2, 12, data_offset));
+        assert_eq!(header.field("y").map(|f| (f.offset, f.dtype())), Some((4, "<u8".to_string())));
+        assert!(parse_header(&bytes[..bytes.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn parse_header_rejects_overflowing_sizes() {
+        let err = parse_header(&header(usize::MAX / 4)).unwrap_err();
+        assert!(err.contains("overflows"), "{err}");
+    }
+}
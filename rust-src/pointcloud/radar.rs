@@ -0,0 +1,135 @@
+use std::path::Path;
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::{PyByteArray, PyList};
+
+use super::open_file;
+use super::pcd::{PcdHeader, parse_header};
+use crate::common::SensorModality;
+use crate::domain::SampleData;
+use crate::geometry::{Frame, Transform};
+use crate::nusc::Tables;
+
+/// Which radar returns to keep by state, as the class-level settings of `RadarPointCloud`.
+#[derive(Clone, Debug)]
+pub struct RadarFilters {
+    pub invalid_states: Vec<i64>,
+    pub dynprop_states: Vec<i64>,
+    pub ambig_states: Vec<i64>,
+}
+
+impl Default for RadarFilters {
+    fn default() -> Self {
+        Self { invalid_states: vec![0], dynprop_states: (0..7).collect(), ambig_states: vec![3] }
+    }
+}
+
+impl RadarFilters {
+    /// Overrides the default filters with the given ones.
+    pub fn new(
+        invalid_states: Option<Vec<i64>>, dynprop_states: Option<Vec<i64>>, ambig_states: Option<Vec<i64>>,
+    ) -> Self {
+        let default = Self::default();
+        Self {
+            invalid_states: invalid_states.unwrap_or(default.invalid_states),
+            dynprop_states: dynprop_states.unwrap_or(default.dynprop_states),
+            ambig_states: ambig_states.unwrap_or(default.ambig_states),
+        }
+    }
+}
+
+/// Radar returns of a PCD file, kept as packed little-endian records of the header fields.
+#[derive(Clone, Debug)]
+pub struct RadarPoints {
+    pub header: PcdHeader,
+    pub data: Vec<u8>,
+}
+
+impl RadarPoints {
+    /// Reads a radar `.pcd` file, keeping the returns that pass `filters`, as `RadarPointCloud.from_file`.
+    pub fn read(path: &Path, filters: &RadarFilters) -> PyResult<Self> {
+        let file = open_file(path)?;
+        let mmap = unsafe { memmap2::Mmap::map(&file) }
+            .map_err(|e| PyValueError::new_err(format!("Failed to map {}: {e}", path.display())))?;
+        let header = parse_header(&mmap)
+            .map_err(|e| PyValueError::new_err(format!("Failed to parse {}: {e}", path.display())))?;
+        let field = |name| {
+            header.field(name).cloned().ok_or_else(|| {
+                PyValueError::new_err(format!("{} has no '{name}' field of radar returns", path.display()))
+            })
+        };
+        let (invalid, dynprop, ambig) = (field("invalid_state")?, field("dyn_prop")?, field("ambig_state")?);
+
+        let bytes = &mmap[header.data_offset..header.data_offset + header.points * header.point_size];
+        let mut points = bytes.chunks_exact(header.point_size).peekable();
+        // A NaN in the first point indicates an empty pointcloud.
+        if points.peek().is_some_and(|p| header.fields.iter().any(|f| f.read(p).is_nan())) {
+            return Ok(Self { header, data: Vec::new() });
+        }
+
+        let keep = |p: &&[u8]| {
+            filters.invalid_states.contains(&(invalid.read(p) as i64))
+                && filters.dynprop_states.contains(&(dynprop.read(p) as i64))
+                && filters.ambig_states.contains(&(ambig.read(p) as i64))
+        };
+        let data = points.filter(keep).flatten().copied().collect();
+        Ok(Self { header, data })
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len() / self.header.point_size
+    }
+
+    /// Moves the positions and the ego-motion compensated velocities into another frame. Velocities are rotated
+    /// in the ground plane, dropping their vertical component as `NuScenes.render_sample_data` does.
+    pub fn transform(&mut self, transform: &Transform) -> PyResult<()> {
+        let field = |name| {
+            self.header
+                .field(name)
+                .cloned()
+                .ok_or_else(|| PyValueError::new_err(format!("Radar has no '{name}' field")))
+        };
+        let [x, y, z, vx, vy] = ["x", "y", "z", "vx_comp", "vy_comp"].map(field);
+        let (x, y, z, vx, vy) = (x?, y?, z?, vx?, vy?);
+        for point in self.data.chunks_exact_mut(self.header.point_size) {
+            let p = transform.apply([x.read(point), y.read(point), z.read(point)]);
+            let v = transform.rotation.rotate([vx.read(point), vy.read(point), 0.0]);
+            x.write_f32(point, p[0] as f32);
+            y.write_f32(point, p[1] as f32);
+            z.write_f32(point, p[2] as f32);
+            vx.write_f32(point, v[0] as f32);
+            vy.write_f32(point, v[1] as f32);
+        }
+        Ok(())
+    }
+
+    /// Returns the fields of all points as an `(N, fields)` row-major matrix, as in `RadarPointCloud.points.T`.
+    pub fn to_matrix(&self) -> Vec<f32> {
+        let fields = &self.header.fields;
+        self.data.chunks_exact(self.header.point_size).flat_map(|p| fields.iter().map(|f| f.read(p) as f32)).collect()
+    }
+
+    /// Returns the points as a NumPy structured array with the fields of the header.
+    pub fn into_structured<'py>(self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        let numpy = py.import("numpy")?;
+        let fields: Vec<_> = self.header.fields.iter().map(|f| (f.name.as_str(), f.dtype())).collect();
+        let dtype = numpy.call_method1("dtype", (PyList::new(py, fields)?,))?;
+        // A bytearray gives a writable array that shares its buffer.
+        numpy.call_method1("frombuffer", (PyByteArray::new(py, &self.data), dtype))
+    }
+}
+
+impl Tables {
+    /// Reads the `.pcd` file of a radar sample data, with positions and velocities in `frame`.
+    pub(crate) fn radar_points(&self, sd: &SampleData, filters: &RadarFilters, frame: Frame) -> PyResult<RadarPoints> {
+        if sd.modality != SensorModality::Radar || !sd.filename.ends_with(".pcd") {
+            return Err(PyValueError::new_err(format!("{} is not a radar .pcd sample data", hex::encode(sd.token))));
+        }
+        let mut points = RadarPoints::read(&self.sample_data_path(sd), filters)?;
+        if frame != Frame::Sensor {
+            points.transform(&self.sensor_pose(sd).between(Frame::Sensor, frame))?;
+        }
+        Ok(points)
+    }
+}
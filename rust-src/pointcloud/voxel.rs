@@ -0,0 +1,215 @@
+use ahash::{HashMap, HashMapExt};
+use numpy::ndarray::{Array2, Array3};
+use numpy::{IntoPyArray, PyReadonlyArray2, PyUntypedArrayMethods};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyTuple;
+use rayon::prelude::*;
+
+/// Regular grid of voxels over a point cloud range, with caps on the points per voxel and the number of voxels, for
+/// points of `columns` values. Pillars are voxels spanning the whole height of the range.
+#[derive(Clone, Copy, Debug)]
+pub struct VoxelGrid {
+    pub range: [f32; 6], // x, y, z minima then maxima
+    pub voxel_size: [f32; 3],
+    pub max_points: usize,
+    pub max_voxels: usize,
+    pub dims: [usize; 3], // Number of voxels along x, y and z
+    pub columns: usize,
+}
+
+/// Voxelized points as the `(voxels, coords, num_points)` of hard voxelization.
+#[derive(Clone, Debug, Default)]
+pub struct Voxels {
+    pub data: Vec<f32>,        // Points of each voxel, zero-padded to the maximum
+    pub coords: Vec<[i32; 3]>, // z, y and x indices of each voxel
+    pub num_points: Vec<i32>,
+    pub columns: usize,
+}
+
+impl Voxels {
+    pub fn len(&self) -> usize {
+        self.coords.len()
+    }
+
+    /// Returns the `(V, P, C)` float32 voxels, `(V, 3)` int32 coordinates and `(V,)` int32 point counts.
+    pub fn into_pytuple(self, py: Python<'_>, max_points: usize) -> PyResult<Bound<'_, PyTuple>> {
+        let voxels = Array3::from_shape_vec((self.len(), max_points, self.columns), self.data).unwrap();
+        let coords = Array2::from_shape_vec((self.coords.len(), 3), self.coords.concat()).unwrap();
+        (voxels.into_pyarray(py), coords.into_pyarray(py), self.num_points.into_pyarray(py)).into_pyobject(py)
+    }
+}
+
+impl VoxelGrid {
+    pub fn new(
+        range: [f32; 6], voxel_size: [f32; 3], max_points: usize, max_voxels: usize, columns: usize,
+    ) -> PyResult<Self> {
+        let valid = (0..3).all(|i| voxel_size[i] > 0.0 && range[i] < range[i + 3]) && max_points > 0;
+        if !valid {
+            return Err(PyValueError::new_err(format!(
+                "Invalid voxel grid with range {range:?}, voxel size {voxel_size:?} and {max_points} points per voxel"
+            )));
+        }
+        let dims = [0, 1, 2].map(|i| ((range[i + 3] - range[i]) / voxel_size[i]).round().max(1.0) as usize);
+        // Voxel indices and the buffer of the voxels kept must fit, and coordinates are returned as int32.
+        let size = dims
+            .iter()
+            .try_fold(1usize, |total, &dim| total.checked_mul(dim))
+            .filter(|_| dims.iter().all(|&dim| dim <= i32::MAX as usize))
+            .and_then(|total| total.min(max_voxels).checked_mul(max_points)?.checked_mul(columns));
+        if size.is_none() {
+            return Err(PyValueError::new_err(format!(
+                "Voxel grid of {dims:?} voxels with {max_points} points of {columns} values per voxel is too large"
+            )));
+        }
+        Ok(Self { range, voxel_size, max_points, max_voxels, dims, columns })
+    }
+
+    /// Linear index of the voxel containing a point, if it is inside the range.
+    fn voxel_of(&self, point: &[f32]) -> Option<usize> {
+        let mut index = 0;
+        for i in (0..3).rev() {
+            let c = ((point[i] - self.range[i]) / self.voxel_size[i]).floor();
+            if !(c >= 0.0 && (c as usize) < self.dims[i]) {
+                return None;
+            }
+            index = index * self.dims[i] + c as usize;
+        }
+        Some(index)
+    }
+
+    /// Voxelizes rows of `columns` values starting with x, y and z.
+    ///
+    /// Voxels are ordered by their first point and keep their first points, so the result does not depend on the
+    /// parallelism. Points beyond `max_points` in a voxel and voxels beyond `max_voxels` are dropped.
+    pub fn voxelize(&self, data: &[f32]) -> Voxels {
+        let columns = self.columns;
+        let keys: Vec<Option<usize>> = data.par_chunks_exact(columns).map(|p| self.voxel_of(p)).collect();
+
+        let mut slots: HashMap<usize, usize> = HashMap::new();
+        let mut members: Vec<Vec<usize>> = Vec::new();
+        let mut coords = Vec::new();
+        for (i, key) in keys.into_iter().enumerate() {
+            let Some(key) = key else { continue };
+            let slot = match slots.get(&key) {
+                Some(&slot) => slot,
+                None if members.len() < self.max_voxels => {
+                    slots.insert(key, members.len());
+                    let (x, rest) = (key % self.dims[0], key / self.dims[0]);
+                    let (y, z) = (rest % self.dims[1], rest / self.dims[1]);
+                    coords.push([z as i32, y as i32, x as i32]);
+                    members.push(Vec::with_capacity(self.max_points));
+                    members.len() - 1
+                }
+                None => continue,
+            };
+            if members[slot].len() < self.max_points {
+                members[slot].push(i);
+            }
+        }
+
+        let mut voxels = vec![0.0; members.len() * self.max_points * columns];
+        voxels.par_chunks_mut(self.max_points * columns).zip(&members).for_each(|(voxel, points)| {
+            for (row, &i) in voxel.chunks_mut(columns).zip(points) {
+                row.copy_from_slice(&data[i * columns..(i + 1) * columns]);
+            }
+        });
+        Voxels { data: voxels, coords, num_points: members.iter().map(|points| points.len() as i32).collect(), columns }
+    }
+}
+
+/// Voxelizes `(N, C)` points whose first columns are x, y and z, within `point_cloud_range` given as x, y and z minima
+/// then maxima. Returns the `(V, max_points, C)` voxels zero-padded, their `(V, 3)` z, y and x coordinates and their
+/// numbers of points. Voxels are ordered by their first point, which makes the result deterministic.
+#[pyfunction]
+#[pyo3(signature = (points, point_cloud_range, voxel_size, max_points=32, max_voxels=20000))]
+pub fn voxelize<'py>(
+    py: Python<'py>, points: PyReadonlyArray2<'py, f32>, point_cloud_range: [f32; 6], voxel_size: [f32; 3],
+    max_points: usize, max_voxels: usize,
+) -> PyResult<Bound<'py, PyTuple>> {
+    let columns = points.shape()[1];
+    if columns < 3 {
+        return Err(PyValueError::new_err(format!(
+            "Expected points of shape (N, C) with C >= 3, got {:?}",
+            points.shape()
+        )));
+    }
+    let grid = VoxelGrid::new(point_cloud_range, voxel_size, max_points, max_voxels, columns)?;
+    let voxels = match points.as_slice() {
+        Ok(data) => py.detach(|| grid.voxelize(data)),
+        Err(_) => {
+            let data: Vec<f32> = points.as_array().iter().copied().collect();
+            py.detach(|| grid.voxelize(&data))
+        }
+    };
+    voxels.into_pytuple(py, max_points)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Grid of 1 meter voxels over [0, 4) x [0, 4) x [0, 2), for points of 4 values.
+    fn grid(max_points: usize, max_voxels: usize) -> VoxelGrid {
+        VoxelGrid::new([0.0, 0.0, 0.0, 4.0, 4.0, 2.0], [1.0; 3], max_points, max_voxels, 4).unwrap()
+    }
+
+    /// Points spread over the grid and around it, with the point index as the fourth value.
+    fn points(n: usize) -> Vec<f32> {
+        (0..n)
+            .flat_map(|i| [(i * 7 % 45) as f32 * 0.1 - 0.2, (i * 11 % 41) as f32 * 0.1, (i % 5) as f32 * 0.5, i as f32])
+            .collect()
+    }
+
+    #[test]
+    fn rejects_invalid_or_overflowing_grids() {
+        assert!(VoxelGrid::new([0.0, 0.0, 0.0, 4.0, 4.0, 2.0], [0.0, 1.0, 1.0], 32, 100, 4).is_err());
+        assert!(VoxelGrid::new([0.0, 0.0, 0.0, 4.0, 4.0, 2.0], [f32::NAN, 1.0, 1.0], 32, 100, 4).is_err());
+        assert!(VoxelGrid::new([0.0, 0.0, 0.0, 4.0, 4.0, 2.0], [1.0; 3], 0, 100, 4).is_err());
+        // Too many voxels to index, or to return int32 coordinates for.
+        assert!(VoxelGrid::new([0.0, 0.0, 0.0, 1e30, 1e30, 1e30], [1e-3; 3], 32, 100, 4).is_err());
+        assert!(VoxelGrid::new([0.0, 0.0, 0.0, 3e9, 1.0, 1.0], [1.0; 3], 32, 100, 4).is_err());
+        // Too large a buffer for the voxels kept.
+        assert!(VoxelGrid::new([0.0, 0.0, 0.0, 4.0, 4.0, 2.0], [1.0; 3], usize::MAX / 2, usize::MAX, 4).is_err());
+        assert_eq!(grid(32, 100).dims, [4, 4, 2]);
+    }
+
+    #[test]
+    fn voxelize_is_deterministic() {
+        let data = points(5000);
+        let (a, b) = (grid(3, 20).voxelize(&data), grid(3, 20).voxelize(&data));
+        assert_eq!((a.len(), &a.coords, &a.num_points), (20, &b.coords, &b.num_points));
+        assert_eq!(a.data, b.data);
+        // The first voxel holds the first point inside of the range, and voxels keep their first points in order.
+        assert_eq!(a.data[3], 1.0);
+        let ids = a.data.chunks(3 * 4).zip(&a.num_points).map(|(voxel, &n)| voxel.chunks(4).take(n as usize));
+        assert!(ids.map(|points| points.map(|p| p[3]).collect::<Vec<_>>()).all(|ids| ids.is_sorted()));
+    }
+
+    #[test]
+    fn voxelize_orders_voxels_by_their_first_point() {
+        let data = points(200);
+        let reversed: Vec<f32> = data.chunks(4).rev().flatten().copied().collect();
+        let (forward, backward) = (grid(200, 100).voxelize(&data), grid(200, 100).voxelize(&reversed));
+        assert_eq!(forward.len(), backward.len());
+        assert!(forward.num_points.iter().all(|&n| n > 0) && forward.num_points.iter().sum::<i32>() < 200);
+
+        // Both hold the same voxels with the same points, ordered by the first point of each input: by their smallest
+        // index forward and by their largest backward.
+        let voxel_points = |voxels: &Voxels| -> Vec<([i32; 3], Vec<f32>)> {
+            let rows = voxels.data.chunks(200 * 4).zip(&voxels.num_points);
+            let ids = rows.map(|(voxel, &n)| {
+                let mut ids: Vec<_> = voxel.chunks(4).take(n as usize).map(|p| p[3]).collect();
+                ids.sort_by(f32::total_cmp);
+                ids
+            });
+            voxels.coords.iter().copied().zip(ids).collect()
+        };
+        let (mut forward, mut backward) = (voxel_points(&forward), voxel_points(&backward));
+        assert!(forward.windows(2).all(|w| w[0].1[0] < w[1].1[0]));
+        assert!(backward.windows(2).all(|w| w[0].1.last() > w[1].1.last()));
+        forward.sort_by_key(|v| v.0);
+        backward.sort_by_key(|v| v.0);
+        assert_eq!(forward, backward);
+    }
+}
@@ -0,0 +1,272 @@
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use ahash::{HashMap, HashMapExt, HashSet};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use rayon::prelude::*;
+
+use crate::domain::SampleData;
+use crate::nusc::Tables;
+use crate::pointcloud::PANOPTIC_DIVISOR;
+
+/// Lidarseg categories from this index on are stuff, which has no instances, as in `panoptic_utils.py`.
+pub const STUFF_START_CLASS_ID: u8 = 24;
+
+/// Extension to read the semantic labels of points from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LabelSource {
+    Lidarseg,
+    Panoptic,
+}
+
+impl FromStr for LabelSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "lidarseg" => Ok(Self::Lidarseg),
+            "panoptic" => Ok(Self::Panoptic),
+            _ => Err(format!("gt_from can only be lidarseg or panoptic, got {s}")),
+        }
+    }
+}
+
+/// Point counts per lidarseg category of every labelled sample, in the order of the label table.
+#[derive(Clone, Debug, Default)]
+pub struct SemanticHistograms {
+    pub samples: Vec<[u8; 16]>,
+    pub scenes: Vec<[u8; 16]>, // Scene of each sample
+    pub counts: Vec<Vec<u64>>,
+}
+
+impl SemanticHistograms {
+    /// Sums the histograms of all samples.
+    pub fn total(&self, num_classes: usize) -> Vec<u64> {
+        let mut total = vec![0; num_classes];
+        for counts in &self.counts {
+            total.iter_mut().zip(counts).for_each(|(t, c)| *t += c);
+        }
+        total
+    }
+
+    /// Sums the histograms of the samples of each scene, in order of first appearance.
+    pub fn per_scene(&self, num_classes: usize) -> (Vec<[u8; 16]>, Vec<Vec<u64>>) {
+        let mut order = Vec::new();
+        let mut sums: HashMap<[u8; 16], Vec<u64>> = HashMap::new();
+        for (scene, counts) in self.scenes.iter().zip(&self.counts) {
+            let sum = sums.entry(*scene).or_insert_with(|| {
+                order.push(*scene);
+                vec![0; num_classes]
+            });
+            sum.iter_mut().zip(counts).for_each(|(s, c)| *s += c);
+        }
+        let sums = order.iter().map(|scene| sums.remove(scene).unwrap()).collect();
+        (order, sums)
+    }
+}
+
+/// Instance statistics of a lidarseg thing category, as `per_category_panoptic_stats` in `panoptic_utils.py`.
+#[derive(Clone, Debug, Default)]
+pub struct CategoryInstances {
+    pub frames_per_instance: Vec<i32>,
+    pub points_per_instance: Vec<i32>, // One per instance and frame
+}
+
+/// Panoptic statistics over frames, as `get_panoptic_instances_stats` in `panoptic_utils.py`.
+#[derive(Clone, Debug, Default)]
+pub struct PanopticStats {
+    pub instances_per_frame: Vec<i32>, // Only frames with thing instances
+    pub categories: Vec<CategoryInstances>,
+}
+
+/// Mean and population standard deviation, or zeros if empty.
+pub fn mean_std(values: &[i32]) -> (f64, f64) {
+    if values.is_empty() {
+        return (0.0, 0.0);
+    }
+    let n = values.len() as f64;
+    let mean = values.iter().map(|&v| v as f64).sum::<f64>() / n;
+    let var = values.iter().map(|&v| (v as f64 - mean).powi(2)).sum::<f64>() / n;
+    (mean, var.sqrt())
+}
+
+/// Returns the sorted distinct values with their number of occurrences, as `np.unique(..., return_counts=True)`.
+pub fn unique_counts(values: &[i32]) -> Vec<[i32; 2]> {
+    let mut sorted = values.to_vec();
+    sorted.sort_unstable();
+    let mut out: Vec<[i32; 2]> = Vec::new();
+    for v in sorted {
+        match out.last_mut() {
+            Some(last) if last[0] == v => last[1] += 1,
+            _ => out.push([v, 1]),
+        }
+    }
+    out
+}
+
+/// Counts the points of each distinct panoptic label, sorted by label.
+fn label_counts(mut labels: Vec<u16>) -> Vec<(u16, i32)> {
+    labels.sort_unstable();
+    let mut out: Vec<(u16, i32)> = Vec::new();
+    for label in labels {
+        match out.last_mut() {
+            Some(last) if last.0 == label => last.1 += 1,
+            _ => out.push((label, 1)),
+        }
+    }
+    out
+}
+
+impl Tables {
+    /// Number of lidarseg categories, from the largest category index.
+    pub(crate) fn lidarseg_class_count(&self) -> usize {
+        self.category.iter().filter_map(|cat| cat.index).map(|index| index as usize + 1).max().unwrap_or_default()
+    }
+
+    /// Names of the lidarseg categories by index, as `lidarseg_idx2name_mapping`.
+    pub(crate) fn lidarseg_class_names(&self) -> Vec<String> {
+        let mut names = vec![String::new(); self.lidarseg_class_count()];
+        for cat in self.category.iter() {
+            if let Some(index) = cat.index {
+                names[index as usize] = cat.name.to_string();
+            }
+        }
+        names
+    }
+
+    /// Sample data with labels from an extension, in the order of its table, optionally restricted to scenes.
+    fn labelled_sample_data(
+        &self, source: LabelSource, scenes: Option<&HashSet<[u8; 16]>>,
+    ) -> PyResult<Vec<(&SampleData<'_>, [u8; 16])>> {
+        let tokens: Vec<[u8; 16]> = match source {
+            LabelSource::Lidarseg => self
+                .lidarseg
+                .as_ref()
+                .ok_or_else(|| PyValueError::new_err("lidarseg not loaded due to missing 'lidarseg.json'"))?
+                .iter()
+                .map(|rec| rec.sample_data_token)
+                .collect(),
+            LabelSource::Panoptic => self
+                .panoptic
+                .as_ref()
+                .ok_or_else(|| PyValueError::new_err("panoptic is not loaded due to missing 'panoptic.json'"))?
+                .iter()
+                .map(|rec| rec.sample_data_token)
+                .collect(),
+        };
+        Ok(tokens
+            .iter()
+            .filter_map(|tok| self.sample_data.get(tok))
+            .map(|sd| (sd, self.sample.get(&sd.sample_token).unwrap().scene_token))
+            .filter(|(_, scene)| scenes.is_none_or(|scenes| scenes.contains(scene)))
+            .collect())
+    }
+
+    /// Reads the labels of every labelled sample in parallel and counts the points of each lidarseg category.
+    pub(crate) fn semantic_histograms(
+        &self, source: LabelSource, scenes: Option<&HashSet<[u8; 16]>>,
+    ) -> PyResult<SemanticHistograms> {
+        let num_classes = self.lidarseg_class_count();
+        let sds = self.labelled_sample_data(source, scenes)?;
+        let counts = sds
+            .par_iter()
+            .map(|(sd, _)| {
+                let labels = match source {
+                    LabelSource::Lidarseg => self.lidarseg_labels(sd, false)?,
+                    LabelSource::Panoptic => {
+                        self.panoptic_labels(sd, false)?.iter().map(|&l| (l / PANOPTIC_DIVISOR) as u8).collect()
+                    }
+                };
+                let mut counts = vec![0u64; num_classes];
+                for label in labels {
+                    *counts.get_mut(label as usize).ok_or_else(|| {
+                        PyValueError::new_err(format!(
+                            "Label {label} of {} is not one of the {num_classes} lidarseg categories",
+                            hex::encode(sd.token)
+                        ))
+                    })? += 1;
+                }
+                Ok(counts)
+            })
+            .collect::<PyResult<_>>()?;
+        Ok(SemanticHistograms {
+            samples: sds.iter().map(|(sd, _)| sd.sample_token).collect(),
+            scenes: sds.iter().map(|(_, scene)| *scene).collect(),
+            counts,
+        })
+    }
+
+    /// Reads the panoptic labels of every labelled sample in parallel and gathers the instance statistics of each thing
+    /// category. Instance ids are unique within a scene.
+    ///
+    /// As in `panoptic_utils.py`, frames are grouped by scene, in order of first appearance, and the instances of a
+    /// category are sorted by scene, then instance id.
+    pub(crate) fn panoptic_instance_stats(&self, scenes: Option<&HashSet<[u8; 16]>>) -> PyResult<PanopticStats> {
+        let sds = self.labelled_sample_data(LabelSource::Panoptic, scenes)?;
+        let frames: Vec<Vec<(u16, i32)>> =
+            sds.par_iter().map(|(sd, _)| self.panoptic_labels(sd, false).map(label_counts)).collect::<PyResult<_>>()?;
+
+        let mut scene_ids = HashMap::new();
+        for (_, scene) in &sds {
+            let next_id = scene_ids.len();
+            scene_ids.entry(*scene).or_insert(next_id);
+        }
+        let mut order: Vec<_> = (0..sds.len()).map(|i| (scene_ids[&sds[i].1], i)).collect();
+        order.sort_unstable();
+
+        let mut stats = PanopticStats {
+            categories: vec![CategoryInstances::default(); STUFF_START_CLASS_ID as usize],
+            ..Default::default()
+        };
+        // Frames spanned by each instance of a category, keyed by scene id and instance id.
+        let mut spans: Vec<BTreeMap<(usize, u16), i32>> = vec![BTreeMap::new(); STUFF_START_CLASS_ID as usize];
+        for (scene_id, i) in order {
+            let mut instances = 0;
+            for &(label, points) in &frames[i] {
+                let (category, instance) = ((label / PANOPTIC_DIVISOR) as usize, label % PANOPTIC_DIVISOR);
+                if category == 0 || category >= STUFF_START_CLASS_ID as usize {
+                    continue;
+                }
+                instances += 1;
+                stats.categories[category].points_per_instance.push(points);
+                *spans[category].entry((scene_id, instance)).or_default() += 1;
+            }
+            if instances > 0 {
+                stats.instances_per_frame.push(instances);
+            }
+        }
+        for (category, spans) in spans.into_iter().enumerate() {
+            stats.categories[category].frames_per_instance = spans.into_values().collect();
+        }
+        Ok(stats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unique_counts_sorts_the_values() {
+        // `np.array(np.unique([3, 1, 3, -2, 3, 1], return_counts=True)).T`
+        assert_eq!(unique_counts(&[3, 1, 3, -2, 3, 1]), [[-2, 1], [1, 2], [3, 3]]);
+        assert!(unique_counts(&[]).is_empty());
+    }
+
+    #[test]
+    fn mean_std_is_the_population_deviation() {
+        // `np.mean` and `np.std` of [2, 4, 4, 4, 5, 5, 7, 9].
+        assert_eq!(mean_std(&[2, 4, 4, 4, 5, 5, 7, 9]), (5.0, 2.0));
+        assert_eq!(mean_std(&[7]), (7.0, 0.0));
+        assert_eq!(mean_std(&[]), (0.0, 0.0));
+    }
+
+    #[test]
+    fn label_counts_counts_each_panoptic_label() {
+        let (car_1, car_2) = (17 * PANOPTIC_DIVISOR + 1, 17 * PANOPTIC_DIVISOR + 2);
+        let labels = vec![car_2, 24 * PANOPTIC_DIVISOR, car_1, car_2, 0, car_2];
+        assert_eq!(label_counts(labels), [(0, 1), (car_1, 1), (car_2, 3), (24 * PANOPTIC_DIVISOR, 1)]);
+        assert!(label_counts(Vec::new()).is_empty());
+    }
+}
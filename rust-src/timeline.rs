@@ -0,0 +1,147 @@
+use ahash::HashMap;
+use enum_map::EnumMap;
+
+use crate::common::SensorChannel;
+use crate::nusc::Tables;
+
+/// Records of one kind sorted by timestamp.
+pub struct TimeSeries {
+    entries: Box<[(u64, [u8; 16])]>,
+}
+
+impl TimeSeries {
+    fn new(mut entries: Vec<(u64, [u8; 16])>) -> Self {
+        entries.sort_unstable();
+        Self { entries: entries.into_boxed_slice() }
+    }
+
+    /// Returns the record closest in time to `timestamp`, preferring the earlier one on ties.
+    pub fn nearest(&self, timestamp: u64) -> Option<(u64, [u8; 16])> {
+        let idx = self.entries.partition_point(|(t, _)| *t < timestamp);
+        let after = self.entries.get(idx);
+        let before = idx.checked_sub(1).and_then(|i| self.entries.get(i));
+        match (before, after) {
+            (Some(b), Some(a)) if timestamp - b.0 <= a.0 - timestamp => Some(*b),
+            (_, Some(a)) => Some(*a),
+            (b, None) => b.copied(),
+        }
+    }
+
+    /// Returns the records with `start <= timestamp <= end`, in temporal order.
+    pub fn window(&self, start: u64, end: u64) -> &[(u64, [u8; 16])] {
+        let lo = self.entries.partition_point(|(t, _)| *t < start);
+        let hi = self.entries.partition_point(|(t, _)| *t <= end);
+        &self.entries[lo..hi.max(lo)]
+    }
+}
+
+/// Per-channel sample data, sorted by timestamp.
+pub type ChannelSeries = EnumMap<SensorChannel, TimeSeries>;
+
+/// Timestamp indexes over sample data and samples, both dataset-wide and per scene.
+pub struct Timeline {
+    pub channels: ChannelSeries,
+    pub samples: TimeSeries,
+    pub scene_channels: HashMap<[u8; 16], ChannelSeries>,
+    pub scene_samples: HashMap<[u8; 16], TimeSeries>,
+}
+
+impl Timeline {
+    pub fn build(tables: &Tables) -> Self {
+        let mut channels: EnumMap<SensorChannel, Vec<_>> = EnumMap::default();
+        let mut scene_channels: HashMap<[u8; 16], EnumMap<SensorChannel, Vec<_>>> = HashMap::default();
+        for sd in &tables.sample_data {
+            let scene_token = tables.sample.get(&sd.sample_token).unwrap().scene_token;
+            channels[sd.channel].push((sd.timestamp, sd.token));
+            scene_channels.entry(scene_token).or_default()[sd.channel].push((sd.timestamp, sd.token));
+        }
+
+        let mut samples = Vec::with_capacity(tables.sample.len());
+        let mut scene_samples: HashMap<[u8; 16], Vec<_>> = HashMap::default();
+        for sample in &tables.sample {
+            samples.push((sample.timestamp, sample.token));
+            scene_samples.entry(sample.scene_token).or_default().push((sample.timestamp, sample.token));
+        }
+
+        Self {
+            channels: channels.map(|_, entries| TimeSeries::new(entries)),
+            samples: TimeSeries::new(samples),
+            scene_channels: scene_channels
+                .into_iter()
+                .map(|(scene, series)| (scene, series.map(|_, entries| TimeSeries::new(entries))))
+                .collect(),
+            scene_samples: scene_samples
+                .into_iter()
+                .map(|(scene, entries)| (scene, TimeSeries::new(entries)))
+                .collect(),
+        }
+    }
+
+    /// Returns the sample data series of a channel, restricted to a scene if given.
+    pub fn channel(&self, channel: SensorChannel, scene: Option<&[u8; 16]>) -> Option<&TimeSeries> {
+        match scene {
+            Some(scene) => self.scene_channels.get(scene).map(|series| &series[channel]),
+            None => Some(&self.channels[channel]),
+        }
+    }
+
+    /// Returns the sample series, restricted to a scene if given.
+    pub fn samples(&self, scene: Option<&[u8; 16]>) -> Option<&TimeSeries> {
+        match scene {
+            Some(scene) => self.scene_samples.get(scene),
+            None => Some(&self.samples),
+        }
+    }
+}
+
+impl Tables {
+    pub(crate) fn timeline(&self) -> &Timeline {
+        self.timeline.get_or_init(|| Timeline::build(self))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A series with records at 10, 20, 20 and 40, given out of order, whose tokens are their position in time.
+    fn series() -> TimeSeries {
+        TimeSeries::new(vec![(40, [3; 16]), (20, [2; 16]), (10, [0; 16]), (20, [1; 16])])
+    }
+
+    fn tokens(entries: &[(u64, [u8; 16])]) -> Vec<u8> {
+        entries.iter().map(|(_, tok)| tok[0]).collect()
+    }
+
+    #[test]
+    fn nearest_prefers_the_earlier_record_on_ties() {
+        let series = series();
+        let nearest = |ts| series.nearest(ts).map(|(t, tok)| (t, tok[0]));
+        assert_eq!(nearest(15), Some((10, 0)));
+        assert_eq!(nearest(30), Some((20, 2)));
+        assert_eq!(nearest(31), Some((40, 3)));
+        assert_eq!(nearest(20), Some((20, 1)));
+        assert_eq!(nearest(0), Some((10, 0)));
+        assert_eq!(nearest(u64::MAX), Some((40, 3)));
+        assert_eq!(TimeSeries::new(Vec::new()).nearest(10), None);
+    }
+
+    #[test]
+    fn window_includes_both_boundaries() {
+        let series = series();
+        assert_eq!(tokens(series.window(10, 40)), [0, 1, 2, 3]);
+        assert_eq!(tokens(series.window(20, 20)), [1, 2]);
+        assert_eq!(tokens(series.window(11, 39)), [1, 2]);
+        assert_eq!(tokens(series.window(0, u64::MAX)), [0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn window_is_empty_outside_the_records() {
+        let series = series();
+        assert!(series.window(21, 39).is_empty());
+        assert!(series.window(0, 9).is_empty());
+        assert!(series.window(41, 50).is_empty());
+        assert!(series.window(40, 10).is_empty());
+        assert!(TimeSeries::new(Vec::new()).window(0, u64::MAX).is_empty());
+    }
+}
@@ -0,0 +1,212 @@
+use std::f64::consts::PI;
+
+use ahash::{HashMap, HashMapExt};
+use rayon::prelude::*;
+
+use crate::geometry::Transform;
+use crate::nusc::Tables;
+
+/// Default half-width of the window used for derivatives, in seconds.
+pub const DERIVATIVE_WINDOW: f64 = 0.1;
+
+/// Derivatives of the ego motion at some timestamps.
+#[derive(Clone, Debug, Default)]
+pub struct Dynamics {
+    pub speed: Vec<f64>,        // m/s
+    pub acceleration: Vec<f64>, // m/s², of the speed
+    pub yaw_rate: Vec<f64>,     // rad/s, counter-clockwise
+}
+
+/// Ego poses of a scene or log sorted by timestamp, interpolated in between.
+pub struct EgoTrajectory {
+    pub timestamps: Box<[u64]>,
+    pub poses: Box<[Transform]>,
+}
+
+impl EgoTrajectory {
+    fn new(mut entries: Vec<(u64, Transform)>) -> Self {
+        entries.sort_unstable_by_key(|(t, _)| *t);
+        // Sensors captured at the same time share the ego pose.
+        entries.dedup_by_key(|(t, _)| *t);
+        let (timestamps, poses): (Vec<_>, Vec<_>) = entries.into_iter().unzip();
+        Self { timestamps: timestamps.into_boxed_slice(), poses: poses.into_boxed_slice() }
+    }
+
+    pub fn start(&self) -> u64 {
+        self.timestamps.first().copied().unwrap_or_default()
+    }
+
+    pub fn end(&self) -> u64 {
+        self.timestamps.last().copied().unwrap_or_default()
+    }
+
+    /// Returns the ego pose at `timestamp`, or `None` outside of the trajectory.
+    pub fn pose_at(&self, timestamp: u64) -> Option<Transform> {
+        let idx = self.timestamps.partition_point(|t| *t < timestamp);
+        let after = *self.timestamps.get(idx)?;
+        if after == timestamp {
+            return Some(self.poses[idx]);
+        }
+        let before = *self.timestamps.get(idx.checked_sub(1)?)?;
+        let amount = (timestamp - before) as f64 / (after - before) as f64;
+        Some(Transform::interpolate(&self.poses[idx - 1], &self.poses[idx], amount))
+    }
+
+    /// Pose at a timestamp in seconds, clamped to the trajectory.
+    fn pose_at_secs(&self, secs: f64) -> Transform {
+        let timestamp = (secs * 1e6).round().clamp(self.start() as f64, self.end() as f64) as u64;
+        self.pose_at(timestamp).unwrap()
+    }
+
+    /// Velocity vector and yaw at `secs`, by central differences over `window` seconds on either side.
+    fn motion_at(&self, secs: f64, window: f64) -> ([f64; 3], f64) {
+        let (t0, t1) = (self.clamp_secs(secs - window), self.clamp_secs(secs + window));
+        if t1 <= t0 {
+            return ([f64::NAN; 3], f64::NAN);
+        }
+        let (p0, p1) = (self.pose_at_secs(t0), self.pose_at_secs(t1));
+        let velocity = [0, 1, 2].map(|i| (p1.translation[i] - p0.translation[i]) / (t1 - t0));
+        let yaw_rate = wrap_angle(p1.rotation.yaw() - p0.rotation.yaw()) / (t1 - t0);
+        (velocity, yaw_rate)
+    }
+
+    fn clamp_secs(&self, secs: f64) -> f64 {
+        secs.clamp(self.start() as f64 * 1e-6, self.end() as f64 * 1e-6)
+    }
+
+    /// Speed, acceleration and yaw rate at each timestamp, by central differences over `window` seconds on either
+    /// side, narrowed at the ends of the trajectory. Timestamps outside of the trajectory get NaN.
+    pub fn dynamics(&self, timestamps: &[u64], window: f64) -> Dynamics {
+        let speed_at = |secs: f64| {
+            let ([vx, vy, vz], _) = self.motion_at(secs, window);
+            (vx * vx + vy * vy + vz * vz).sqrt()
+        };
+        let mut dynamics = Dynamics::default();
+        for &timestamp in timestamps {
+            if timestamp < self.start() || timestamp > self.end() {
+                dynamics.speed.push(f64::NAN);
+                dynamics.acceleration.push(f64::NAN);
+                dynamics.yaw_rate.push(f64::NAN);
+                continue;
+            }
+            let secs = timestamp as f64 * 1e-6;
+            let (_, yaw_rate) = self.motion_at(secs, window);
+            let (t0, t1) = (self.clamp_secs(secs - window), self.clamp_secs(secs + window));
+            let acceleration = if t1 > t0 { (speed_at(t1) - speed_at(t0)) / (t1 - t0) } else { f64::NAN };
+            dynamics.speed.push(speed_at(secs));
+            dynamics.acceleration.push(acceleration);
+            dynamics.yaw_rate.push(yaw_rate);
+        }
+        dynamics
+    }
+}
+
+/// Wraps an angle into `[-pi, pi)`.
+fn wrap_angle(angle: f64) -> f64 {
+    (angle + PI).rem_euclid(2.0 * PI) - PI
+}
+
+/// Ego trajectories of every scene and every log.
+pub struct Trajectories {
+    pub scenes: HashMap<[u8; 16], EgoTrajectory>,
+    pub logs: HashMap<[u8; 16], EgoTrajectory>,
+}
+
+impl Trajectories {
+    pub fn build(tables: &Tables) -> Self {
+        let mut scene_poses: HashMap<[u8; 16], Vec<(u64, Transform)>> = HashMap::new();
+        for sd in &tables.sample_data {
+            let scene_token = tables.sample.get(&sd.sample_token).unwrap().scene_token;
+            let pose = tables.ego_pose.get(&sd.ego_pose_token).unwrap();
+            let transform = Transform::from_pose(pose.translation, pose.rotation);
+            scene_poses.entry(scene_token).or_default().push((pose.timestamp, transform));
+        }
+
+        let mut log_poses: HashMap<[u8; 16], Vec<(u64, Transform)>> = HashMap::new();
+        for (scene_token, poses) in &scene_poses {
+            let log_token = tables.scene.get(scene_token).unwrap().log_token;
+            log_poses.entry(log_token).or_default().extend_from_slice(poses);
+        }
+
+        let build = |poses: HashMap<[u8; 16], Vec<_>>| {
+            poses.into_par_iter().map(|(token, entries)| (token, EgoTrajectory::new(entries))).collect()
+        };
+        Self { scenes: build(scene_poses), logs: build(log_poses) }
+    }
+
+    /// Returns the trajectory of a scene or log token.
+    pub fn get(&self, token: &[u8; 16]) -> Option<&EgoTrajectory> {
+        self.scenes.get(token).or_else(|| self.logs.get(token))
+    }
+}
+
+impl Tables {
+    pub(crate) fn trajectories(&self) -> &Trajectories {
+        self.trajectories.get_or_init(|| Trajectories::build(self))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::FRAC_PI_2;
+
+    use super::*;
+    use crate::geometry::Quaternion;
+
+    fn pose(x: f64, yaw: f64) -> Transform {
+        Transform::new(Quaternion::new((yaw / 2.0).cos(), 0.0, 0.0, (yaw / 2.0).sin()), [x, 0.0, 0.0])
+    }
+
+    /// Trajectory sampled every 0.1 seconds over one second, at positions `x(t)` with a yaw of `yaw(t)`.
+    fn trajectory(x: impl Fn(f64) -> f64, yaw: impl Fn(f64) -> f64) -> EgoTrajectory {
+        EgoTrajectory::new(
+            (0..=10).rev().map(|i| (i * 100_000, pose(x(i as f64 * 0.1), yaw(i as f64 * 0.1)))).collect(),
+        )
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-9, "{actual} != {expected}");
+    }
+
+    #[test]
+    fn pose_at_interpolates_between_timestamps() {
+        let trajectory = EgoTrajectory::new(vec![(1_000_000, pose(10.0, FRAC_PI_2)), (0, pose(0.0, 0.0))]);
+        assert_eq!(trajectory.pose_at(1_000_000), Some(pose(10.0, FRAC_PI_2)));
+        let between = trajectory.pose_at(250_000).unwrap();
+        assert_close(between.translation[0], 2.5);
+        assert_close(between.rotation.yaw(), FRAC_PI_2 / 4.0);
+        assert_eq!(trajectory.pose_at(1_000_001), None);
+        assert_eq!(EgoTrajectory::new(vec![(5, pose(0.0, 0.0))]).pose_at(4), None);
+    }
+
+    #[test]
+    fn dynamics_of_a_uniform_acceleration() {
+        // x = t², so the speed is 2t and the acceleration 2.
+        let dynamics = trajectory(|t| t * t, |_| 0.0).dynamics(&[500_000, 0, 1_000_001], DERIVATIVE_WINDOW);
+        assert_close(dynamics.speed[0], 1.0);
+        assert_close(dynamics.acceleration[0], 2.0);
+        assert_close(dynamics.yaw_rate[0], 0.0);
+        // The windows are narrowed at the start: the speed spans [0, 0.1], and that at 0.1 seconds spans [0, 0.2].
+        assert_close(dynamics.speed[1], 0.1);
+        assert_close(dynamics.acceleration[1], (0.2 - 0.1) / 0.1);
+        assert!(dynamics.speed[2].is_nan() && dynamics.acceleration[2].is_nan() && dynamics.yaw_rate[2].is_nan());
+    }
+
+    #[test]
+    fn yaw_rate_wraps_around_pi() {
+        // Turning left by 0.5 rad/s from 3 rad crosses pi at about 0.28 seconds.
+        let left = trajectory(|_| 0.0, |t| wrap_angle(3.0 + 0.5 * t)).dynamics(&[300_000], DERIVATIVE_WINDOW);
+        assert_close(left.yaw_rate[0], 0.5);
+        let right = trajectory(|_| 0.0, |t| wrap_angle(-3.0 - 0.5 * t)).dynamics(&[300_000], DERIVATIVE_WINDOW);
+        assert_close(right.yaw_rate[0], -0.5);
+        assert_close(left.speed[0], 0.0);
+    }
+
+    #[test]
+    fn wrap_angle_is_in_minus_pi_to_pi() {
+        assert_close(wrap_angle(PI), -PI);
+        assert_close(wrap_angle(-PI), -PI);
+        assert_close(wrap_angle(3.0 * FRAC_PI_2), -FRAC_PI_2);
+        assert_close(wrap_angle(-5.0 * FRAC_PI_2), -FRAC_PI_2);
+    }
+}
@@ -0,0 +1,168 @@
+use std::sync::Arc;
+
+use rayon::prelude::*;
+
+use crate::common::SensorChannel;
+use crate::domain::{EgoPose, Sample, SampleAnnotation, SampleData};
+use crate::geometry::{Frame, Quaternion};
+use crate::nusc::Tables;
+use crate::table::Table;
+
+/// Default of `max_time_diff` in `NuScenes.box_velocity`, in seconds.
+pub const MAX_TIME_DIFF: f64 = 1.5;
+
+/// Estimates the velocity of an annotation in the global frame, as `NuScenes.box_velocity`.
+///
+/// Uses the centered difference between the previous and next annotations if possible, and the difference to
+/// whichever exists otherwise. Returns NaN for single-frame tracks and for gaps longer than `max_time_diff`, which is
+/// doubled for centered differences.
+pub fn box_velocity(
+    anns: &Table<SampleAnnotation>, samples: &Table<Sample>, ann: &SampleAnnotation, max_time_diff: f64,
+) -> [f64; 3] {
+    let prev = ann.prev.and_then(|tok| anns.get(&tok));
+    let next = ann.next.and_then(|tok| anns.get(&tok));
+    let (first, last, max_time_diff) = match (prev, next) {
+        (None, None) => return [f64::NAN; 3],
+        (Some(prev), Some(next)) => (prev, next, max_time_diff * 2.0),
+        (Some(prev), None) => (prev, ann, max_time_diff),
+        (None, Some(next)) => (ann, next, max_time_diff),
+    };
+
+    let timestamp = |ann: &SampleAnnotation| 1e-6 * samples.get(&ann.sample_token).unwrap().timestamp as f64;
+    let time_diff = timestamp(last) - timestamp(first);
+    if time_diff > max_time_diff {
+        return [f64::NAN; 3];
+    }
+    [0, 1, 2].map(|i| (last.translation[i] as f64 - first.translation[i] as f64) / time_diff)
+}
+
+/// Rotation of the ego vehicle at a sample, taken from the ego pose of its keyframe lidar sweep.
+fn ego_rotation(sample_data: &Table<SampleData>, ego_pose: &Table<EgoPose>, sample: &Sample) -> Option<Quaternion> {
+    let sd = sample_data.get(&sample.data[SensorChannel::LidarTop])?;
+    ego_pose.get(&sd.ego_pose_token).map(|pose| Quaternion::from_f32(pose.rotation))
+}
+
+/// Rotates a global velocity into the ego frame of the annotation's sample.
+fn to_ego(
+    samples: &Table<Sample>, sample_data: &Table<SampleData>, ego_pose: &Table<EgoPose>, ann: &SampleAnnotation,
+    velocity: [f64; 3],
+) -> [f64; 3] {
+    let sample = samples.get(&ann.sample_token).unwrap();
+    match ego_rotation(sample_data, ego_pose, sample) {
+        Some(rotation) => rotation.inverse().rotate(velocity),
+        None => [f64::NAN; 3],
+    }
+}
+
+/// Stores the velocities with the default `max_time_diff` in every annotation, in both the global and ego frames.
+pub fn annotate_velocities(
+    anns: &mut Table<SampleAnnotation>, samples: &Table<Sample>, sample_data: &Table<SampleData>,
+    ego_pose: &Table<EgoPose>,
+) {
+    let velocities: Vec<_> = anns
+        .data
+        .par_iter()
+        .map(|ann| {
+            let velocity = box_velocity(anns, samples, ann, MAX_TIME_DIFF);
+            let velocity_ego = to_ego(samples, sample_data, ego_pose, ann, velocity);
+            (velocity.map(|v| v as f32), velocity_ego.map(|v| v as f32))
+        })
+        .collect();
+
+    // The table has just been built, so it is not shared yet.
+    let data = Arc::get_mut(&mut anns.data).unwrap();
+    for (ann, (velocity, velocity_ego)) in data.iter_mut().zip(velocities) {
+        ann.velocity = Some(velocity);
+        ann.velocity_ego = Some(velocity_ego);
+    }
+}
+
+impl Tables {
+    /// Velocity of an annotation in the global or ego frame, reusing the stored one for the default `max_time_diff`.
+    pub(crate) fn annotation_velocity(&self, ann: &SampleAnnotation, frame: Frame, max_time_diff: f64) -> [f32; 3] {
+        let stored = match frame {
+            Frame::Ego => ann.velocity_ego,
+            _ => ann.velocity,
+        };
+        if let Some(velocity) = stored.filter(|_| max_time_diff == MAX_TIME_DIFF) {
+            return velocity;
+        }
+
+        let velocity = box_velocity(&self.sample_ann, &self.sample, ann, max_time_diff);
+        let velocity = match frame {
+            Frame::Ego => to_ego(&self.sample, &self.sample_data, &self.ego_pose, ann, velocity),
+            _ => velocity,
+        };
+        velocity.map(|v| v as f32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use super::*;
+    use crate::common::{CategoryKind, Visibility};
+
+    fn sample(id: u8, timestamp: u64) -> Sample {
+        let (token, scene_token) = ([id; 16], [0; 16]);
+        Sample { token, scene_token, prev: None, next: None, timestamp, data: Default::default(), anns: Box::new([]) }
+    }
+
+    fn ann(id: u8, sample: u8, prev: Option<u8>, next: Option<u8>, x: f32) -> SampleAnnotation<'static> {
+        SampleAnnotation {
+            token: [id; 16],
+            sample_token: [sample; 16],
+            instance_token: [0; 16],
+            attribute_tokens: Box::new([]),
+            prev: prev.map(|id| [id; 16]),
+            next: next.map(|id| [id; 16]),
+            visibility: Visibility::V80_100,
+            translation: [x, 0.0, 0.0],
+            rotation: [1.0, 0.0, 0.0, 0.0],
+            size: [1.0; 3],
+            num_lidar_pts: 0,
+            num_radar_pts: 0,
+            category: CategoryKind::VehicleCar,
+            category_name: Cow::Borrowed("vehicle.car"),
+            attribute_names: None,
+            class_id: None,
+            class_name: None,
+            velocity: None,
+            velocity_ego: None,
+        }
+    }
+
+    /// A track of three annotations at 0, 0.5 and 1.5 seconds moving along x, and a single-frame track.
+    fn tables() -> (Table<SampleAnnotation<'static>>, Table<Sample>) {
+        let samples = [sample(10, 0), sample(11, 500_000), sample(12, 1_500_000)];
+        let anns = [
+            ann(1, 10, None, Some(2), 0.0),
+            ann(2, 11, Some(1), Some(3), 1.0),
+            ann(3, 12, Some(2), None, 4.0),
+            ann(4, 11, None, None, 7.0),
+        ];
+        (Table::new(Box::new(anns)), Table::new(Box::new(samples)))
+    }
+
+    #[test]
+    fn box_velocity_uses_the_neighbours_at_the_ends_of_a_track() {
+        let (anns, samples) = tables();
+        let velocity = |id| box_velocity(&anns, &samples, anns.get(&[id; 16]).unwrap(), MAX_TIME_DIFF);
+        assert_eq!(velocity(1), [2.0, 0.0, 0.0]);
+        assert_eq!(velocity(2), [4.0 / 1.5, 0.0, 0.0]);
+        assert_eq!(velocity(3), [3.0, 0.0, 0.0]);
+        assert!(velocity(4).iter().all(|v| v.is_nan()));
+    }
+
+    #[test]
+    fn box_velocity_is_nan_beyond_max_time_diff() {
+        let (anns, samples) = tables();
+        let velocity = |id, max_time_diff| box_velocity(&anns, &samples, anns.get(&[id; 16]).unwrap(), max_time_diff);
+        assert!(velocity(3, 0.9).iter().all(|v| v.is_nan()));
+        assert_eq!(velocity(3, 1.0), [3.0, 0.0, 0.0]);
+        // Centered differences span two gaps, so the limit is doubled.
+        assert!(velocity(2, 0.7).iter().all(|v| v.is_nan()));
+        assert_eq!(velocity(2, 0.75), [4.0 / 1.5, 0.0, 0.0]);
+    }
+}